@@ -0,0 +1,62 @@
+use array_box::ArrayBox;
+use compositor::{ImageId, ImageSource};
+use log::error;
+use value_box::{BorrowedPtr, OwnedPtr, ReturnBoxerResult};
+
+/// Creates an image source copying unpremultiplied RGBA pixels, 4 bytes per pixel
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_image_source_new_rgba(
+    width: u32,
+    height: u32,
+    pixels: BorrowedPtr<ArrayBox<u8>>,
+) -> OwnedPtr<ImageSource> {
+    pixels
+        .with_ref_ok(|pixels| {
+            let pixels = pixels.to_slice();
+            if pixels.len() != width as usize * height as usize * 4 {
+                error!(
+                    "Expected {} bytes for a {}x{} image, got {}",
+                    width as usize * height as usize * 4,
+                    width,
+                    height,
+                    pixels.len()
+                );
+                return OwnedPtr::null();
+            }
+            OwnedPtr::new(ImageSource::rgba(width, height, pixels))
+        })
+        .or_log(OwnedPtr::null())
+}
+
+/// Creates an image source copying encoded PNG, JPEG or WebP bytes
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_image_source_new_encoded(
+    bytes: BorrowedPtr<ArrayBox<u8>>,
+) -> OwnedPtr<ImageSource> {
+    bytes
+        .with_ref_ok(|bytes| OwnedPtr::new(ImageSource::encoded(bytes.to_slice())))
+        .or_log(OwnedPtr::null())
+}
+
+/// Replaces the content id computed from the pixels with the one provided by the host
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_image_source_set_id(
+    mut source: BorrowedPtr<ImageSource>,
+    id: ImageId,
+) {
+    source
+        .with_mut_ok(|source| {
+            *source = source.clone().with_id(id);
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_image_source_get_id(source: BorrowedPtr<ImageSource>) -> ImageId {
+    source.with_ref_ok(|source| source.id()).or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_image_source_drop(source: OwnedPtr<ImageSource>) {
+    drop(source);
+}
@@ -3,7 +3,7 @@ use std::sync::Arc;
 use string_box::StringBox;
 use value_box::{BorrowedPtr, OwnedPtr, ReturnBoxerResult};
 
-use compositor::Layer;
+use compositor::{Layer, LayerTreeDump};
 
 #[unsafe(no_mangle)]
 pub extern "C" fn compositor_layer_clone(
@@ -23,6 +23,32 @@ pub extern "C" fn compositor_layer_debug(
         .or_log(OwnedPtr::null())
 }
 
+/// Print a layer tree with one indented line per layer
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_dump(layer: BorrowedPtr<Arc<dyn Layer>>) -> OwnedPtr<StringBox> {
+    layer
+        .with_ref_ok(|layer| {
+            OwnedPtr::new(StringBox::from_string(
+                LayerTreeDump::new(layer.as_ref()).to_string(),
+            ))
+        })
+        .or_log(OwnedPtr::null())
+}
+
+/// Export a layer tree as a Graphviz DOT digraph
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_dump_dot(
+    layer: BorrowedPtr<Arc<dyn Layer>>,
+) -> OwnedPtr<StringBox> {
+    layer
+        .with_ref_ok(|layer| {
+            OwnedPtr::new(StringBox::from_string(
+                LayerTreeDump::new(layer.as_ref()).to_dot(),
+            ))
+        })
+        .or_log(OwnedPtr::null())
+}
+
 #[unsafe(no_mangle)]
 pub extern "C" fn compositor_layer_with_layers(
     layer: BorrowedPtr<Arc<dyn Layer>>,
@@ -0,0 +1,160 @@
+use std::sync::Arc;
+
+use log::error;
+use value_box::{BorrowedPtr, OwnedPtr, ReturnBoxerResult};
+
+use compositor::{Geometry, Layer, LayerTreeBuilder, Matrix, Picture, Point, Shadow};
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_builder_new() -> OwnedPtr<LayerTreeBuilder> {
+    OwnedPtr::new(LayerTreeBuilder::new())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_builder_push_offset(
+    mut builder: BorrowedPtr<LayerTreeBuilder>,
+    offset_x: f32,
+    offset_y: f32,
+) {
+    builder
+        .with_mut_ok(|builder| {
+            builder.push_offset(Point::new_f32(offset_x, offset_y));
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_builder_push_clip(
+    mut builder: BorrowedPtr<LayerTreeBuilder>,
+    geometry: BorrowedPtr<Geometry>,
+    offset_x: f32,
+    offset_y: f32,
+) {
+    builder
+        .with_mut(|builder| {
+            geometry.with_clone_ok(|geometry| {
+                builder.push_clip(geometry, Point::new_f32(offset_x, offset_y));
+            })
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_builder_push_opacity(
+    mut builder: BorrowedPtr<LayerTreeBuilder>,
+    alpha: f32,
+) {
+    builder
+        .with_mut_ok(|builder| {
+            builder.push_opacity(alpha);
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_builder_push_transform(
+    mut builder: BorrowedPtr<LayerTreeBuilder>,
+    matrix: OwnedPtr<Matrix>,
+) {
+    builder
+        .with_mut(|builder| {
+            matrix.with_value_ok(|matrix| {
+                builder.push_transform(matrix);
+            })
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_builder_push_shadow(
+    mut builder: BorrowedPtr<LayerTreeBuilder>,
+    shadow: OwnedPtr<Shadow>,
+) {
+    builder
+        .with_mut(|builder| {
+            shadow.with_value_ok(|shadow| {
+                builder.push_shadow(shadow);
+            })
+        })
+        .log();
+}
+
+/// Open any container layer, for example a clip layer with a dynamic offset
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_builder_push_layer(
+    mut builder: BorrowedPtr<LayerTreeBuilder>,
+    layer: BorrowedPtr<Arc<dyn Layer>>,
+) {
+    builder
+        .with_mut(|builder| {
+            layer.with_clone_ok(|layer| {
+                builder.push_layer(layer);
+            })
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_builder_add_layer(
+    mut builder: BorrowedPtr<LayerTreeBuilder>,
+    layer: BorrowedPtr<Arc<dyn Layer>>,
+) {
+    builder
+        .with_mut(|builder| {
+            layer.with_clone_ok(|layer| {
+                builder.add_layer(layer);
+            })
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_builder_add_picture(
+    mut builder: BorrowedPtr<LayerTreeBuilder>,
+    picture: BorrowedPtr<Arc<dyn Picture>>,
+    needs_cache: bool,
+) {
+    builder
+        .with_mut(|builder| {
+            picture.with_clone_ok(|picture| {
+                builder.add_picture(picture, needs_cache);
+            })
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_builder_pop(mut builder: BorrowedPtr<LayerTreeBuilder>) {
+    builder
+        .with_mut_ok(|builder| {
+            builder.pop();
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_builder_depth(builder: BorrowedPtr<LayerTreeBuilder>) -> usize {
+    builder.with_ref_ok(|builder| builder.depth()).or_log(0)
+}
+
+/// Consume the builder and return the built tree,
+/// or a null pointer if pushes and pops were unbalanced
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_builder_build(
+    builder: OwnedPtr<LayerTreeBuilder>,
+) -> OwnedPtr<Arc<dyn Layer>> {
+    builder
+        .with_value_ok(|builder| match builder.build() {
+            Ok(layer) => OwnedPtr::new(layer),
+            Err(build_error) => {
+                error!("Failed to build layer tree: {}", build_error);
+                OwnedPtr::null()
+            }
+        })
+        .or_log(OwnedPtr::null())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_builder_drop(ptr: OwnedPtr<LayerTreeBuilder>) {
+    drop(ptr);
+}
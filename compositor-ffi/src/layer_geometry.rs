@@ -0,0 +1,63 @@
+use std::sync::Arc;
+
+use value_box::{BorrowedPtr, OwnedPtr, ReturnBoxerResult};
+
+use compositor::{Fill, Geometry, GeometryLayer, Layer, Stroke};
+
+/// Creates a new geometry layer without fill or stroke consuming the geometry
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_geometry_layer_new(
+    geometry: OwnedPtr<Geometry>,
+) -> OwnedPtr<Arc<dyn Layer>> {
+    geometry
+        .with_value_ok(|geometry| {
+            OwnedPtr::new(Arc::new(GeometryLayer::new(geometry)) as Arc<dyn Layer>)
+        })
+        .or_log(OwnedPtr::null())
+}
+
+/// Sets the fill of the geometry layer consuming the fill
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_geometry_layer_set_fill(
+    mut geometry_layer: BorrowedPtr<Arc<dyn Layer>>,
+    fill: OwnedPtr<Fill>,
+) {
+    geometry_layer
+        .with_mut(|layer| {
+            fill.with_value_ok(|fill| {
+                let updated = {
+                    let geometry_layer = layer
+                        .any()
+                        .downcast_ref::<GeometryLayer>()
+                        .expect("Is not a geometry layer!");
+
+                    geometry_layer.with_fill(fill).clone_arc()
+                };
+                *layer = updated;
+            })
+        })
+        .log();
+}
+
+/// Sets the stroke of the geometry layer consuming the stroke
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_geometry_layer_set_stroke(
+    mut geometry_layer: BorrowedPtr<Arc<dyn Layer>>,
+    stroke: OwnedPtr<Stroke>,
+) {
+    geometry_layer
+        .with_mut(|layer| {
+            stroke.with_value_ok(|stroke| {
+                let updated = {
+                    let geometry_layer = layer
+                        .any()
+                        .downcast_ref::<GeometryLayer>()
+                        .expect("Is not a geometry layer!");
+
+                    geometry_layer.with_stroke(stroke).clone_arc()
+                };
+                *layer = updated;
+            })
+        })
+        .log();
+}
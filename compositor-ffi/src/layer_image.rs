@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use value_box::{BorrowedPtr, OwnedPtr, ReturnBoxerResult};
+
+use compositor::{
+    Color, Extent, ImageFilterMode, ImageLayer, ImageMipmapMode, ImageSampling, ImageSource, Layer,
+    Rectangle,
+};
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_image_layer_new(
+    source: OwnedPtr<ImageSource>,
+    width: f32,
+    height: f32,
+) -> OwnedPtr<Arc<dyn Layer>> {
+    source
+        .with_value_ok(|source| {
+            OwnedPtr::new(
+                Arc::new(ImageLayer::new(source, Extent::new(width, height))) as Arc<dyn Layer>,
+            )
+        })
+        .or_log(OwnedPtr::null())
+}
+
+/// Draws the image as a nine-patch with the center given in image pixels
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_image_layer_set_nine_patch(
+    mut image_layer: BorrowedPtr<Arc<dyn Layer>>,
+    left: f32,
+    top: f32,
+    width: f32,
+    height: f32,
+) {
+    image_layer
+        .with_mut_ok(|layer| {
+            let updated = {
+                let image_layer = layer
+                    .any()
+                    .downcast_ref::<ImageLayer>()
+                    .expect("Is not an image layer!");
+
+                image_layer
+                    .with_nine_patch(Rectangle::new(left, top, width, height))
+                    .clone_arc()
+            };
+            *layer = updated;
+        })
+        .log();
+}
+
+/// Filter: 0 - nearest, 1 - linear.
+/// Mipmap: 0 - none, 1 - nearest, 2 - linear.
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_image_layer_set_sampling(
+    mut image_layer: BorrowedPtr<Arc<dyn Layer>>,
+    filter: u32,
+    mipmap: u32,
+) {
+    let filter = match filter {
+        0 => ImageFilterMode::Nearest,
+        _ => ImageFilterMode::Linear,
+    };
+    let mipmap = match mipmap {
+        1 => ImageMipmapMode::Nearest,
+        2 => ImageMipmapMode::Linear,
+        _ => ImageMipmapMode::None,
+    };
+
+    image_layer
+        .with_mut_ok(|layer| {
+            let updated = {
+                let image_layer = layer
+                    .any()
+                    .downcast_ref::<ImageLayer>()
+                    .expect("Is not an image layer!");
+
+                image_layer
+                    .with_sampling(ImageSampling::new(filter, mipmap))
+                    .clone_arc()
+            };
+            *layer = updated;
+        })
+        .log();
+}
+
+/// Sets a color to fill the layer with while the image is being decoded
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_image_layer_set_placeholder(
+    mut image_layer: BorrowedPtr<Arc<dyn Layer>>,
+    argb: u32,
+) {
+    image_layer
+        .with_mut_ok(|layer| {
+            let updated = {
+                let image_layer = layer
+                    .any()
+                    .downcast_ref::<ImageLayer>()
+                    .expect("Is not an image layer!");
+
+                image_layer
+                    .with_placeholder(Color::from_argb(argb))
+                    .clone_arc()
+            };
+            *layer = updated;
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_image_layer_get_image_id(
+    image_layer: BorrowedPtr<Arc<dyn Layer>>,
+) -> u64 {
+    image_layer
+        .with_ref_ok(|layer| {
+            layer
+                .any()
+                .downcast_ref::<ImageLayer>()
+                .expect("Is not an image layer!")
+                .source()
+                .id()
+        })
+        .or_log(0)
+}
@@ -0,0 +1,238 @@
+use std::sync::Arc;
+
+use array_box::ArrayBox;
+use value_box::{BorrowedPtr, OwnedPtr, ReturnBoxerResult};
+
+use compositor::{Extent, Layer, ListLayer, ListRowIndex, Picture, PictureLayer, Point};
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_list_layer_default() -> OwnedPtr<Arc<dyn Layer>> {
+    OwnedPtr::new(Arc::new(ListLayer::default()) as Arc<dyn Layer>)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_list_layer_new(
+    camera_x: f32,
+    camera_y: f32,
+    width: f32,
+    height: f32,
+) -> OwnedPtr<Arc<dyn Layer>> {
+    OwnedPtr::new(Arc::new(ListLayer::new(
+        Point::new(camera_x, camera_y),
+        Extent::new(width, height),
+    )) as Arc<dyn Layer>)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_list_layer_add_row(
+    layer: BorrowedPtr<Arc<dyn Layer>>,
+    height: f32,
+) -> ListRowIndex {
+    layer
+        .with_ref_ok(|layer| {
+            let list_layer = layer
+                .any()
+                .downcast_ref::<ListLayer>()
+                .expect("Is not a list layer!");
+
+            list_layer.add_row(height)
+        })
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_list_layer_add_rows(
+    layer: BorrowedPtr<Arc<dyn Layer>>,
+    heights: BorrowedPtr<ArrayBox<f32>>,
+) {
+    layer
+        .with_ref(|layer| {
+            heights.with_ref_ok(|heights| {
+                let list_layer = layer
+                    .any()
+                    .downcast_ref::<ListLayer>()
+                    .expect("Is not a list layer!");
+
+                list_layer.add_rows(heights.to_slice().iter().copied());
+            })
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_list_layer_set_row_height(
+    layer: BorrowedPtr<Arc<dyn Layer>>,
+    index: ListRowIndex,
+    height: f32,
+) {
+    layer
+        .with_ref_ok(|layer| {
+            let list_layer = layer
+                .any()
+                .downcast_ref::<ListLayer>()
+                .expect("Is not a list layer!");
+
+            list_layer.set_row_height(index, height);
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_list_layer_row_count(layer: BorrowedPtr<Arc<dyn Layer>>) -> usize {
+    layer
+        .with_ref_ok(|layer| {
+            let list_layer = layer
+                .any()
+                .downcast_ref::<ListLayer>()
+                .expect("Is not a list layer!");
+
+            list_layer.row_count()
+        })
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_list_layer_total_height(layer: BorrowedPtr<Arc<dyn Layer>>) -> f32 {
+    layer
+        .with_ref_ok(|layer| {
+            let list_layer = layer
+                .any()
+                .downcast_ref::<ListLayer>()
+                .expect("Is not a list layer!");
+
+            list_layer.total_height().into()
+        })
+        .or_log(0.0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_list_layer_row_top(
+    layer: BorrowedPtr<Arc<dyn Layer>>,
+    index: ListRowIndex,
+) -> f32 {
+    layer
+        .with_ref_ok(|layer| {
+            let list_layer = layer
+                .any()
+                .downcast_ref::<ListLayer>()
+                .expect("Is not a list layer!");
+
+            list_layer
+                .row_top(index)
+                .map(|top| top.into())
+                .unwrap_or(0.0)
+        })
+        .or_log(0.0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_list_layer_row_set_picture(
+    list_layer: BorrowedPtr<Arc<dyn Layer>>,
+    index: ListRowIndex,
+    picture: BorrowedPtr<Arc<dyn Picture>>,
+) {
+    list_layer
+        .with_ref_ok(|list_layer| {
+            picture.with_clone_ok(|picture| {
+                let list_layer = list_layer
+                    .any()
+                    .downcast_ref::<ListLayer>()
+                    .expect("Is not a list layer!");
+
+                let picture_layer = PictureLayer::new(picture, false);
+
+                if let Some(row) = list_layer.find_row(index) {
+                    row.set_picture(picture_layer);
+                }
+            })
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_list_layer_row_set_picture_layer(
+    list_layer: BorrowedPtr<Arc<dyn Layer>>,
+    index: ListRowIndex,
+    picture_layer: BorrowedPtr<Arc<dyn Layer>>,
+) {
+    list_layer
+        .with_ref_ok(|list_layer| {
+            picture_layer.with_ref_ok(|picture_layer| {
+                let list_layer = list_layer
+                    .any()
+                    .downcast_ref::<ListLayer>()
+                    .expect("Is not a list layer!");
+
+                let picture_layer = picture_layer
+                    .any()
+                    .downcast_ref::<PictureLayer>()
+                    .expect("Is not a picture layer!");
+
+                if let Some(row) = list_layer.find_row(index) {
+                    row.set_picture(picture_layer.clone());
+                }
+            })
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_list_layer_set_camera_position(
+    mut list_layer: BorrowedPtr<Arc<dyn Layer>>,
+    camera_x: f32,
+    camera_y: f32,
+) {
+    list_layer
+        .with_mut_ok(|layer| {
+            let updated = {
+                let list_layer = layer
+                    .any()
+                    .downcast_ref::<ListLayer>()
+                    .expect("Is not a list layer!");
+
+                list_layer
+                    .with_camera_position(Point::new(camera_x, camera_y))
+                    .clone_arc()
+            };
+            *layer = updated;
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_list_layer_visible_rows(
+    list_layer: BorrowedPtr<Arc<dyn Layer>>,
+    mut indices: BorrowedPtr<ArrayBox<u32>>,
+) {
+    list_layer
+        .with_ref_ok(|list_layer| {
+            indices.with_mut_ok(|indices| {
+                let list_layer = list_layer
+                    .any()
+                    .downcast_ref::<ListLayer>()
+                    .expect("Is not a list layer!");
+
+                indices.set_vector(list_layer.visible_rows().collect());
+            })
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_list_layer_visible_rows_without_pictures(
+    list_layer: BorrowedPtr<Arc<dyn Layer>>,
+    mut indices: BorrowedPtr<ArrayBox<u32>>,
+) {
+    list_layer
+        .with_ref_ok(|list_layer| {
+            indices.with_mut_ok(|indices| {
+                let list_layer = list_layer
+                    .any()
+                    .downcast_ref::<ListLayer>()
+                    .expect("Is not a list layer!");
+
+                indices.set_vector(list_layer.visible_rows_without_picture());
+            })
+        })
+        .log();
+}
@@ -1,4 +1,4 @@
-use compositor::{Layer, Picture, PictureLayer};
+use compositor::{Layer, Picture, PictureCacheHint, PictureLayer};
 use std::sync::Arc;
 use value_box::{BorrowedPtr, OwnedPtr, ReturnBoxerResult};
 
@@ -14,6 +14,27 @@ pub extern "C" fn compositor_picture_layer_new(
         .or_log(OwnedPtr::null())
 }
 
+/// Cache hint: 0 - auto, 1 - always, 2 - never.
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_picture_layer_new_with_cache_hint(
+    picture: BorrowedPtr<Arc<dyn Picture>>,
+    cache_hint: u32,
+) -> OwnedPtr<Arc<dyn Layer>> {
+    let cache_hint = match cache_hint {
+        1 => PictureCacheHint::Always,
+        2 => PictureCacheHint::Never,
+        _ => PictureCacheHint::Auto,
+    };
+
+    picture
+        .with_clone_ok(|picture| {
+            OwnedPtr::new(
+                Arc::new(PictureLayer::new_with_cache_hint(picture, cache_hint)) as Arc<dyn Layer>,
+            )
+        })
+        .or_log(OwnedPtr::null())
+}
+
 #[unsafe(no_mangle)]
 pub extern "C" fn compositor_picture_layer_needs_cache(
     layer: BorrowedPtr<Arc<dyn Layer>>,
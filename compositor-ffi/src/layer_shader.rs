@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use array_box::ArrayBox;
+use string_box::StringBox;
+use value_box::{BorrowedPtr, OwnedPtr, ReturnBoxerResult};
+
+use compositor::{
+    Geometry, ImageSource, Layer, RuntimeShader, ShaderId, ShaderLayer, ShaderUniform,
+};
+
+/// Creates a new shader layer from the SkSL source consuming the geometry
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_shader_layer_new(
+    source: BorrowedPtr<StringBox>,
+    geometry: OwnedPtr<Geometry>,
+) -> OwnedPtr<Arc<dyn Layer>> {
+    source
+        .with_ref(|source| {
+            geometry.with_value_ok(|geometry| {
+                let shader = RuntimeShader::new(source.to_string());
+                OwnedPtr::new(Arc::new(ShaderLayer::new(shader, geometry)) as Arc<dyn Layer>)
+            })
+        })
+        .or_log(OwnedPtr::null())
+}
+
+/// Kind: 0 - float, 1 - float2, 2 - float3, 3 - float4, 4 - float2x2, 5 - float3x3, 6 - float4x4.
+/// Matrices are in column-major order. Values that do not match the kind are ignored.
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_shader_layer_set_uniform(
+    mut shader_layer: BorrowedPtr<Arc<dyn Layer>>,
+    name: BorrowedPtr<StringBox>,
+    kind: u32,
+    values: BorrowedPtr<ArrayBox<f32>>,
+) {
+    shader_layer
+        .with_mut(|layer| {
+            name.with_ref(|name| {
+                values.with_ref_ok(|values| {
+                    let Some(uniform) = shader_uniform(kind, values.to_slice()) else {
+                        return;
+                    };
+
+                    let updated = {
+                        let shader_layer = layer
+                            .any()
+                            .downcast_ref::<ShaderLayer>()
+                            .expect("Is not a shader layer!");
+
+                        shader_layer
+                            .with_uniform(name.to_string(), uniform)
+                            .clone_arc()
+                    };
+                    *layer = updated;
+                })
+            })
+        })
+        .log();
+}
+
+/// Binds an image to a child shader declared in the source
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_shader_layer_set_child(
+    mut shader_layer: BorrowedPtr<Arc<dyn Layer>>,
+    name: BorrowedPtr<StringBox>,
+    image: BorrowedPtr<ImageSource>,
+) {
+    shader_layer
+        .with_mut(|layer| {
+            name.with_ref(|name| {
+                image.with_clone_ok(|image| {
+                    let updated = {
+                        let shader_layer = layer
+                            .any()
+                            .downcast_ref::<ShaderLayer>()
+                            .expect("Is not a shader layer!");
+
+                        shader_layer.with_child(name.to_string(), image).clone_arc()
+                    };
+                    *layer = updated;
+                })
+            })
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_shader_layer_get_shader_id(
+    shader_layer: BorrowedPtr<Arc<dyn Layer>>,
+) -> ShaderId {
+    shader_layer
+        .with_ref_ok(|layer| {
+            layer
+                .any()
+                .downcast_ref::<ShaderLayer>()
+                .expect("Is not a shader layer!")
+                .shader()
+                .id()
+        })
+        .or_log(0)
+}
+
+fn shader_uniform(kind: u32, values: &[f32]) -> Option<ShaderUniform> {
+    let uniform = match kind {
+        0 => ShaderUniform::Float(*values.first()?),
+        1 => ShaderUniform::Float2(values.try_into().ok()?),
+        2 => ShaderUniform::Float3(values.try_into().ok()?),
+        3 => ShaderUniform::Float4(values.try_into().ok()?),
+        4 => ShaderUniform::Float2x2(values.try_into().ok()?),
+        5 => ShaderUniform::Float3x3(values.try_into().ok()?),
+        6 => ShaderUniform::Float4x4(values.try_into().ok()?),
+        _ => return None,
+    };
+    Some(uniform)
+}
@@ -4,8 +4,8 @@ use array_box::ArrayBox;
 use value_box::{BorrowedPtr, OwnedPtr, ReturnBoxerResult};
 
 use compositor::{
-    Extent, Layer, Picture, PictureLayer, Point, TiledFigureId, TiledLayer, TiledLayerFigure,
-    TiledLayerScaleFactor,
+    Color, Extent, Layer, Picture, PictureLayer, Point, Rectangle, TiledFigureId, TiledLayer,
+    TiledLayerFigure, TiledLayerScaleFactor, TiledLayerTileStatistics, TiledLayerTransaction,
 };
 
 #[unsafe(no_mangle)]
@@ -54,6 +54,133 @@ pub extern "C" fn compositor_tiled_layer_add_figure(
         .log();
 }
 
+/// Return false if there is no figure with a given id
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_remove_figure(
+    layer: BorrowedPtr<Arc<dyn Layer>>,
+    id: TiledFigureId,
+) -> bool {
+    layer
+        .with_ref_ok(|layer| {
+            let tiled_layer = layer
+                .any()
+                .downcast_ref::<TiledLayer>()
+                .expect("Is not a tiled layer!");
+
+            tiled_layer.remove_figure(id)
+        })
+        .or_log(false)
+}
+
+/// Move and resize a figure keeping its picture. Return false if there is no such figure
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_update_figure(
+    layer: BorrowedPtr<Arc<dyn Layer>>,
+    id: TiledFigureId,
+    offset_x: f32,
+    offset_y: f32,
+    width: f32,
+    height: f32,
+) -> bool {
+    layer
+        .with_ref_ok(|layer| {
+            let tiled_layer = layer
+                .any()
+                .downcast_ref::<TiledLayer>()
+                .expect("Is not a tiled layer!");
+
+            tiled_layer.update_figure(
+                id,
+                Point::new_f32(offset_x, offset_y),
+                Extent::new(width, height),
+            )
+        })
+        .or_log(false)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_transaction_new() -> OwnedPtr<TiledLayerTransaction> {
+    OwnedPtr::new(TiledLayerTransaction::new())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_transaction_add_figure(
+    mut transaction: BorrowedPtr<TiledLayerTransaction>,
+    id: TiledFigureId,
+    offset_x: f32,
+    offset_y: f32,
+    width: f32,
+    height: f32,
+) {
+    transaction
+        .with_mut_ok(|transaction| {
+            transaction.add_figure(TiledLayerFigure::new(
+                id,
+                Point::new_f32(offset_x, offset_y),
+                Extent::new(width, height),
+            ));
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_transaction_remove_figure(
+    mut transaction: BorrowedPtr<TiledLayerTransaction>,
+    id: TiledFigureId,
+) {
+    transaction
+        .with_mut_ok(|transaction| {
+            transaction.remove_figure(id);
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_transaction_update_figure(
+    mut transaction: BorrowedPtr<TiledLayerTransaction>,
+    id: TiledFigureId,
+    offset_x: f32,
+    offset_y: f32,
+    width: f32,
+    height: f32,
+) {
+    transaction
+        .with_mut_ok(|transaction| {
+            transaction.update_figure(
+                id,
+                Point::new_f32(offset_x, offset_y),
+                Extent::new(width, height),
+            );
+        })
+        .log();
+}
+
+/// Consume the transaction and apply its changes to the layer.
+/// Return the amount of changes that found their figure
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_apply_transaction(
+    layer: BorrowedPtr<Arc<dyn Layer>>,
+    transaction: OwnedPtr<TiledLayerTransaction>,
+) -> usize {
+    layer
+        .with_ref(|layer| {
+            transaction.with_value_ok(|transaction| {
+                let tiled_layer = layer
+                    .any()
+                    .downcast_ref::<TiledLayer>()
+                    .expect("Is not a tiled layer!");
+
+                tiled_layer.apply_transaction(transaction)
+            })
+        })
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_transaction_drop(ptr: OwnedPtr<TiledLayerTransaction>) {
+    drop(ptr);
+}
+
 #[unsafe(no_mangle)]
 pub extern "C" fn compositor_tiled_layer_figure_set_picture(
     tiled_layer: BorrowedPtr<Arc<dyn Layer>>,
@@ -105,6 +232,45 @@ pub extern "C" fn compositor_tiled_layer_figure_set_picture_layer(
         .log();
 }
 
+/// Remove cached pictures of the tiles overlapping a given region in layer coordinates.
+/// Return the amount of invalidated tiles
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_invalidate_region(
+    tiled_layer: BorrowedPtr<Arc<dyn Layer>>,
+    left: f32,
+    top: f32,
+    width: f32,
+    height: f32,
+) -> usize {
+    tiled_layer
+        .with_ref_ok(|tiled_layer| {
+            let tiled_layer = tiled_layer
+                .any()
+                .downcast_ref::<TiledLayer>()
+                .expect("Is not a tiled layer!");
+
+            tiled_layer.invalidate_region(&Rectangle::new(left, top, width, height))
+        })
+        .or_log(0)
+}
+
+/// Remove all cached tile pictures. Return the amount of invalidated tiles
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_invalidate_all(
+    tiled_layer: BorrowedPtr<Arc<dyn Layer>>,
+) -> usize {
+    tiled_layer
+        .with_ref_ok(|tiled_layer| {
+            let tiled_layer = tiled_layer
+                .any()
+                .downcast_ref::<TiledLayer>()
+                .expect("Is not a tiled layer!");
+
+            tiled_layer.invalidate_all()
+        })
+        .or_log(0)
+}
+
 #[unsafe(no_mangle)]
 pub extern "C" fn compositor_tiled_layer_set_camera_position(
     mut tiled_layer: BorrowedPtr<Arc<dyn Layer>>,
@@ -188,6 +354,225 @@ pub extern "C" fn compositor_tiled_layer_set_scale_out_factor(
         .log();
 }
 
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_set_zoom_level_budget(
+    tiled_layer: BorrowedPtr<Arc<dyn Layer>>,
+    budget: usize,
+) {
+    tiled_layer
+        .with_ref_ok(|tiled_layer| {
+            let tiled_layer = tiled_layer
+                .any()
+                .downcast_ref::<TiledLayer>()
+                .expect("Is not a tiled layer!");
+
+            tiled_layer.set_zoom_level_budget(budget);
+        })
+        .log();
+}
+
+/// Limit how many tile pictures the layer retains over all zoom levels,
+/// evicting the ones farthest from the camera first
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_set_tile_budget(
+    tiled_layer: BorrowedPtr<Arc<dyn Layer>>,
+    budget: usize,
+) {
+    tiled_layer
+        .with_ref_ok(|tiled_layer| {
+            let tiled_layer = tiled_layer
+                .any()
+                .downcast_ref::<TiledLayer>()
+                .expect("Is not a tiled layer!");
+
+            tiled_layer.set_tile_budget(Some(budget));
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_set_unlimited_tile_budget(
+    tiled_layer: BorrowedPtr<Arc<dyn Layer>>,
+) {
+    tiled_layer
+        .with_ref_ok(|tiled_layer| {
+            let tiled_layer = tiled_layer
+                .any()
+                .downcast_ref::<TiledLayer>()
+                .expect("Is not a tiled layer!");
+
+            tiled_layer.set_tile_budget(None);
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_tile_statistics(
+    tiled_layer: BorrowedPtr<Arc<dyn Layer>>,
+) -> OwnedPtr<TiledLayerTileStatistics> {
+    tiled_layer
+        .with_ref_ok(|tiled_layer| {
+            let tiled_layer = tiled_layer
+                .any()
+                .downcast_ref::<TiledLayer>()
+                .expect("Is not a tiled layer!");
+
+            OwnedPtr::new(tiled_layer.tile_statistics())
+        })
+        .or_log(OwnedPtr::null())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_tile_statistics_tiles(
+    statistics: BorrowedPtr<TiledLayerTileStatistics>,
+) -> usize {
+    statistics
+        .with_ref_ok(|statistics| statistics.tiles())
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_tile_statistics_zoom_levels(
+    statistics: BorrowedPtr<TiledLayerTileStatistics>,
+) -> usize {
+    statistics
+        .with_ref_ok(|statistics| statistics.zoom_levels())
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_tile_statistics_picture_bytes(
+    statistics: BorrowedPtr<TiledLayerTileStatistics>,
+) -> usize {
+    statistics
+        .with_ref_ok(|statistics| statistics.picture_bytes())
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_tile_statistics_evictions(
+    statistics: BorrowedPtr<TiledLayerTileStatistics>,
+) -> u64 {
+    statistics
+        .with_ref_ok(|statistics| statistics.evictions())
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_tile_statistics_drop(
+    ptr: OwnedPtr<TiledLayerTileStatistics>,
+) {
+    drop(ptr);
+}
+
+/// Limit how many missing tiles are recorded per frame while scaled tiles
+/// of another zoom level can be drawn in their place
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_set_tile_recording_budget(
+    mut tiled_layer: BorrowedPtr<Arc<dyn Layer>>,
+    budget: usize,
+) {
+    tiled_layer
+        .with_mut_ok(|layer| {
+            let updated = {
+                let tiled_layer = layer
+                    .any()
+                    .downcast_ref::<TiledLayer>()
+                    .expect("Is not a tiled layer!");
+
+                tiled_layer
+                    .with_tile_recording_budget(Some(budget))
+                    .clone_arc()
+            };
+            *layer = updated;
+        })
+        .log();
+}
+
+/// Record all missing visible tiles in the same frame
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_set_unlimited_tile_recording(
+    mut tiled_layer: BorrowedPtr<Arc<dyn Layer>>,
+) {
+    tiled_layer
+        .with_mut_ok(|layer| {
+            let updated = {
+                let tiled_layer = layer
+                    .any()
+                    .downcast_ref::<TiledLayer>()
+                    .expect("Is not a tiled layer!");
+
+                tiled_layer.with_tile_recording_budget(None).clone_arc()
+            };
+            *layer = updated;
+        })
+        .log();
+}
+
+/// Set the amount of tiles around the viewport that are recorded and rasterized ahead of time
+/// when the compositor cache rasterizes in the background
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_set_prefetch_margin(
+    mut tiled_layer: BorrowedPtr<Arc<dyn Layer>>,
+    margin: u32,
+) {
+    tiled_layer
+        .with_mut_ok(|layer| {
+            let updated = {
+                let tiled_layer = layer
+                    .any()
+                    .downcast_ref::<TiledLayer>()
+                    .expect("Is not a tiled layer!");
+
+                tiled_layer.with_prefetch_margin(margin).clone_arc()
+            };
+            *layer = updated;
+        })
+        .log();
+}
+
+/// Fill visible tiles that wait to be recorded with a given color
+/// under the scaled tiles of other zoom levels
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_set_tile_placeholder(
+    mut tiled_layer: BorrowedPtr<Arc<dyn Layer>>,
+    argb: u32,
+) {
+    tiled_layer
+        .with_mut_ok(|layer| {
+            let updated = {
+                let tiled_layer = layer
+                    .any()
+                    .downcast_ref::<TiledLayer>()
+                    .expect("Is not a tiled layer!");
+
+                tiled_layer
+                    .with_tile_placeholder(Some(Color::from_argb(argb)))
+                    .clone_arc()
+            };
+            *layer = updated;
+        })
+        .log();
+}
+
+/// Return true if some visible tiles are not yet recorded at the current zoom level,
+/// meaning that the layer should be composed again
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_tiled_layer_has_missing_visible_tiles(
+    tiled_layer: BorrowedPtr<Arc<dyn Layer>>,
+) -> bool {
+    tiled_layer
+        .with_ref_ok(|tiled_layer| {
+            let tiled_layer = tiled_layer
+                .any()
+                .downcast_ref::<TiledLayer>()
+                .expect("Is not a tiled layer!");
+
+            tiled_layer.has_missing_visible_tiles()
+        })
+        .or_log(false)
+}
+
 #[unsafe(no_mangle)]
 pub extern "C" fn compositor_tiled_layer_visible_figures(
     tiled_layer: BorrowedPtr<Arc<dyn Layer>>,
@@ -4,37 +4,57 @@
 extern crate cfg_if;
 
 pub use geometry::*;
+pub use image::*;
 pub use layer::*;
+pub use layer_builder::*;
 pub use layer_clip::*;
+pub use layer_geometry::*;
+pub use layer_image::*;
 pub use layer_leftover::*;
+pub use layer_list::*;
 pub use layer_offset::*;
 pub use layer_offset_dynamic::*;
 pub use layer_opacity::*;
 pub use layer_picture::*;
+pub use layer_shader::*;
 pub use layer_shadow::*;
 pub use layer_texture::*;
 pub use layer_tiled::*;
 pub use layer_transformation::*;
 pub use matrix::*;
+pub use optimizer::*;
+pub use paint::*;
 pub use picture::*;
 pub use shadow::*;
+pub use statistics::*;
+pub use validation::*;
 
 mod geometry;
+mod image;
 mod layer;
+mod layer_builder;
 mod layer_clip;
+mod layer_geometry;
+mod layer_image;
 mod layer_leftover;
+mod layer_list;
 mod layer_offset;
 mod layer_offset_dynamic;
 mod layer_opacity;
 mod layer_picture;
+mod layer_shader;
 mod layer_shadow;
 mod layer_texture;
 mod layer_tiled;
 mod layer_transformation;
 mod matrix;
+mod optimizer;
+mod paint;
 mod path;
 mod picture;
 mod shadow;
+mod statistics;
+mod validation;
 
 cfg_if! {
     if #[cfg(feature = "phlow")] {
@@ -0,0 +1,37 @@
+use std::sync::Arc;
+
+use value_box::{BorrowedPtr, OwnedPtr, ReturnBoxerResult};
+
+use compositor::{Layer, OptimizedLayerTree, optimize_layer_tree};
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_optimize(
+    layer: BorrowedPtr<Arc<dyn Layer>>,
+) -> OwnedPtr<OptimizedLayerTree> {
+    layer
+        .with_ref_ok(|layer| OwnedPtr::new(optimize_layer_tree(layer)))
+        .or_log(OwnedPtr::null())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_optimized_layer_tree_get_layer(
+    optimized: BorrowedPtr<OptimizedLayerTree>,
+) -> OwnedPtr<Arc<dyn Layer>> {
+    optimized
+        .with_ref_ok(|optimized| OwnedPtr::new(optimized.layer().clone()))
+        .or_log(OwnedPtr::null())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_optimized_layer_tree_removed_layers(
+    optimized: BorrowedPtr<OptimizedLayerTree>,
+) -> usize {
+    optimized
+        .with_ref_ok(|optimized| optimized.removed_layers())
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_optimized_layer_tree_drop(ptr: OwnedPtr<OptimizedLayerTree>) {
+    drop(ptr);
+}
@@ -0,0 +1,155 @@
+use array_box::ArrayBox;
+use compositor::{
+    Color, Fill, GradientStop, Point, Scalar, Stroke, StrokeCap, StrokeDash, StrokeJoin,
+};
+use value_box::{BorrowedPtr, OwnedPtr, ReturnBoxerResult};
+
+fn gradient_stops(colors: &[u32], positions: &[f32]) -> Vec<GradientStop> {
+    colors
+        .iter()
+        .zip(positions.iter())
+        .map(|(argb, position)| GradientStop::new(*position, Color::from_argb(*argb)))
+        .collect()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_fill_solid(argb: u32) -> OwnedPtr<Fill> {
+    OwnedPtr::new(Fill::solid(Color::from_argb(argb)))
+}
+
+/// Colors and positions must have the same length, extra colors or positions are ignored
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_fill_linear_gradient(
+    start_x: f32,
+    start_y: f32,
+    end_x: f32,
+    end_y: f32,
+    colors: BorrowedPtr<ArrayBox<u32>>,
+    positions: BorrowedPtr<ArrayBox<f32>>,
+) -> OwnedPtr<Fill> {
+    colors
+        .with_ref(|colors| {
+            positions.with_ref_ok(|positions| {
+                OwnedPtr::new(Fill::linear_gradient(
+                    Point::new(start_x, start_y),
+                    Point::new(end_x, end_y),
+                    gradient_stops(colors.to_slice(), positions.to_slice()),
+                ))
+            })
+        })
+        .or_log(OwnedPtr::null())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_fill_radial_gradient(
+    center_x: f32,
+    center_y: f32,
+    radius: f32,
+    colors: BorrowedPtr<ArrayBox<u32>>,
+    positions: BorrowedPtr<ArrayBox<f32>>,
+) -> OwnedPtr<Fill> {
+    colors
+        .with_ref(|colors| {
+            positions.with_ref_ok(|positions| {
+                OwnedPtr::new(Fill::radial_gradient(
+                    Point::new(center_x, center_y),
+                    radius,
+                    gradient_stops(colors.to_slice(), positions.to_slice()),
+                ))
+            })
+        })
+        .or_log(OwnedPtr::null())
+}
+
+/// Angles are in degrees
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_fill_sweep_gradient(
+    center_x: f32,
+    center_y: f32,
+    start_angle: f32,
+    end_angle: f32,
+    colors: BorrowedPtr<ArrayBox<u32>>,
+    positions: BorrowedPtr<ArrayBox<f32>>,
+) -> OwnedPtr<Fill> {
+    colors
+        .with_ref(|colors| {
+            positions.with_ref_ok(|positions| {
+                OwnedPtr::new(Fill::sweep_gradient(
+                    Point::new(center_x, center_y),
+                    start_angle,
+                    end_angle,
+                    gradient_stops(colors.to_slice(), positions.to_slice()),
+                ))
+            })
+        })
+        .or_log(OwnedPtr::null())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_fill_drop(fill: OwnedPtr<Fill>) {
+    drop(fill);
+}
+
+/// Creates a new stroke consuming the fill
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_stroke_new(fill: OwnedPtr<Fill>, width: f32) -> OwnedPtr<Stroke> {
+    fill.with_value_ok(|fill| OwnedPtr::new(Stroke::new(fill, width)))
+        .or_log(OwnedPtr::null())
+}
+
+/// Cap: 0 - butt, 1 - round, 2 - square
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_stroke_set_cap(mut stroke: BorrowedPtr<Stroke>, cap: u32) {
+    let cap = match cap {
+        1 => StrokeCap::Round,
+        2 => StrokeCap::Square,
+        _ => StrokeCap::Butt,
+    };
+
+    stroke
+        .with_mut_ok(|stroke| {
+            *stroke = stroke.clone().with_cap(cap);
+        })
+        .log();
+}
+
+/// Join: 0 - miter, 1 - round, 2 - bevel
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_stroke_set_join(mut stroke: BorrowedPtr<Stroke>, join: u32) {
+    let join = match join {
+        1 => StrokeJoin::Round,
+        2 => StrokeJoin::Bevel,
+        _ => StrokeJoin::Miter,
+    };
+
+    stroke
+        .with_mut_ok(|stroke| {
+            *stroke = stroke.clone().with_join(join);
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_stroke_set_dash(
+    mut stroke: BorrowedPtr<Stroke>,
+    intervals: BorrowedPtr<ArrayBox<f32>>,
+    phase: f32,
+) {
+    stroke
+        .with_mut(|stroke| {
+            intervals.with_ref_ok(|intervals| {
+                let intervals = intervals
+                    .to_slice()
+                    .iter()
+                    .map(|interval| Scalar::from(*interval))
+                    .collect();
+                *stroke = stroke.clone().with_dash(StrokeDash::new(intervals, phase));
+            })
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_stroke_drop(stroke: OwnedPtr<Stroke>) {
+    drop(stroke);
+}
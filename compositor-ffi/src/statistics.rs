@@ -0,0 +1,150 @@
+use std::sync::Arc;
+
+use string_box::StringBox;
+use value_box::{BorrowedPtr, OwnedPtr, ReturnBoxerResult};
+
+use compositor::{Layer, LayerStatistics};
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_statistics(
+    layer: BorrowedPtr<Arc<dyn Layer>>,
+) -> OwnedPtr<LayerStatistics> {
+    layer
+        .with_ref_ok(|layer| OwnedPtr::new(LayerStatistics::collect(layer.as_ref())))
+        .or_log(OwnedPtr::null())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_statistics_count_layers(
+    statistics: BorrowedPtr<LayerStatistics>,
+) -> usize {
+    statistics
+        .with_ref_ok(|statistics| statistics.count_layers())
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_statistics_count_layers_of_type(
+    statistics: BorrowedPtr<LayerStatistics>,
+    layer_type: BorrowedPtr<StringBox>,
+) -> usize {
+    statistics
+        .with_ref(|statistics| {
+            layer_type.with_ref_ok(|layer_type| {
+                statistics.count_layers_of_type(layer_type.to_string().as_str())
+            })
+        })
+        .or_log(0)
+}
+
+/// The amount of distinct layer types present in the tree
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_statistics_count_layer_types(
+    statistics: BorrowedPtr<LayerStatistics>,
+) -> usize {
+    statistics
+        .with_ref_ok(|statistics| statistics.layer_counts().count())
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_statistics_get_layer_type(
+    statistics: BorrowedPtr<LayerStatistics>,
+    index: usize,
+) -> OwnedPtr<StringBox> {
+    statistics
+        .with_ref_ok(|statistics| {
+            statistics
+                .layer_counts()
+                .nth(index)
+                .map(|(layer_type, _)| {
+                    OwnedPtr::new(StringBox::from_string(layer_type.to_string()))
+                })
+                .unwrap_or_else(OwnedPtr::null)
+        })
+        .or_log(OwnedPtr::null())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_statistics_get_layer_type_count(
+    statistics: BorrowedPtr<LayerStatistics>,
+    index: usize,
+) -> usize {
+    statistics
+        .with_ref_ok(|statistics| {
+            statistics
+                .layer_counts()
+                .nth(index)
+                .map(|(_, count)| count)
+                .unwrap_or(0)
+        })
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_statistics_max_depth(
+    statistics: BorrowedPtr<LayerStatistics>,
+) -> usize {
+    statistics
+        .with_ref_ok(|statistics| statistics.max_depth())
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_statistics_count_distinct_pictures(
+    statistics: BorrowedPtr<LayerStatistics>,
+) -> usize {
+    statistics
+        .with_ref_ok(|statistics| statistics.count_distinct_pictures())
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_statistics_picture_bytes(
+    statistics: BorrowedPtr<LayerStatistics>,
+) -> usize {
+    statistics
+        .with_ref_ok(|statistics| statistics.picture_bytes())
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_statistics_path_bytes(
+    statistics: BorrowedPtr<LayerStatistics>,
+) -> usize {
+    statistics
+        .with_ref_ok(|statistics| statistics.path_bytes())
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_statistics_tile_picture_bytes(
+    statistics: BorrowedPtr<LayerStatistics>,
+) -> usize {
+    statistics
+        .with_ref_ok(|statistics| statistics.tile_picture_bytes())
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_statistics_figure_picture_bytes(
+    statistics: BorrowedPtr<LayerStatistics>,
+) -> usize {
+    statistics
+        .with_ref_ok(|statistics| statistics.figure_picture_bytes())
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_statistics_total_bytes(
+    statistics: BorrowedPtr<LayerStatistics>,
+) -> usize {
+    statistics
+        .with_ref_ok(|statistics| statistics.total_bytes())
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_statistics_drop(ptr: OwnedPtr<LayerStatistics>) {
+    drop(ptr);
+}
@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use array_box::ArrayBox;
+use string_box::StringBox;
+use value_box::{BorrowedPtr, OwnedPtr, ReturnBoxerResult};
+
+use compositor::{Layer, LayerDiagnostic, validate};
+
+/// Validate a layer tree with backend independent rules.
+/// Returns an empty list of diagnostics if the tree is valid
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_validate(
+    layer: BorrowedPtr<Arc<dyn Layer>>,
+) -> OwnedPtr<Vec<LayerDiagnostic>> {
+    layer
+        .with_ref_ok(|layer| OwnedPtr::new(validate(layer.as_ref())))
+        .or_log(OwnedPtr::null())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_diagnostics_count(
+    diagnostics: BorrowedPtr<Vec<LayerDiagnostic>>,
+) -> usize {
+    diagnostics
+        .with_ref_ok(|diagnostics| diagnostics.len())
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_diagnostics_get_message(
+    diagnostics: BorrowedPtr<Vec<LayerDiagnostic>>,
+    index: usize,
+) -> OwnedPtr<StringBox> {
+    diagnostics
+        .with_ref_ok(|diagnostics| {
+            diagnostics
+                .get(index)
+                .map(|diagnostic| OwnedPtr::new(StringBox::from_string(diagnostic.to_string())))
+                .unwrap_or_else(OwnedPtr::null)
+        })
+        .or_log(OwnedPtr::null())
+}
+
+/// Fill a given array with the child indices leading from the root to the offending layer
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_diagnostics_get_path(
+    diagnostics: BorrowedPtr<Vec<LayerDiagnostic>>,
+    index: usize,
+    mut path: BorrowedPtr<ArrayBox<u32>>,
+) {
+    diagnostics
+        .with_ref(|diagnostics| {
+            path.with_mut_ok(|path| {
+                let indices = diagnostics
+                    .get(index)
+                    .map(|diagnostic| diagnostic.path().iter().map(|each| *each as u32).collect())
+                    .unwrap_or_default();
+                path.set_vector(indices);
+            })
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn compositor_layer_diagnostics_drop(ptr: OwnedPtr<Vec<LayerDiagnostic>>) {
+    drop(ptr);
+}
@@ -1,8 +1,13 @@
 use std::sync::Arc;
+use string_box::StringBox;
 use value_box::{BorrowedPtr, OwnedPtr, ReturnBoxerResult};
 
-use compositor::{Compositor, Layer};
-use compositor_skia::{Cache, Canvas, SkiaCachelessCompositor, SkiaCompositor};
+use compositor::{Compositor, Layer, LayerDiagnostic, LayerValidator, RuntimeShader, TiledLayer};
+use compositor_skia::{
+    Cache, CachePurgeLevel, CacheStatistics, CachedPictureEntry, Canvas, RasterCacheHeuristics,
+    RasterCachePolicy, RasterCacheStatistics, SkiaCachelessCompositor, SkiaCompositor,
+    SkiaValidationRules, SyncRasterizer, ThreadPoolRasterizer,
+};
 
 #[unsafe(no_mangle)]
 pub fn skia_compositor_compose(
@@ -21,6 +26,28 @@ pub fn skia_compositor_compose(
         .log();
 }
 
+/// Like `skia_compositor_compose`, but validates the tree first if `strict_validation` is true
+/// and refuses to compose it if it is invalid, regardless of the build profile
+#[unsafe(no_mangle)]
+pub fn skia_compositor_compose_with_strict_validation(
+    layer: BorrowedPtr<Arc<dyn Layer>>,
+    canvas: BorrowedPtr<Canvas>,
+    mut cache: BorrowedPtr<Cache>,
+    strict_validation: bool,
+) {
+    layer
+        .with_ref(|layer| {
+            cache.with_mut_ok(|cache| {
+                canvas.with_ref_ok(|canvas| {
+                    SkiaCompositor::new(None, canvas, cache)
+                        .with_strict_validation(strict_validation)
+                        .compose(layer.clone());
+                })
+            })
+        })
+        .log();
+}
+
 #[unsafe(no_mangle)]
 pub fn skia_cacheless_compositor_compose(
     layer: BorrowedPtr<Arc<dyn Layer>>,
@@ -44,3 +71,412 @@ pub fn skia_compositor_cache_new() -> OwnedPtr<Cache> {
 pub fn skia_compositor_cache_drop(cache: OwnedPtr<Cache>) {
     drop(cache);
 }
+
+/// Compiles the SkSL source of a runtime shader and caches the result.
+/// Returns a null pointer if the shader compiled, otherwise an error message
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_compile_runtime_shader(
+    mut cache: BorrowedPtr<Cache>,
+    source: BorrowedPtr<StringBox>,
+) -> OwnedPtr<StringBox> {
+    cache
+        .with_mut(|cache| {
+            source.with_ref_ok(|source| {
+                match cache.compile_runtime_shader(&RuntimeShader::new(source.to_string())) {
+                    Ok(_) => OwnedPtr::null(),
+                    Err(error) => OwnedPtr::new(StringBox::from_string(error.to_string())),
+                }
+            })
+        })
+        .or_log(OwnedPtr::null())
+}
+
+/// Limit the total size of rasterized pictures, tiles and shadows in bytes, zero means no limit
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_set_byte_budget(mut cache: BorrowedPtr<Cache>, byte_budget: usize) {
+    cache
+        .with_mut_ok(|cache| {
+            let policy = RasterCachePolicy {
+                byte_budget: (byte_budget > 0).then_some(byte_budget),
+                ..*cache.policy()
+            };
+            cache.set_policy(policy);
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_set_max_unused_frames(
+    mut cache: BorrowedPtr<Cache>,
+    max_unused_frames: u64,
+) {
+    cache
+        .with_mut_ok(|cache| {
+            let policy = RasterCachePolicy {
+                max_unused_frames,
+                ..*cache.policy()
+            };
+            cache.set_policy(policy);
+        })
+        .log();
+}
+
+/// Let compositors drawing with the cache decide which pictures with an automatic cache hint
+/// are cached, using the default heuristics. Without them such pictures are never cached
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_set_raster_cache_heuristics_enabled(
+    mut cache: BorrowedPtr<Cache>,
+    enabled: bool,
+) {
+    cache
+        .with_mut_ok(|cache| {
+            cache.set_raster_cache_heuristics(enabled.then(RasterCacheHeuristics::default))
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_get_raster_bytes(cache: BorrowedPtr<Cache>) -> usize {
+    cache
+        .with_ref_ok(|cache| cache.count_raster_bytes())
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_pin_picture(mut cache: BorrowedPtr<Cache>, picture_id: u32) {
+    cache
+        .with_mut_ok(|cache| cache.pin_picture(picture_id))
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_unpin_picture(mut cache: BorrowedPtr<Cache>, picture_id: u32) {
+    cache
+        .with_mut_ok(|cache| {
+            cache.unpin_picture(picture_id);
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_invalidate_picture(
+    mut cache: BorrowedPtr<Cache>,
+    picture_id: u32,
+) -> bool {
+    cache
+        .with_mut_ok(|cache| cache.invalidate_picture(picture_id))
+        .or_log(false)
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_invalidate_all(mut cache: BorrowedPtr<Cache>) {
+    cache.with_mut_ok(|cache| cache.invalidate_all()).log();
+}
+
+/// Release memory, returns the amount of released raster bytes.
+/// Level: 0 - unused images, 1 - unpinned images, 2 - everything.
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_purge(mut cache: BorrowedPtr<Cache>, level: u32) -> usize {
+    let level = match level {
+        0 => CachePurgeLevel::Unused,
+        1 => CachePurgeLevel::Unpinned,
+        _ => CachePurgeLevel::All,
+    };
+    cache.with_mut_ok(|cache| cache.purge(level)).or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_get_statistics(
+    cache: BorrowedPtr<Cache>,
+) -> OwnedPtr<CacheStatistics> {
+    cache
+        .with_ref_ok(|cache| OwnedPtr::new(cache.statistics()))
+        .or_log(OwnedPtr::null())
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_reset_statistics(mut cache: BorrowedPtr<Cache>) {
+    cache.with_mut_ok(|cache| cache.reset_statistics()).log();
+}
+
+fn with_raster_cache_statistics(
+    statistics: BorrowedPtr<CacheStatistics>,
+    kind: u32,
+    getter: impl FnOnce(&RasterCacheStatistics) -> u64,
+) -> u64 {
+    statistics
+        .with_ref_ok(|statistics| match kind {
+            0 => getter(&statistics.pictures),
+            1 => getter(&statistics.shadows),
+            _ => 0,
+        })
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_statistics_get_frame(statistics: BorrowedPtr<CacheStatistics>) -> u64 {
+    statistics
+        .with_ref_ok(|statistics| statistics.frame)
+        .or_log(0)
+}
+
+/// Kind: 0 - pictures and tiles, 1 - shadows.
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_statistics_get_entries(
+    statistics: BorrowedPtr<CacheStatistics>,
+    kind: u32,
+) -> u64 {
+    with_raster_cache_statistics(statistics, kind, |statistics| statistics.entries as u64)
+}
+
+/// See `skia_compositor_cache_statistics_get_entries` for the kinds
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_statistics_get_pinned(
+    statistics: BorrowedPtr<CacheStatistics>,
+    kind: u32,
+) -> u64 {
+    with_raster_cache_statistics(statistics, kind, |statistics| statistics.pinned as u64)
+}
+
+/// See `skia_compositor_cache_statistics_get_entries` for the kinds
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_statistics_get_bytes(
+    statistics: BorrowedPtr<CacheStatistics>,
+    kind: u32,
+) -> u64 {
+    with_raster_cache_statistics(statistics, kind, |statistics| statistics.bytes as u64)
+}
+
+/// See `skia_compositor_cache_statistics_get_entries` for the kinds
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_statistics_get_hits(
+    statistics: BorrowedPtr<CacheStatistics>,
+    kind: u32,
+) -> u64 {
+    with_raster_cache_statistics(statistics, kind, |statistics| statistics.hits)
+}
+
+/// See `skia_compositor_cache_statistics_get_entries` for the kinds
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_statistics_get_misses(
+    statistics: BorrowedPtr<CacheStatistics>,
+    kind: u32,
+) -> u64 {
+    with_raster_cache_statistics(statistics, kind, |statistics| statistics.misses)
+}
+
+/// See `skia_compositor_cache_statistics_get_entries` for the kinds
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_statistics_get_evictions(
+    statistics: BorrowedPtr<CacheStatistics>,
+    kind: u32,
+) -> u64 {
+    with_raster_cache_statistics(statistics, kind, |statistics| statistics.evictions)
+}
+
+/// Return the amount of compiled runtime shaders including the ones that failed to compile
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_statistics_get_shaders(
+    statistics: BorrowedPtr<CacheStatistics>,
+) -> u64 {
+    statistics
+        .with_ref_ok(|statistics| statistics.shaders as u64)
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_statistics_drop(statistics: OwnedPtr<CacheStatistics>) {
+    drop(statistics);
+}
+
+/// Return the amount of rasterized tiles of a given tiled layer over all zoom levels
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_count_rasterized_tiles(
+    cache: BorrowedPtr<Cache>,
+    tiled_layer: BorrowedPtr<Arc<dyn Layer>>,
+) -> usize {
+    tiled_layer
+        .with_ref(|tiled_layer| {
+            cache.with_ref_ok(|cache| {
+                let tiled_layer = tiled_layer
+                    .any()
+                    .downcast_ref::<TiledLayer>()
+                    .expect("Is not a tiled layer!");
+                cache.count_rasterized_tiles(tiled_layer)
+            })
+        })
+        .or_log(0)
+}
+
+/// Return the size in bytes of rasterized tiles of a given tiled layer over all zoom levels
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_count_tile_raster_bytes(
+    cache: BorrowedPtr<Cache>,
+    tiled_layer: BorrowedPtr<Arc<dyn Layer>>,
+) -> usize {
+    tiled_layer
+        .with_ref(|tiled_layer| {
+            cache.with_ref_ok(|cache| {
+                let tiled_layer = tiled_layer
+                    .any()
+                    .downcast_ref::<TiledLayer>()
+                    .expect("Is not a tiled layer!");
+                cache.count_tile_raster_bytes(tiled_layer)
+            })
+        })
+        .or_log(0)
+}
+
+/// A snapshot of the rasterized pictures and tiles,
+/// read with the `skia_compositor_cache_picture_entries_*` functions
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_get_picture_entries(
+    cache: BorrowedPtr<Cache>,
+) -> OwnedPtr<Vec<CachedPictureEntry>> {
+    cache
+        .with_ref_ok(|cache| OwnedPtr::new(cache.picture_entries().collect()))
+        .or_log(OwnedPtr::null())
+}
+
+fn with_picture_entry<T: Default>(
+    entries: BorrowedPtr<Vec<CachedPictureEntry>>,
+    index: usize,
+    getter: impl FnOnce(&CachedPictureEntry) -> T,
+) -> T {
+    entries
+        .with_ref_ok(|entries| entries.get(index).map(getter).unwrap_or_default())
+        .or_log(T::default())
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_picture_entries_get_count(
+    entries: BorrowedPtr<Vec<CachedPictureEntry>>,
+) -> usize {
+    entries.with_ref_ok(|entries| entries.len()).or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_picture_entries_get_picture_id(
+    entries: BorrowedPtr<Vec<CachedPictureEntry>>,
+    index: usize,
+) -> u32 {
+    with_picture_entry(entries, index, |entry| entry.picture_id)
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_picture_entries_get_bytes(
+    entries: BorrowedPtr<Vec<CachedPictureEntry>>,
+    index: usize,
+) -> usize {
+    with_picture_entry(entries, index, |entry| entry.bytes)
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_picture_entries_get_last_used_frame(
+    entries: BorrowedPtr<Vec<CachedPictureEntry>>,
+    index: usize,
+) -> u64 {
+    with_picture_entry(entries, index, |entry| entry.last_used_frame)
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_picture_entries_is_pinned(
+    entries: BorrowedPtr<Vec<CachedPictureEntry>>,
+    index: usize,
+) -> bool {
+    with_picture_entry(entries, index, |entry| entry.pinned)
+}
+
+/// Return one of the nine values of the matrix the picture was rasterized with, in row-major order
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_picture_entries_get_matrix_value(
+    entries: BorrowedPtr<Vec<CachedPictureEntry>>,
+    index: usize,
+    value_index: usize,
+) -> f32 {
+    with_picture_entry(entries, index, |entry| {
+        if value_index < 9 {
+            entry.matrix[value_index]
+        } else {
+            0.0
+        }
+    })
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_picture_entries_drop(entries: OwnedPtr<Vec<CachedPictureEntry>>) {
+    drop(entries);
+}
+
+/// Rasterize cached pictures and shadows on a given amount of background threads,
+/// zero means one thread per available core. Returns false if the threads could not be spawned
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_use_thread_pool_rasterizer(
+    mut cache: BorrowedPtr<Cache>,
+    thread_count: usize,
+) -> bool {
+    cache
+        .with_mut_ok(|cache| {
+            let rasterizer = if thread_count > 0 {
+                ThreadPoolRasterizer::new(thread_count)
+            } else {
+                ThreadPoolRasterizer::with_available_parallelism()
+            };
+            match rasterizer {
+                Ok(rasterizer) => {
+                    cache.set_rasterizer(rasterizer);
+                    true
+                }
+                Err(_) => false,
+            }
+        })
+        .or_log(false)
+}
+
+/// Rasterize cached pictures and shadows right away while composing
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_use_sync_rasterizer(mut cache: BorrowedPtr<Cache>) {
+    cache
+        .with_mut_ok(|cache| cache.set_rasterizer(SyncRasterizer::new()))
+        .log();
+}
+
+/// Return true if pictures or shadows are still being rasterized in the background
+/// and another frame should be composed to show them
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_has_pending_rasterizations(cache: BorrowedPtr<Cache>) -> bool {
+    cache
+        .with_ref_ok(|cache| cache.has_pending_rasterizations())
+        .or_log(false)
+}
+
+/// Return true if images are still being decoded in the background
+/// and another frame should be composed to show them
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_has_pending_image_decodes(cache: BorrowedPtr<Cache>) -> bool {
+    cache
+        .with_ref_ok(|cache| cache.has_pending_image_decodes())
+        .or_log(false)
+}
+
+/// Return true if any work is still being done in the background
+/// and another frame should be composed to show its results
+#[unsafe(no_mangle)]
+pub fn skia_compositor_cache_has_pending_work(cache: BorrowedPtr<Cache>) -> bool {
+    cache
+        .with_ref_ok(|cache| cache.has_pending_work())
+        .or_log(false)
+}
+
+/// Validate a layer tree with the rules of the Skia backend.
+/// The diagnostics are read and released with the `compositor_layer_diagnostics_*` functions
+#[unsafe(no_mangle)]
+pub fn skia_compositor_validate(
+    layer: BorrowedPtr<Arc<dyn Layer>>,
+) -> OwnedPtr<Vec<LayerDiagnostic>> {
+    layer
+        .with_ref_ok(|layer| {
+            OwnedPtr::new(LayerValidator::new(&SkiaValidationRules).validate(layer.as_ref()))
+        })
+        .or_log(OwnedPtr::null())
+}
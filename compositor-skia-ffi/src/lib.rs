@@ -1,7 +1,9 @@
 mod compositor;
 mod path;
 mod picture;
+mod render_thread;
 
 pub use crate::compositor::*;
 pub use path::*;
 pub use picture::*;
+pub use render_thread::*;
@@ -0,0 +1,243 @@
+use std::ffi::c_void;
+use std::sync::Arc;
+use std::time::Duration;
+
+use value_box::{BorrowedPtr, OwnedPtr, ReturnBoxerResult};
+
+use compositor::Layer;
+use compositor_skia::{
+    FrameCallback, FramePhase, FrameTiming, FrameTimingsHistory, Platform, PlatformContext,
+    RasterCacheHeuristics, RenderThread, RenderThreadOptions,
+};
+
+pub type FrameCallbackFn = extern "C" fn(*mut c_void, u64, u64);
+
+/// The payload is owned by the host, which must keep it alive and safe to use from the
+/// render thread until the callback is replaced or the render thread is dropped
+struct CallbackPayload(*mut c_void);
+
+unsafe impl Send for CallbackPayload {}
+unsafe impl Sync for CallbackPayload {}
+
+impl CallbackPayload {
+    fn get(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+fn frame_callback(
+    callback: Option<FrameCallbackFn>,
+    payload: *mut c_void,
+) -> Option<FrameCallback> {
+    let callback = callback?;
+    let payload = CallbackPayload(payload);
+    Some(Arc::new(move |timing: FrameTiming| {
+        callback(
+            payload.get(),
+            timing.frame_id,
+            timing.timestamp.as_micros() as u64,
+        )
+    }))
+}
+
+/// Move the platform context to a new render thread.
+/// A frame interval of zero uses the default interval of 60 frames per second.
+/// With raster cache heuristics pictures that are drawn often are cached automatically.
+/// Returns a null pointer if the thread could not be spawned
+#[unsafe(no_mangle)]
+pub fn skia_compositor_render_thread_spawn(
+    context: OwnedPtr<PlatformContext>,
+    frame_interval_micros: u64,
+    cacheless: bool,
+    raster_cache_heuristics: bool,
+) -> OwnedPtr<RenderThread> {
+    context
+        .with_value_ok(|context| {
+            let platform = context.platform().unwrap_or(Platform::Unsupported);
+            let mut options = RenderThreadOptions {
+                cacheless,
+                raster_cache_heuristics: raster_cache_heuristics
+                    .then(RasterCacheHeuristics::default),
+                ..Default::default()
+            };
+            if frame_interval_micros > 0 {
+                options.frame_interval = Duration::from_micros(frame_interval_micros);
+            }
+            RenderThread::spawn(platform, context, options)
+                .map(OwnedPtr::new)
+                .unwrap_or_else(|_| OwnedPtr::null())
+        })
+        .or_log(OwnedPtr::null())
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_render_thread_submit_layer(
+    thread: BorrowedPtr<RenderThread>,
+    layer: BorrowedPtr<Arc<dyn Layer>>,
+) {
+    thread
+        .with_ref(|thread| layer.with_clone_ok(|layer| thread.submit_layer(layer)))
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_render_thread_request_redraw(thread: BorrowedPtr<RenderThread>) {
+    thread.with_ref_ok(|thread| thread.request_redraw()).log();
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_render_thread_resize_surface(
+    thread: BorrowedPtr<RenderThread>,
+    width: i32,
+    height: i32,
+) {
+    thread
+        .with_ref_ok(|thread| thread.resize_surface((width, height)))
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_render_thread_set_scale_factor(
+    thread: BorrowedPtr<RenderThread>,
+    scale_factor: f32,
+) {
+    thread
+        .with_ref_ok(|thread| thread.set_scale_factor(scale_factor))
+        .log();
+}
+
+/// Validate submitted trees before drawing them and skip invalid ones.
+/// Enabled by default in debug builds only
+#[unsafe(no_mangle)]
+pub fn skia_compositor_render_thread_set_strict_validation(
+    thread: BorrowedPtr<RenderThread>,
+    strict_validation: bool,
+) {
+    thread
+        .with_ref_ok(|thread| thread.set_strict_validation(strict_validation))
+        .log();
+}
+
+/// The callback receives the payload, the frame id and the microseconds since the render thread started.
+/// Passing no callback removes the current one
+#[unsafe(no_mangle)]
+pub fn skia_compositor_render_thread_set_frame_begin_callback(
+    thread: BorrowedPtr<RenderThread>,
+    callback: Option<FrameCallbackFn>,
+    payload: *mut c_void,
+) {
+    thread
+        .with_ref_ok(|thread| thread.set_frame_begin_callback(frame_callback(callback, payload)))
+        .log();
+}
+
+/// See `skia_compositor_render_thread_set_frame_begin_callback`
+#[unsafe(no_mangle)]
+pub fn skia_compositor_render_thread_set_frame_presented_callback(
+    thread: BorrowedPtr<RenderThread>,
+    callback: Option<FrameCallbackFn>,
+    payload: *mut c_void,
+) {
+    thread
+        .with_ref_ok(|thread| {
+            thread.set_frame_presented_callback(frame_callback(callback, payload))
+        })
+        .log();
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_render_thread_get_presented_frames(
+    thread: BorrowedPtr<RenderThread>,
+) -> u64 {
+    thread
+        .with_ref_ok(|thread| thread.statistics().presented_frames)
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_render_thread_get_coalesced_frames(
+    thread: BorrowedPtr<RenderThread>,
+) -> u64 {
+    thread
+        .with_ref_ok(|thread| thread.statistics().coalesced_frames)
+        .or_log(0)
+}
+
+/// Stop the render thread, waiting for the frame that is being drawn
+#[unsafe(no_mangle)]
+pub fn skia_compositor_render_thread_drop(thread: OwnedPtr<RenderThread>) {
+    drop(thread);
+}
+
+/// Return a snapshot of the timings of the most recently drawn frames
+#[unsafe(no_mangle)]
+pub fn skia_compositor_render_thread_get_frame_timings(
+    thread: BorrowedPtr<RenderThread>,
+) -> OwnedPtr<FrameTimingsHistory> {
+    thread
+        .with_ref_ok(|thread| OwnedPtr::new(thread.frame_timings()))
+        .or_log(OwnedPtr::null())
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_frame_timings_get_count(timings: BorrowedPtr<FrameTimingsHistory>) -> usize {
+    timings.with_ref_ok(|timings| timings.len()).or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_frame_timings_get_frame_id(
+    timings: BorrowedPtr<FrameTimingsHistory>,
+    index: usize,
+) -> u64 {
+    timings
+        .with_ref_ok(|timings| {
+            timings
+                .get(index)
+                .map(|frame| frame.frame_id)
+                .unwrap_or_default()
+        })
+        .or_log(0)
+}
+
+/// Phases are numbered in the order of `FramePhase`: traversal, picture rasterization,
+/// shadow rasterization, flush and present, total.
+/// Frames are indexed from the oldest one
+#[unsafe(no_mangle)]
+pub fn skia_compositor_frame_timings_get_duration_micros(
+    timings: BorrowedPtr<FrameTimingsHistory>,
+    index: usize,
+    phase: u32,
+) -> u64 {
+    timings
+        .with_ref_ok(|timings| {
+            timings
+                .get(index)
+                .zip(FramePhase::ALL.get(phase as usize))
+                .map(|(frame, phase)| frame.duration(*phase).as_micros() as u64)
+                .unwrap_or_default()
+        })
+        .or_log(0)
+}
+
+/// `percentile` is between 0 and 100, see `skia_compositor_frame_timings_get_duration_micros`
+/// for the phase numbers
+#[unsafe(no_mangle)]
+pub fn skia_compositor_frame_timings_get_percentile_micros(
+    timings: BorrowedPtr<FrameTimingsHistory>,
+    phase: u32,
+    percentile: f32,
+) -> u64 {
+    timings
+        .with_ref_ok(|timings| {
+            FramePhase::ALL
+                .get(phase as usize)
+                .map(|phase| timings.percentile(*phase, percentile).as_micros() as u64)
+                .unwrap_or_default()
+        })
+        .or_log(0)
+}
+
+#[unsafe(no_mangle)]
+pub fn skia_compositor_frame_timings_drop(timings: OwnedPtr<FrameTimingsHistory>) {
+    drop(timings);
+}
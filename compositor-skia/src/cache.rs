@@ -1,12 +1,35 @@
-use crate::{ImageCache, ShadowCache};
-use compositor::Shadow;
-use log::info;
-use skia_safe::{Image, Matrix};
+use crate::{
+    CacheStatistics, CachedPictureEntry, CachedShadowEntry, DecodedImageCache, ImageCache,
+    PictureMergeCache, PictureToRasterize, PictureUsage, PictureUsageTracker,
+    RasterCacheHeuristics, RasterCachePolicy, RasterizedPicture, RasterizedShadow, Rasterizer,
+    ShaderCache, ShaderError, ShadowCache, ShadowScale, ShadowToRasterize, SyncRasterizer,
+};
+use compositor::{ImageSource, RuntimeShader, Shadow, TiledLayer};
+use log::{error, trace};
+use skia_safe::{Canvas, Image, Matrix, RuntimeEffect};
+
+/// How much `Cache::purge` releases, for example in response to memory pressure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachePurgeLevel {
+    /// Unpinned images that were not used in the current frame
+    Unused,
+    /// Every unpinned image, merged pictures and decoded images
+    Unpinned,
+    /// Everything, including pinned images
+    All,
+}
 
 #[derive(Debug)]
 pub struct Cache {
     pub(crate) shadow_cache: ShadowCache,
     pub(crate) image_cache: ImageCache,
+    pub(crate) decoded_image_cache: DecodedImageCache,
+    pub(crate) shader_cache: ShaderCache,
+    pub(crate) picture_merge_cache: PictureMergeCache,
+    pub(crate) picture_usage: PictureUsageTracker,
+    rasterizer: Box<dyn Rasterizer>,
+    policy: RasterCachePolicy,
+    raster_cache_heuristics: Option<RasterCacheHeuristics>,
 }
 
 impl Cache {
@@ -14,35 +37,281 @@ impl Cache {
         Self {
             shadow_cache: ShadowCache::new(),
             image_cache: ImageCache::new(),
+            decoded_image_cache: DecodedImageCache::new(),
+            shader_cache: ShaderCache::new(),
+            picture_merge_cache: PictureMergeCache::new(),
+            picture_usage: PictureUsageTracker::new(),
+            rasterizer: Box::new(SyncRasterizer::new()),
+            policy: RasterCachePolicy::default(),
+            raster_cache_heuristics: None,
+        }
+    }
+
+    pub fn with_rasterizer(rasterizer: impl Rasterizer + 'static) -> Self {
+        Self {
+            rasterizer: Box::new(rasterizer),
+            ..Self::new()
+        }
+    }
+
+    /// Replace the rasterizer of pictures and shadows that need to be cached.
+    /// Jobs still pending in the previous rasterizer are dropped
+    pub fn set_rasterizer(&mut self, rasterizer: impl Rasterizer + 'static) {
+        self.rasterizer = Box::new(rasterizer);
+    }
+
+    pub fn with_policy(policy: RasterCachePolicy) -> Self {
+        Self {
+            policy,
+            ..Self::new()
+        }
+    }
+
+    pub fn policy(&self) -> &RasterCachePolicy {
+        &self.policy
+    }
+
+    /// The new policy is applied at the end of the next frame
+    pub fn set_policy(&mut self, policy: RasterCachePolicy) {
+        self.policy = policy;
+    }
+
+    pub fn raster_cache_heuristics(&self) -> Option<RasterCacheHeuristics> {
+        self.raster_cache_heuristics
+    }
+
+    /// Heuristics used by compositors drawing with this cache that were not given their own.
+    /// Without heuristics pictures with `PictureCacheHint::Auto` are never cached
+    pub fn set_raster_cache_heuristics(&mut self, heuristics: Option<RasterCacheHeuristics>) {
+        self.raster_cache_heuristics = heuristics;
+    }
+
+    /// The total size of rasterized pictures, tiles and shadows
+    pub fn count_raster_bytes(&self) -> usize {
+        self.image_cache.count_cached_bytes() + self.shadow_cache.count_cached_bytes()
+    }
+
+    /// The amount of rasterized tiles of a given tiled layer, over all zoom levels
+    pub fn count_rasterized_tiles(&self, layer: &TiledLayer) -> usize {
+        let mut count = 0;
+        layer.for_each_tile_picture(|picture| {
+            if self.image_cache.has_cached_image(picture.id()) {
+                count += 1;
+            }
+        });
+        count
+    }
+
+    /// The total size of rasterized tiles of a given tiled layer, over all zoom levels
+    pub fn count_tile_raster_bytes(&self, layer: &TiledLayer) -> usize {
+        let mut bytes = 0;
+        layer.for_each_tile_picture(|picture| {
+            bytes += self
+                .image_cache
+                .cached_image_bytes(picture.id())
+                .unwrap_or(0);
+        });
+        bytes
+    }
+
+    /// Drop rasterized images of tiles that a given tiled layer evicted or invalidated
+    pub fn remove_released_tile_images(&mut self, layer: &TiledLayer) -> usize {
+        layer
+            .take_released_tile_pictures()
+            .into_iter()
+            .filter(|picture_id| self.image_cache.images.remove(picture_id))
+            .count()
+    }
+
+    /// Keep the rasterized image of a given picture or tile regardless of its age and the byte budget
+    pub fn pin_picture(&mut self, picture_id: u32) {
+        self.image_cache.pin_picture(picture_id);
+    }
+
+    pub fn unpin_picture(&mut self, picture_id: u32) -> bool {
+        self.image_cache.unpin_picture(picture_id)
+    }
+
+    /// Keep the rasterized image of a given shadow regardless of its age and the byte budget.
+    /// Shadows are cached moved to their pixel origin, so this pins the shadows at every position
+    pub fn pin_shadow(&mut self, shadow: Shadow) {
+        self.shadow_cache.pin_shadow(shadow.normalize().0);
+    }
+
+    pub fn unpin_shadow(&mut self, shadow: &Shadow) -> bool {
+        self.shadow_cache.unpin_shadow(&shadow.normalize().0)
+    }
+
+    pub fn statistics(&self) -> CacheStatistics {
+        CacheStatistics {
+            frame: self.image_cache.images.frame(),
+            pictures: self.image_cache.images.statistics(),
+            shadows: self.shadow_cache.images.statistics(),
+            shaders: self.shader_cache.count_compiled_shaders(),
+        }
+    }
+
+    /// Reset the hit, miss and eviction counters of pictures and shadows
+    pub fn reset_statistics(&mut self) {
+        self.image_cache.images.reset_counters();
+        self.shadow_cache.images.reset_counters();
+    }
+
+    /// Rasterized pictures and tiles in no particular order
+    pub fn picture_entries(&self) -> impl Iterator<Item = CachedPictureEntry> + '_ {
+        self.image_cache.entries()
+    }
+
+    /// Rasterized shadows in no particular order
+    pub fn shadow_entries(&self) -> impl Iterator<Item = CachedShadowEntry<'_>> {
+        self.shadow_cache.entries()
+    }
+
+    /// Remove the rasterized image of a given picture or tile even if it is pinned,
+    /// it is rasterized again the next time it is drawn. Return true if there was an image
+    pub fn invalidate_picture(&mut self, picture_id: u32) -> bool {
+        self.rasterizer.invalidate_picture(picture_id);
+        self.image_cache.images.remove(&picture_id)
+    }
+
+    /// Remove the rasterized image of a given shadow even if it is pinned,
+    /// together with the image of the same shadow at other positions
+    pub fn invalidate_shadow(&mut self, shadow: &Shadow) -> bool {
+        let shadow = shadow.normalize().0;
+        self.rasterizer.invalidate_shadow(&shadow);
+        self.shadow_cache.remove_shadow_images(&shadow)
+    }
+
+    /// Remove every rasterized picture, tile and shadow together with merged pictures
+    /// and compiled shaders. Pinned keys stay pinned and their images are kept once rasterized again
+    pub fn invalidate_all(&mut self) {
+        self.rasterizer.invalidate_all();
+        self.image_cache.clear();
+        self.shadow_cache.clear();
+        self.shader_cache.clear();
+        self.picture_merge_cache.clear();
+        self.picture_usage.clear();
+    }
+
+    /// Release memory down to a given level, return the amount of released raster bytes
+    pub fn purge(&mut self, level: CachePurgeLevel) -> usize {
+        let bytes_before = self.count_raster_bytes();
+        match level {
+            CachePurgeLevel::Unused => {
+                self.image_cache.remove_unused_images(0);
+                self.shadow_cache.remove_unused_images(0);
+            }
+            CachePurgeLevel::Unpinned => {
+                self.image_cache.images.remove_unpinned();
+                self.shadow_cache.images.remove_unpinned();
+                self.picture_merge_cache.clear();
+                self.decoded_image_cache.clear();
+            }
+            CachePurgeLevel::All => {
+                self.image_cache.images.remove_unpinned();
+                self.shadow_cache.images.remove_unpinned();
+                // pinned images are invalidated rather than evicted
+                self.invalidate_all();
+                self.decoded_image_cache.clear();
+            }
         }
+        bytes_before - self.count_raster_bytes()
     }
 
     pub fn mark_images_as_not_used(&mut self) {
         self.image_cache.mark_images_as_not_used();
         self.shadow_cache.mark_images_as_not_used();
+        self.decoded_image_cache.mark_images_as_not_used();
+        self.picture_merge_cache.mark_pictures_as_not_used();
+        self.picture_usage.begin_frame();
     }
 
     pub fn remove_unused_images(&mut self) {
-        let removed_pictures = self.image_cache.remove_unused_images();
-        let removed_shadows = self.shadow_cache.remove_unused_images();
-        info!(
+        let max_unused_frames = self.policy.max_unused_frames;
+        let mut removed_pictures = self.image_cache.remove_unused_images(max_unused_frames);
+        let mut removed_shadows = self.shadow_cache.remove_unused_images(max_unused_frames);
+        if let Some(byte_budget) = self.policy.byte_budget {
+            let (evicted_pictures, evicted_shadows) = self.evict_to_budget(byte_budget);
+            removed_pictures += evicted_pictures;
+            removed_shadows += evicted_shadows;
+        }
+        let removed_decoded_images = self.decoded_image_cache.remove_unused_images();
+        let removed_merged_pictures = self.picture_merge_cache.remove_unused_pictures();
+        self.picture_usage.remove_unseen_pictures();
+        trace!(
             "Removed {} unused cached pictures. {} left.",
             removed_pictures,
             self.image_cache.count_cached_images()
         );
-        info!(
+        trace!(
             "Removed {} unused cached shadows. {} left.",
             removed_shadows,
             self.shadow_cache.count_cached_shadows()
         );
+        trace!(
+            "Removed {} unused decoded images. {} left.",
+            removed_decoded_images,
+            self.decoded_image_cache.count_cached_images()
+        );
+        trace!(
+            "Removed {} unused merged pictures. {} left.",
+            removed_merged_pictures,
+            self.picture_merge_cache.count_merged_pictures()
+        );
+    }
+
+    /// Evict the least recently used pictures and shadows until they fit in a given amount of bytes.
+    /// Images used in the current frame are kept even if that exceeds the budget.
+    /// Return the amount of evicted pictures and shadows
+    fn evict_to_budget(&mut self, byte_budget: usize) -> (usize, usize) {
+        if self.count_raster_bytes() <= byte_budget {
+            return (0, 0);
+        }
+
+        let mut pictures = self
+            .image_cache
+            .images
+            .eviction_candidates()
+            .into_iter()
+            .peekable();
+        let mut shadows = self
+            .shadow_cache
+            .images
+            .eviction_candidates()
+            .into_iter()
+            .peekable();
+
+        let mut evicted = (0, 0);
+        while self.count_raster_bytes() > byte_budget {
+            let evict_picture = match (pictures.peek(), shadows.peek()) {
+                (Some((picture_frame, _)), Some((shadow_frame, _))) => {
+                    picture_frame <= shadow_frame
+                }
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+
+            if evict_picture {
+                if let Some((_, picture_id)) = pictures.next() {
+                    self.image_cache.images.evict(&picture_id);
+                    evicted.0 += 1;
+                }
+            } else if let Some((_, shadow)) = shadows.next() {
+                self.shadow_cache.images.evict(&shadow);
+                evicted.1 += 1;
+            }
+        }
+        evicted
     }
 
-    pub fn get_shadow_image(&mut self, shadow: &Shadow) -> Option<&Image> {
-        self.shadow_cache.get_shadow_image(shadow)
+    /// Shadows are expected to be normalized with `Shadow::normalize`
+    pub fn get_shadow_image(&mut self, shadow: &Shadow, scale: &ShadowScale) -> Option<&Image> {
+        self.shadow_cache.get_shadow_image(shadow, scale)
     }
 
-    pub fn push_shadow_image(&mut self, shadow: Shadow, image: Image) {
-        self.shadow_cache.push_shadow_image(shadow, image);
+    pub fn push_shadow_image(&mut self, shadow: Shadow, scale: ShadowScale, image: Image) {
+        self.shadow_cache.push_shadow_image(shadow, scale, image);
     }
 
     pub fn get_picture_image(&mut self, picture_id: u32) -> Option<(Image, Matrix)> {
@@ -53,7 +322,168 @@ impl Cache {
         self.image_cache.remove_picture_image(picture_id);
     }
 
+    /// Record that a picture left to the compositor is drawn in the current frame
+    pub fn observe_picture(&mut self, picture_id: u32, matrix: Matrix) -> PictureUsage {
+        self.picture_usage.observe(picture_id, matrix)
+    }
+
     pub fn push_id_image(&mut self, picture_id: u32, image: Image, matrix: Matrix) {
         self.image_cache.push_id_image(picture_id, image, matrix);
     }
+
+    /// Rasterize a given picture with the rasterizer of the cache.
+    /// Returns `None` if the rasterizer works in the background and the image is not ready yet
+    pub fn rasterize_picture(
+        &mut self,
+        canvas: &Canvas,
+        picture_to_rasterize: PictureToRasterize,
+    ) -> Option<RasterizedPicture> {
+        self.rasterizer
+            .rasterize_picture(canvas, vec![picture_to_rasterize])
+            .pop()
+    }
+
+    /// Rasterize a given shadow with the rasterizer of the cache.
+    /// Returns `None` if the rasterizer works in the background and the image is not ready yet
+    pub fn rasterize_shadow(
+        &mut self,
+        canvas: &Canvas,
+        shadow_to_rasterize: ShadowToRasterize,
+    ) -> Option<RasterizedShadow> {
+        self.rasterizer
+            .rasterize_shadow(canvas, vec![shadow_to_rasterize])
+            .pop()
+    }
+
+    /// Move pictures and shadows that were rasterized in the background into the cache.
+    /// Returns the amount of received images
+    pub fn receive_rasterized_images(&mut self) -> usize {
+        let (rasterized_pictures, rasterized_shadows) = self.rasterizer.collect_rasterized();
+        let mut received = 0;
+        for rasterized_picture in rasterized_pictures {
+            match rasterized_picture.image {
+                None => error!(
+                    "Failed to rasterize picture {}",
+                    rasterized_picture.picture.unique_id()
+                ),
+                Some(image) => {
+                    let picture_id = rasterized_picture.picture.unique_id();
+                    self.push_id_image(picture_id, image, rasterized_picture.matrix);
+                    received += 1;
+                }
+            }
+        }
+        for rasterized_shadow in rasterized_shadows {
+            match rasterized_shadow.image {
+                None => error!("Failed to rasterize shadow {:?}", rasterized_shadow.shadow),
+                Some(image) => {
+                    self.push_shadow_image(
+                        rasterized_shadow.shadow,
+                        rasterized_shadow.scale,
+                        image,
+                    );
+                    received += 1;
+                }
+            }
+        }
+        received
+    }
+
+    /// Return true if some pictures or shadows are still being rasterized in the background
+    /// and another frame should be requested
+    pub fn has_pending_rasterizations(&self) -> bool {
+        self.rasterizer.has_pending_jobs()
+    }
+
+    /// Return true if the rasterizer works in the background, so that pictures can be
+    /// scheduled ahead of the frame they are drawn in without blocking the current one
+    pub fn rasterizes_in_background(&self) -> bool {
+        self.rasterizer.works_in_background()
+    }
+
+    /// Return a decoded image for a given source, or schedule its decoding if it is not yet available
+    pub fn get_decoded_image(
+        &mut self,
+        source: &ImageSource,
+        needs_mipmaps: bool,
+    ) -> Option<Image> {
+        self.decoded_image_cache
+            .get_decoded_image(source, needs_mipmaps)
+    }
+
+    /// Move images that finished decoding in the background into the cache
+    pub fn receive_decoded_images(&mut self) -> usize {
+        self.decoded_image_cache.receive_decoded_images()
+    }
+
+    /// Return true if some images are still being decoded and another frame should be requested
+    pub fn has_pending_image_decodes(&self) -> bool {
+        self.decoded_image_cache.has_pending_images()
+    }
+
+    /// Return true if any work is still being done in the background
+    /// and another frame should be requested to show its results
+    pub fn has_pending_work(&self) -> bool {
+        self.has_pending_image_decodes() || self.has_pending_rasterizations()
+    }
+
+    /// Return a compiled effect for a given runtime shader, compiling and caching it on the first request
+    pub fn get_runtime_effect(
+        &mut self,
+        shader: &RuntimeShader,
+    ) -> Result<RuntimeEffect, ShaderError> {
+        self.shader_cache.get_runtime_effect(shader)
+    }
+
+    /// Compile a given runtime shader ahead of time, reporting compilation errors to the caller
+    pub fn compile_runtime_shader(&mut self, shader: &RuntimeShader) -> Result<(), ShaderError> {
+        self.get_runtime_effect(shader).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SkiaCompositor;
+    use compositor::Compositor;
+    use skia_safe::surfaces;
+    use std::sync::Arc;
+
+    #[test]
+    pub fn purging_everything_clears_compiled_shaders() {
+        let shader = RuntimeShader::new("half4 main(float2 position) { return half4(1); }");
+        let invalid_shader = RuntimeShader::new("half4 main(float2 position) { return; }");
+
+        let mut cache = Cache::new();
+        assert!(cache.compile_runtime_shader(&shader).is_ok());
+        assert!(cache.compile_runtime_shader(&invalid_shader).is_err());
+        assert_eq!(cache.statistics().shaders, 2);
+
+        cache.purge(CachePurgeLevel::Unpinned);
+        assert_eq!(cache.statistics().shaders, 2);
+
+        cache.purge(CachePurgeLevel::All);
+        assert_eq!(cache.statistics().shaders, 0);
+    }
+
+    #[test]
+    pub fn counts_rasterized_tiles_of_a_tiled_layer() {
+        let layer = TiledLayer::default();
+        let mut cache = Cache::new();
+        let mut surface = surfaces::raster_n32_premul((600, 400)).unwrap();
+        SkiaCompositor::new(None, surface.canvas(), &mut cache).compose(Arc::new(layer.clone()));
+
+        let tiles = layer.tile_statistics().tiles;
+        assert!(tiles > 0);
+        assert_eq!(cache.count_rasterized_tiles(&layer), tiles);
+        assert!(cache.count_tile_raster_bytes(&layer) > 0);
+        assert_eq!(
+            cache.count_tile_raster_bytes(&layer),
+            cache.count_raster_bytes()
+        );
+
+        layer.invalidate_all();
+        assert_eq!(cache.count_rasterized_tiles(&layer), 0);
+        assert_eq!(cache.count_tile_raster_bytes(&layer), 0);
+    }
 }
@@ -0,0 +1,255 @@
+use compositor::{ImageData, ImageId, ImageSource};
+use log::{error, trace};
+use skia_safe::image::CachingHint;
+use skia_safe::{AlphaType, ColorType, Data, Image, ImageInfo, images};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Error, Formatter};
+use std::sync::mpsc::{Receiver, Sender, TryRecvError, channel};
+use std::thread;
+
+/// The amount of frames after which a decoded image is purged if not used
+pub const DECODED_IMAGE_UNUSED_FRAMES_LIMIT: usize = 60;
+
+/// Decode a given image source into a raster image on the current thread
+pub fn decode_image_source(source: &ImageSource) -> Option<Image> {
+    match source.data() {
+        ImageData::Rgba {
+            width,
+            height,
+            pixels,
+        } => {
+            let info = ImageInfo::new(
+                (*width as i32, *height as i32),
+                ColorType::RGBA8888,
+                AlphaType::Unpremul,
+                None,
+            );
+            images::raster_from_data(&info, Data::new_copy(pixels), *width as usize * 4)
+        }
+        ImageData::Encoded(bytes) => {
+            images::deferred_from_encoded_data(Data::new_copy(bytes), None)
+                // force the decode now rather than lazily during the draw
+                .and_then(|image| image.make_raster_image(None, CachingHint::Disallow))
+        }
+    }
+}
+
+struct DecodedImage {
+    image: Image,
+    mipmapped_image: Option<Image>,
+    frames_to_purge: usize,
+}
+
+impl DecodedImage {
+    fn new(image: Image) -> Self {
+        Self {
+            image,
+            mipmapped_image: None,
+            frames_to_purge: DECODED_IMAGE_UNUSED_FRAMES_LIMIT,
+        }
+    }
+
+    fn mark_not_used(&mut self) {
+        self.frames_to_purge = self.frames_to_purge.saturating_sub(1);
+    }
+
+    fn mark_used(&mut self) {
+        self.frames_to_purge = DECODED_IMAGE_UNUSED_FRAMES_LIMIT;
+    }
+
+    fn should_purge(&self) -> bool {
+        self.frames_to_purge == 0
+    }
+
+    fn image(&mut self, needs_mipmaps: bool) -> Image {
+        if !needs_mipmaps {
+            return self.image.clone();
+        }
+
+        if self.mipmapped_image.is_none() {
+            self.mipmapped_image = self.image.with_default_mipmaps();
+        }
+
+        self.mipmapped_image
+            .clone()
+            .unwrap_or_else(|| self.image.clone())
+    }
+}
+
+/// Decodes image sources on a background thread.
+/// The thread is spawned when the first image is requested and exits when the decoder is dropped.
+struct ImageDecoder {
+    worker: Option<(Sender<ImageSource>, Receiver<(ImageId, Option<Image>)>)>,
+}
+
+impl ImageDecoder {
+    fn new() -> Self {
+        Self { worker: None }
+    }
+
+    fn is_running(&self) -> bool {
+        self.worker.is_some()
+    }
+
+    /// Send a given source to the decoder thread, returning false if it could not be scheduled
+    fn decode(&mut self, source: ImageSource) -> bool {
+        let (requests, _) = self.worker.get_or_insert_with(Self::spawn_worker);
+        if let Err(error) = requests.send(source) {
+            error!("Image decoder thread is gone: {}", error);
+            // the thread will be respawned on the next request
+            self.worker = None;
+            return false;
+        }
+        true
+    }
+
+    fn try_receive(&mut self) -> Option<(ImageId, Option<Image>)> {
+        let (_, results) = self.worker.as_ref()?;
+        match results.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                error!("Image decoder thread is gone");
+                // the thread will be respawned on the next request
+                self.worker = None;
+                None
+            }
+        }
+    }
+
+    fn spawn_worker() -> (Sender<ImageSource>, Receiver<(ImageId, Option<Image>)>) {
+        let (request_sender, request_receiver) = channel::<ImageSource>();
+        let (result_sender, result_receiver) = channel();
+
+        thread::Builder::new()
+            .name("compositor-image-decoder".to_string())
+            .spawn(move || {
+                for source in request_receiver {
+                    let image = decode_image_source(&source);
+                    if result_sender.send((source.id(), image)).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("Failed to spawn an image decoder thread");
+
+        (request_sender, result_receiver)
+    }
+}
+
+/// Keeps decoded images by their content id and schedules the decoding of missing ones
+pub struct DecodedImageCache {
+    images: HashMap<ImageId, DecodedImage>,
+    pending: HashSet<ImageId>,
+    /// images that failed to decode with the amount of frames after which they are forgotten
+    /// if not requested, and decoded again if requested afterwards
+    failed: HashMap<ImageId, usize>,
+    decoder: ImageDecoder,
+}
+
+impl Debug for DecodedImageCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        f.debug_struct("DecodedImageCache")
+            .field("images", &self.images.len())
+            .field("pending", &self.pending.len())
+            .field("failed", &self.failed.len())
+            .finish()
+    }
+}
+
+impl DecodedImageCache {
+    pub fn new() -> Self {
+        Self {
+            images: HashMap::new(),
+            pending: HashSet::new(),
+            failed: HashMap::new(),
+            decoder: ImageDecoder::new(),
+        }
+    }
+
+    /// Return a decoded image for a given source if it is ready,
+    /// otherwise schedule it for decoding on the background thread.
+    pub fn get_decoded_image(
+        &mut self,
+        source: &ImageSource,
+        needs_mipmaps: bool,
+    ) -> Option<Image> {
+        if let Some(decoded_image) = self.images.get_mut(&source.id()) {
+            decoded_image.mark_used();
+            return Some(decoded_image.image(needs_mipmaps));
+        }
+
+        if let Some(frames_to_purge) = self.failed.get_mut(&source.id()) {
+            *frames_to_purge = DECODED_IMAGE_UNUSED_FRAMES_LIMIT;
+            return None;
+        }
+
+        if !self.pending.contains(&source.id()) {
+            trace!("Schedule decoding of image {}", source.id());
+            if self.decoder.decode(source.clone()) {
+                self.pending.insert(source.id());
+            } else {
+                // images sent to the previous thread will never arrive, they are scheduled again
+                self.pending.clear();
+            }
+        }
+
+        None
+    }
+
+    /// Move the images decoded since the last call into the cache.
+    /// Returns the amount of received images.
+    pub fn receive_decoded_images(&mut self) -> usize {
+        let mut received = 0;
+        while let Some((id, image)) = self.decoder.try_receive() {
+            self.pending.remove(&id);
+            match image {
+                None => {
+                    error!("Failed to decode image {}", id);
+                    self.failed.insert(id, DECODED_IMAGE_UNUSED_FRAMES_LIMIT);
+                }
+                Some(image) => {
+                    self.images.insert(id, DecodedImage::new(image));
+                    received += 1;
+                }
+            }
+        }
+        if !self.decoder.is_running() {
+            self.pending.clear();
+        }
+        received
+    }
+
+    /// Return true if there are images being decoded,
+    /// in which case the host should request another frame
+    pub fn has_pending_images(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    pub fn count_cached_images(&self) -> usize {
+        self.images.len()
+    }
+
+    pub fn mark_images_as_not_used(&mut self) {
+        for image in self.images.values_mut() {
+            image.mark_not_used();
+        }
+        for frames_to_purge in self.failed.values_mut() {
+            *frames_to_purge = frames_to_purge.saturating_sub(1);
+        }
+    }
+
+    /// Forget every decoded image, images that failed to decode are decoded again on request
+    pub fn clear(&mut self) {
+        self.images.clear();
+        self.failed.clear();
+    }
+
+    pub fn remove_unused_images(&mut self) -> usize {
+        self.failed
+            .retain(|_, frames_to_purge| *frames_to_purge > 0);
+        let amount_before = self.images.len();
+        self.images.retain(|_, image| !image.should_purge());
+        amount_before - self.images.len()
+    }
+}
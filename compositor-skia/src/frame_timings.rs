@@ -0,0 +1,249 @@
+use crate::RasterizationStats;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Time spent rasterizing pictures and shadows that were not cached yet during one frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RasterizationTimings {
+    pub pictures: usize,
+    pub picture_duration: Duration,
+    pub shadows: usize,
+    pub shadow_duration: Duration,
+}
+
+impl RasterizationTimings {
+    pub fn add_picture(&mut self, stats: &RasterizationStats) {
+        self.pictures += 1;
+        self.picture_duration += stats.total_duration;
+    }
+
+    pub fn add_shadow(&mut self, stats: &RasterizationStats) {
+        self.shadows += 1;
+        self.shadow_duration += stats.total_duration;
+    }
+
+    pub fn total_duration(&self) -> Duration {
+        self.picture_duration + self.shadow_duration
+    }
+}
+
+/// A measured part of a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FramePhase {
+    /// Walking the layer tree and recording draw commands, without rasterization
+    Traversal,
+    PictureRasterization,
+    ShadowRasterization,
+    FlushAndPresent,
+    Total,
+}
+
+impl FramePhase {
+    pub const ALL: [FramePhase; 5] = [
+        FramePhase::Traversal,
+        FramePhase::PictureRasterization,
+        FramePhase::ShadowRasterization,
+        FramePhase::FlushAndPresent,
+        FramePhase::Total,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameTimings {
+    pub frame_id: u64,
+    pub traversal: Duration,
+    pub rasterization: RasterizationTimings,
+    pub flush_and_present: Duration,
+    pub total: Duration,
+}
+
+impl FrameTimings {
+    pub fn duration(&self, phase: FramePhase) -> Duration {
+        match phase {
+            FramePhase::Traversal => self.traversal,
+            FramePhase::PictureRasterization => self.rasterization.picture_duration,
+            FramePhase::ShadowRasterization => self.rasterization.shadow_duration,
+            FramePhase::FlushAndPresent => self.flush_and_present,
+            FramePhase::Total => self.total,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DurationPercentiles {
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrameTimingsSummary {
+    pub frames: usize,
+    pub traversal: DurationPercentiles,
+    pub picture_rasterization: DurationPercentiles,
+    pub shadow_rasterization: DurationPercentiles,
+    pub flush_and_present: DurationPercentiles,
+    pub total: DurationPercentiles,
+}
+
+/// Keeps the timings of the most recent frames, dropping the oldest ones once full
+#[derive(Debug, Clone)]
+pub struct FrameTimingsHistory {
+    frames: VecDeque<FrameTimings>,
+    capacity: usize,
+}
+
+impl Default for FrameTimingsHistory {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+impl FrameTimingsHistory {
+    /// About four seconds at 60 frames per second
+    pub const DEFAULT_CAPACITY: usize = 240;
+
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            frames: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, timings: FrameTimings) {
+        while self.frames.len() >= self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(timings);
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        while self.frames.len() > self.capacity {
+            self.frames.pop_front();
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Return the timings of a given frame, starting with the oldest one
+    pub fn get(&self, index: usize) -> Option<&FrameTimings> {
+        self.frames.get(index)
+    }
+
+    pub fn last(&self) -> Option<&FrameTimings> {
+        self.frames.back()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &FrameTimings> {
+        self.frames.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    /// Nearest-rank percentile of a given phase, where `percentile` is between 0 and 100
+    pub fn percentile(&self, phase: FramePhase, percentile: f32) -> Duration {
+        percentile_of_sorted(&self.sorted_durations(phase), percentile)
+    }
+
+    pub fn summary(&self) -> FrameTimingsSummary {
+        let percentiles = |phase| {
+            let durations = self.sorted_durations(phase);
+            DurationPercentiles {
+                p50: percentile_of_sorted(&durations, 50.0),
+                p90: percentile_of_sorted(&durations, 90.0),
+                p99: percentile_of_sorted(&durations, 99.0),
+                max: durations.last().copied().unwrap_or_default(),
+            }
+        };
+
+        FrameTimingsSummary {
+            frames: self.frames.len(),
+            traversal: percentiles(FramePhase::Traversal),
+            picture_rasterization: percentiles(FramePhase::PictureRasterization),
+            shadow_rasterization: percentiles(FramePhase::ShadowRasterization),
+            flush_and_present: percentiles(FramePhase::FlushAndPresent),
+            total: percentiles(FramePhase::Total),
+        }
+    }
+
+    fn sorted_durations(&self, phase: FramePhase) -> Vec<Duration> {
+        let mut durations: Vec<Duration> = self
+            .frames
+            .iter()
+            .map(|frame| frame.duration(phase))
+            .collect();
+        durations.sort_unstable();
+        durations
+    }
+}
+
+fn percentile_of_sorted(durations: &[Duration], percentile: f32) -> Duration {
+    if durations.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank =
+        (percentile.clamp(0.0, 100.0) as f64 * durations.len() as f64 / 100.0).ceil() as usize;
+    durations[rank.clamp(1, durations.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(frame_id: u64, total_millis: u64) -> FrameTimings {
+        FrameTimings {
+            frame_id,
+            traversal: Duration::ZERO,
+            rasterization: RasterizationTimings::default(),
+            flush_and_present: Duration::ZERO,
+            total: Duration::from_millis(total_millis),
+        }
+    }
+
+    #[test]
+    pub fn oldest_frames_are_dropped() {
+        let mut history = FrameTimingsHistory::new(3);
+        for frame_id in 1..=5 {
+            history.push(frame(frame_id, frame_id));
+        }
+
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.get(0).unwrap().frame_id, 3);
+        assert_eq!(history.last().unwrap().frame_id, 5);
+    }
+
+    #[test]
+    pub fn percentiles() {
+        let mut history = FrameTimingsHistory::new(100);
+        for frame_id in 1..=100 {
+            history.push(frame(frame_id, frame_id));
+        }
+
+        let summary = history.summary();
+        assert_eq!(summary.frames, 100);
+        assert_eq!(summary.total.p50, Duration::from_millis(50));
+        assert_eq!(summary.total.p90, Duration::from_millis(90));
+        assert_eq!(summary.total.p99, Duration::from_millis(99));
+        assert_eq!(summary.total.max, Duration::from_millis(100));
+        assert_eq!(
+            history.percentile(FramePhase::Total, 0.0),
+            Duration::from_millis(1)
+        );
+        assert_eq!(summary.traversal.max, Duration::ZERO);
+    }
+}
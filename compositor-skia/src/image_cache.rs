@@ -1,40 +1,39 @@
+use crate::raster_cache::RasterCache;
 use skia_safe::{Image, Matrix};
-use std::collections::HashMap;
 use std::fmt::{Debug, Error, Formatter};
 
 #[derive(Debug)]
 pub struct CachedImage {
     image: Image,
-    was_used: bool,
     matrix: Matrix,
 }
 
 impl CachedImage {
     pub fn new(image: Image, matrix: Matrix) -> Self {
-        Self {
-            image,
-            was_used: false,
-            matrix,
-        }
-    }
-
-    pub fn mark_not_used(&mut self) {
-        self.was_used = false;
+        Self { image, matrix }
     }
+}
 
-    pub fn mark_used(&mut self) {
-        self.was_used = true;
-    }
+/// A rasterized picture or tile as listed by `Cache::picture_entries`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CachedPictureEntry {
+    pub picture_id: u32,
+    /// The transformation the picture was rasterized with
+    pub matrix: Matrix,
+    pub bytes: usize,
+    pub last_used_frame: u64,
+    pub pinned: bool,
 }
 
+/// Rasterized pictures and tiles by picture id
 pub struct ImageCache {
-    pub images: HashMap<u32, CachedImage>,
+    pub(crate) images: RasterCache<u32, CachedImage>,
 }
 
 impl Debug for ImageCache {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         f.debug_struct("ImageCache")
-            .field("images:", &self.images.keys())
+            .field("images:", &self.images)
             .finish()
     }
 }
@@ -42,24 +41,20 @@ impl Debug for ImageCache {
 impl ImageCache {
     pub fn new() -> Self {
         Self {
-            images: HashMap::new(),
+            images: RasterCache::new(),
         }
     }
 
     pub fn push_id_image(&mut self, picture_id: u32, image: Image, matrix: Matrix) {
-        let mut cached_image = CachedImage::new(image, matrix);
-        cached_image.mark_used();
-
-        self.images.insert(picture_id, cached_image);
+        let bytes = image.image_info().compute_min_byte_size();
+        self.images
+            .insert(picture_id, CachedImage::new(image, matrix), bytes);
     }
 
     pub fn get_picture_image(&mut self, picture_id: u32) -> Option<(Image, Matrix)> {
-        self.images.get_mut(&picture_id).and_then(|cached_image| {
-            cached_image.mark_used();
-            let image = cached_image.image.clone();
-            let matrix = cached_image.matrix;
-            Some((image, matrix))
-        })
+        self.images
+            .get(&picture_id)
+            .map(|cached_image| (cached_image.image.clone(), cached_image.matrix))
     }
 
     pub fn remove_picture_image(&mut self, picture_id: u32) {
@@ -67,26 +62,54 @@ impl ImageCache {
     }
 
     pub fn has_cached_image(&self, picture_id: u32) -> bool {
-        self.images.contains_key(&picture_id)
+        self.images.contains(&picture_id)
     }
 
     pub fn count_cached_images(&self) -> usize {
         self.images.len()
     }
 
+    pub fn count_cached_bytes(&self) -> usize {
+        self.images.bytes()
+    }
+
+    /// The size of the image of a given picture, or [`None`] if it is not cached
+    pub fn cached_image_bytes(&self, picture_id: u32) -> Option<usize> {
+        self.images.entry_bytes(&picture_id)
+    }
+
+    /// Keep the image of a given picture regardless of its age and the byte budget
+    pub fn pin_picture(&mut self, picture_id: u32) {
+        self.images.pin(picture_id);
+    }
+
+    pub fn unpin_picture(&mut self, picture_id: u32) -> bool {
+        self.images.unpin(&picture_id)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = CachedPictureEntry> + '_ {
+        self.images
+            .iter()
+            .map(|(picture_id, entry)| CachedPictureEntry {
+                picture_id: *picture_id,
+                matrix: entry.value.matrix,
+                bytes: entry.bytes,
+                last_used_frame: entry.last_used_frame,
+                pinned: self.images.is_pinned(picture_id),
+            })
+    }
+
     pub fn clear(&mut self) {
         self.images.clear();
     }
 
+    /// Start a new frame, images that are not used during it age by one frame
     pub fn mark_images_as_not_used(&mut self) {
-        for cached_image in self.images.values_mut() {
-            cached_image.mark_not_used();
-        }
+        self.images.begin_frame();
     }
 
-    pub fn remove_unused_images(&mut self) -> usize {
-        let size = self.images.len();
-        self.images.retain(|_, cached_image| cached_image.was_used);
-        size - self.images.len()
+    /// Remove unpinned images that were not used for more than a given amount of frames
+    pub fn remove_unused_images(&mut self, max_unused_frames: u64) -> usize {
+        self.images.remove_unused(max_unused_frames)
     }
 }
@@ -6,26 +6,51 @@ extern crate cfg_if;
 #[macro_use]
 extern crate lazy_static;
 
+pub use compositor_skia_platform::{Platform, PlatformContext};
 pub use skia_safe::{Canvas, Path, Picture};
 
-pub use cache::Cache;
-pub use image_cache::ImageCache;
+pub use cache::{Cache, CachePurgeLevel};
+pub use decoded_image_cache::{DecodedImageCache, decode_image_source};
+pub use frame_timings::{
+    DurationPercentiles, FramePhase, FrameTimings, FrameTimingsHistory, FrameTimingsSummary,
+    RasterizationTimings,
+};
+pub use image_cache::{CachedPictureEntry, ImageCache};
+pub use picture_merger::{PictureMergeCache, PictureMergeOptions, merge_small_pictures};
+pub use picture_scale::{PictureScaleOptions, ScaleQuantization};
+pub use platform_compositor::PlatformCompositor;
+pub use raster_cache::{CacheStatistics, RasterCachePolicy, RasterCacheStatistics};
+pub use raster_cache_heuristics::{PictureUsage, PictureUsageTracker, RasterCacheHeuristics};
+pub use render_thread::{
+    FrameCallback, FrameTiming, RenderThread, RenderThreadOptions, RenderThreadStatistics,
+};
 pub use renderers::*;
-pub use shadow_cache::ShadowCache;
+pub use shader_cache::{ShaderCache, ShaderError, compile_runtime_shader, make_runtime_shader};
+pub use shadow_cache::{CachedShadowEntry, ShadowCache};
 pub use skia_cacheless_compositor::SkiaCachelessCompositor;
 pub use skia_compositor::SkiaCompositor;
 pub use types::*;
+pub use validation::SkiaValidationRules;
 
 mod cache;
+mod decoded_image_cache;
+mod frame_timings;
 mod image_cache;
+mod picture_merger;
+mod picture_scale;
 mod platform_compositor;
+mod raster_cache;
+mod raster_cache_heuristics;
+mod render_thread;
 mod renderers;
+mod shader_cache;
 mod shadow_cache;
 mod skia_cacheless_compositor;
 mod skia_compositor;
 mod textures;
 mod types;
 mod utils;
+mod validation;
 
 cfg_if! {
     if #[cfg(feature = "phlow")] {
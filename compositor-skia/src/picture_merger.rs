@@ -0,0 +1,331 @@
+use crate::{SkiaPicture, into_skia_rect};
+use compositor::{Layer, Picture, PictureCacheHint, PictureLayer};
+use skia_safe::PictureRecorder;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+/// Controls which pictures are merged by `merge_small_pictures`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PictureMergeOptions {
+    /// Pictures with a larger cull rect area are never merged
+    pub max_picture_area: f32,
+    /// Runs of small pictures are split into groups of at most this many pictures.
+    /// Groups end after pictures picked by their id, so inserting or removing a picture
+    /// only changes its own group and the others are merged into the same pictures as before
+    pub max_group_size: usize,
+}
+
+impl Default for PictureMergeOptions {
+    fn default() -> Self {
+        Self {
+            max_picture_area: 64.0 * 64.0,
+            max_group_size: 64,
+        }
+    }
+}
+
+struct MergedPicture {
+    picture: Arc<dyn Picture>,
+    was_used: bool,
+}
+
+/// Keeps merged pictures by the ids of the pictures they were recorded from, so that the same
+/// group of pictures is merged into the same picture every frame and its raster cache entry is reused
+pub struct PictureMergeCache {
+    pictures: HashMap<Vec<u32>, MergedPicture>,
+}
+
+impl Debug for PictureMergeCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PictureMergeCache")
+            .field("pictures", &self.pictures.len())
+            .finish()
+    }
+}
+
+impl PictureMergeCache {
+    pub fn new() -> Self {
+        Self {
+            pictures: HashMap::new(),
+        }
+    }
+
+    fn get_or_record(&mut self, group: &[&PictureLayer]) -> Option<Arc<dyn Picture>> {
+        let ids: Vec<u32> = group.iter().map(|layer| layer.id()).collect();
+
+        if let Some(merged) = self.pictures.get_mut(&ids) {
+            merged.was_used = true;
+            return Some(merged.picture.clone());
+        }
+
+        let picture = record_merged_picture(group)?;
+        self.pictures.insert(
+            ids,
+            MergedPicture {
+                picture: picture.clone(),
+                was_used: true,
+            },
+        );
+        Some(picture)
+    }
+
+    pub fn mark_pictures_as_not_used(&mut self) {
+        self.pictures
+            .values_mut()
+            .for_each(|merged| merged.was_used = false);
+    }
+
+    /// Return the amount of removed merged pictures
+    pub fn remove_unused_pictures(&mut self) -> usize {
+        let count = self.pictures.len();
+        self.pictures.retain(|_, merged| merged.was_used);
+        count - self.pictures.len()
+    }
+
+    pub fn count_merged_pictures(&self) -> usize {
+        self.pictures.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.pictures.clear();
+    }
+}
+
+fn record_merged_picture(group: &[&PictureLayer]) -> Option<Arc<dyn Picture>> {
+    let bounds = group
+        .iter()
+        .map(|layer| into_skia_rect(&layer.cull_rect()))
+        .reduce(|mut bounds, cull_rect| {
+            bounds.join(cull_rect);
+            bounds
+        })?;
+
+    let mut recorder = PictureRecorder::new();
+    let canvas = recorder.begin_recording(bounds, false);
+    for layer in group {
+        let picture = layer.picture();
+        let picture = picture.any().downcast_ref::<skia_safe::Picture>()?;
+        canvas.draw_picture(picture, None, None);
+    }
+
+    recorder
+        .finish_recording_as_picture(None)
+        .map(|picture| Arc::new(SkiaPicture::new(picture)) as Arc<dyn Picture>)
+}
+
+/// A merged picture is cached if any of its pictures must be, and left to the compositor
+/// if any of them is
+fn merged_cache_hint(group: &[&PictureLayer]) -> PictureCacheHint {
+    let hints = || group.iter().map(|layer| layer.cache_hint());
+    if hints().any(|hint| hint == PictureCacheHint::Always) {
+        PictureCacheHint::Always
+    } else if hints().any(|hint| hint == PictureCacheHint::Auto) {
+        PictureCacheHint::Auto
+    } else {
+        PictureCacheHint::Never
+    }
+}
+
+/// Return the lengths of the groups a run of pictures with given ids is split into.
+/// A group of at least two pictures ends after a picture whose hashed id is a multiple of
+/// a quarter of the maximum group size, or when it reaches the maximum size
+fn group_lengths(ids: impl IntoIterator<Item = u32>, max_group_size: usize) -> Vec<usize> {
+    let max_group_size = max_group_size.max(2);
+    let boundary_modulus = (max_group_size / 4).max(2) as u32;
+    // ids are mostly sequential, the multiplicative hash spreads them
+    let is_boundary = |id: u32| (id.wrapping_mul(0x9E37_79B9) >> 16) % boundary_modulus == 0;
+
+    let mut lengths = vec![];
+    let mut length = 0;
+    for id in ids {
+        length += 1;
+        if length == max_group_size || (length >= 2 && is_boundary(id)) {
+            lengths.push(length);
+            length = 0;
+        }
+    }
+    if length > 0 {
+        lengths.push(length);
+    }
+    lengths
+}
+
+fn is_mergeable(layer: &Arc<dyn Layer>, options: &PictureMergeOptions) -> bool {
+    layer
+        .any()
+        .downcast_ref::<PictureLayer>()
+        .filter(|picture_layer| picture_layer.picture().any().is::<skia_safe::Picture>())
+        .map(|picture_layer| {
+            let cull_rect = picture_layer.cull_rect();
+            (cull_rect.width() * cull_rect.height()).0 <= options.max_picture_area
+        })
+        .unwrap_or(false)
+}
+
+/// Replace runs of consecutive small sibling pictures with one merged picture per group.
+/// Siblings are composed under the same transformation and opacity,
+/// so drawing them as one picture gives the same result with fewer draw calls and cache entries.
+pub fn merge_small_pictures(
+    layer: &Arc<dyn Layer>,
+    options: &PictureMergeOptions,
+    cache: &mut PictureMergeCache,
+) -> Arc<dyn Layer> {
+    let layers = layer.layers();
+    if layers.is_empty() {
+        return layer.clone();
+    }
+
+    let mut children: Vec<Arc<dyn Layer>> = Vec::with_capacity(layers.len());
+    let mut changed = false;
+    let mut index = 0;
+
+    while index < layers.len() {
+        let run_length = layers[index..]
+            .iter()
+            .take_while(|child| is_mergeable(child, options))
+            .count();
+
+        if run_length < 2 {
+            let child = merge_small_pictures(&layers[index], options, cache);
+            changed |= !Arc::ptr_eq(&child, &layers[index]);
+            children.push(child);
+            index += 1;
+            continue;
+        }
+
+        let run: Vec<&PictureLayer> = layers[index..index + run_length]
+            .iter()
+            .filter_map(|child| child.any().downcast_ref::<PictureLayer>())
+            .collect();
+
+        let mut group_start = index;
+        for group_length in
+            group_lengths(run.iter().map(|layer| layer.id()), options.max_group_size)
+        {
+            let chunk = &layers[group_start..group_start + group_length];
+            let group = &run[group_start - index..group_start - index + group_length];
+            group_start += group_length;
+
+            match (chunk.len() > 1)
+                .then(|| cache.get_or_record(group))
+                .flatten()
+            {
+                None => children.extend(chunk.iter().cloned()),
+                Some(picture) => {
+                    children.push(Arc::new(PictureLayer::new_with_cache_hint(
+                        picture,
+                        merged_cache_hint(group),
+                    )));
+                    changed = true;
+                }
+            }
+        }
+        index += run_length;
+    }
+
+    if changed {
+        layer.with_layers(children)
+    } else {
+        layer.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use compositor::OffsetLayer;
+    use skia_safe::{Paint, Rect};
+
+    fn small_picture() -> Arc<dyn Layer> {
+        let mut recorder = PictureRecorder::new();
+        let canvas = recorder.begin_recording(Rect::new(0.0, 0.0, 10.0, 10.0), false);
+        canvas.draw_rect(Rect::new(0.0, 0.0, 10.0, 10.0), &Paint::default());
+        let picture = recorder.finish_recording_as_picture(None).unwrap();
+
+        Arc::new(PictureLayer::new(Arc::new(SkiaPicture::new(picture)), true))
+    }
+
+    #[test]
+    pub fn merged_picture_is_reused() {
+        let root = OffsetLayer::new().with_layers(vec![small_picture(), small_picture()]);
+        let options = PictureMergeOptions::default();
+        let mut cache = PictureMergeCache::new();
+
+        let first = merge_small_pictures(&root, &options, &mut cache);
+        let second = merge_small_pictures(&root, &options, &mut cache);
+
+        assert_eq!(first.layers().len(), 1);
+        assert_eq!(cache.count_merged_pictures(), 1);
+
+        let first_id = first.layers()[0]
+            .any()
+            .downcast_ref::<PictureLayer>()
+            .unwrap()
+            .id();
+        let second_id = second.layers()[0]
+            .any()
+            .downcast_ref::<PictureLayer>()
+            .unwrap()
+            .id();
+        assert_eq!(first_id, second_id);
+    }
+
+    #[test]
+    pub fn group_boundaries_follow_picture_ids() {
+        let groups = |ids: &[u32]| {
+            let mut start = 0;
+            group_lengths(ids.iter().copied(), 16)
+                .into_iter()
+                .map(|length| {
+                    start += length;
+                    ids[start - length..start].to_vec()
+                })
+                .collect::<Vec<Vec<u32>>>()
+        };
+
+        let ids = (1..=64).collect::<Vec<u32>>();
+        let before = groups(&ids);
+        assert!(before.len() > 2);
+        assert!(before.iter().all(|group| group.len() <= 16));
+
+        let mut inserted = ids.clone();
+        inserted.insert(10, 1000);
+        let mut removed = ids.clone();
+        removed.remove(30);
+
+        for after in [groups(&inserted), groups(&removed)] {
+            let changed = after.iter().filter(|group| !before.contains(group)).count();
+            assert_eq!(changed, 1);
+        }
+    }
+
+    #[test]
+    pub fn merged_pictures_are_reused_after_an_insertion() {
+        let pictures = (0..64).map(|_| small_picture()).collect::<Vec<_>>();
+        // no group reaches the maximum size, so groups only end after picked pictures
+        let options = PictureMergeOptions {
+            max_group_size: 1024,
+            ..Default::default()
+        };
+        let mut cache = PictureMergeCache::new();
+
+        merge_small_pictures(
+            &OffsetLayer::new().with_layers(pictures.clone()),
+            &options,
+            &mut cache,
+        );
+        let merged_before = cache.count_merged_pictures();
+
+        let mut inserted = pictures;
+        inserted.insert(10, small_picture());
+        merge_small_pictures(
+            &OffsetLayer::new().with_layers(inserted),
+            &options,
+            &mut cache,
+        );
+
+        // only the group with the new picture, possibly split in two, is recorded again
+        assert!(cache.count_merged_pictures() - merged_before <= 2);
+    }
+}
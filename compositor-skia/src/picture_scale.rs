@@ -0,0 +1,121 @@
+use skia_safe::{Matrix, Size};
+
+/// The scales pictures are rasterized at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScaleQuantization {
+    /// Rasterize at the exact scale of the canvas
+    Exact,
+    /// Round the scale up to the next power of two
+    PowerOfTwo,
+    /// Round the scale up to the next multiple of a given step
+    Step(f32),
+}
+
+impl ScaleQuantization {
+    pub fn quantize(&self, scale: f32) -> f32 {
+        if !scale.is_finite() || scale <= 0.0 {
+            return scale;
+        }
+        match self {
+            ScaleQuantization::Exact => scale,
+            ScaleQuantization::PowerOfTwo => scale.log2().ceil().exp2(),
+            ScaleQuantization::Step(step) if *step > 0.0 => (scale / step).ceil() * step,
+            ScaleQuantization::Step(_) => scale,
+        }
+    }
+}
+
+/// Controls when cached picture images are reused under a different canvas scale
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PictureScaleOptions {
+    /// Cached images are drawn as they are while their scale differs from the
+    /// quantized canvas scale by at most this fraction
+    pub tolerance: f32,
+    pub quantization: ScaleQuantization,
+    /// Rasterize pictures at the new scale with the rasterizer of the cache once the tolerance
+    /// is exceeded, drawing the old image scaled until the new one is ready. A background
+    /// rasterizer such as `ThreadPoolRasterizer` keeps the rasterization off the render thread.
+    /// Otherwise the picture is drawn directly and rasterized again in the next frame
+    pub rescale_in_background: bool,
+}
+
+impl Default for PictureScaleOptions {
+    fn default() -> Self {
+        Self {
+            tolerance: 0.01,
+            quantization: ScaleQuantization::Exact,
+            rescale_in_background: false,
+        }
+    }
+}
+
+impl PictureScaleOptions {
+    /// Return a given canvas matrix with its scale replaced by the quantized one
+    pub fn rasterization_matrix(&self, canvas_matrix: &Matrix) -> Matrix {
+        let mut matrix = *canvas_matrix;
+        if let Some(scale) = canvas_matrix.decompose_scale(None) {
+            let quantized = Size::new(
+                self.quantization.quantize(scale.width),
+                self.quantization.quantize(scale.height),
+            );
+            if quantized != scale {
+                matrix.pre_scale(
+                    (
+                        quantized.width / scale.width,
+                        quantized.height / scale.height,
+                    ),
+                    None,
+                );
+            }
+        }
+        matrix
+    }
+
+    /// True if an image rasterized with a given matrix can be drawn under a given canvas matrix
+    pub fn can_reuse(&self, image_matrix: &Matrix, canvas_matrix: &Matrix) -> bool {
+        let target_scale = self
+            .rasterization_matrix(canvas_matrix)
+            .decompose_scale(None);
+        match (image_matrix.decompose_scale(None), target_scale) {
+            (Some(image_scale), Some(target_scale)) => {
+                is_within_tolerance(image_scale.width, target_scale.width, self.tolerance)
+                    && is_within_tolerance(image_scale.height, target_scale.height, self.tolerance)
+            }
+            (image_scale, target_scale) => image_scale == target_scale,
+        }
+    }
+}
+
+fn is_within_tolerance(scale: f32, target: f32, tolerance: f32) -> bool {
+    scale == target || (scale / target - 1.0).abs() <= tolerance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn quantized_scales() {
+        assert_eq!(ScaleQuantization::Exact.quantize(1.3), 1.3);
+        assert_eq!(ScaleQuantization::PowerOfTwo.quantize(1.3), 2.0);
+        assert_eq!(ScaleQuantization::PowerOfTwo.quantize(0.3), 0.5);
+        assert_eq!(ScaleQuantization::PowerOfTwo.quantize(4.0), 4.0);
+        assert_eq!(ScaleQuantization::Step(0.25).quantize(1.1), 1.25);
+    }
+
+    #[test]
+    pub fn reuse_within_tolerance() {
+        let options = PictureScaleOptions::default();
+        let image_matrix = Matrix::scale((1.0, 1.0));
+
+        assert!(options.can_reuse(&image_matrix, &Matrix::scale((1.0001, 1.0001))));
+        assert!(!options.can_reuse(&image_matrix, &Matrix::scale((1.5, 1.5))));
+
+        let options = PictureScaleOptions {
+            quantization: ScaleQuantization::PowerOfTwo,
+            ..Default::default()
+        };
+        let image_matrix = options.rasterization_matrix(&Matrix::scale((1.2, 1.2)));
+        assert!(options.can_reuse(&image_matrix, &Matrix::scale((1.9, 1.9))));
+    }
+}
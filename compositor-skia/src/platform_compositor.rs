@@ -1,10 +1,15 @@
 use std::error::Error;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use anyhow::Result;
 use skia_safe::{Color, Color4f, Font, FontMgr, FontStyle, ISize, Paint, Point, Surface};
 
-use crate::{Cache, SkiaCachelessCompositor, SkiaCompositor};
+use crate::{
+    Cache, FrameTimings, FrameTimingsHistory, PictureScaleOptions, RasterCacheHeuristics,
+    RasterCachePolicy, RasterizationTimings, Rasterizer, ScaleQuantization, ShadowDownsampling,
+    SkiaCachelessCompositor, SkiaCompositor,
+};
 use compositor::{Compositor, Layer};
 use compositor_skia_platform::{Platform, PlatformContext};
 
@@ -25,6 +30,12 @@ pub struct PlatformCompositor {
     cache: Cache,
     render_fps: Option<fps_counter::FPSCounter>,
     scale_factor: f32,
+    frame_timings: FrameTimingsHistory,
+    frame_id: u64,
+    picture_scale: PictureScaleOptions,
+    shadow_scale_quantization: ScaleQuantization,
+    shadow_downsampling: Option<ShadowDownsampling>,
+    strict_validation: bool,
 }
 
 impl PlatformCompositor {
@@ -36,6 +47,12 @@ impl PlatformCompositor {
             cache: Cache::new(),
             render_fps: None,
             scale_factor: 1.0,
+            frame_timings: FrameTimingsHistory::default(),
+            frame_id: 0,
+            picture_scale: PictureScaleOptions::default(),
+            shadow_scale_quantization: SkiaCompositor::DEFAULT_SHADOW_SCALE_QUANTIZATION,
+            shadow_downsampling: None,
+            strict_validation: cfg!(debug_assertions),
         }
     }
 
@@ -66,6 +83,57 @@ impl PlatformCompositor {
         self.render_fps.take();
     }
 
+    pub fn set_cache_policy(&mut self, policy: RasterCachePolicy) {
+        self.cache.set_policy(policy);
+    }
+
+    /// Let the compositor decide which pictures with `PictureCacheHint::Auto` are cached
+    pub fn set_raster_cache_heuristics(&mut self, heuristics: Option<RasterCacheHeuristics>) {
+        self.cache.set_raster_cache_heuristics(heuristics);
+    }
+
+    /// Rasterize cached pictures and shadows with a given rasterizer, for example in the background
+    /// with a `ThreadPoolRasterizer`
+    pub fn set_rasterizer(&mut self, rasterizer: impl Rasterizer + 'static) {
+        self.cache.set_rasterizer(rasterizer);
+    }
+
+    pub fn set_picture_scale_options(&mut self, picture_scale: PictureScaleOptions) {
+        self.picture_scale = picture_scale;
+    }
+
+    /// Change the scales shadows are rasterized and cached at
+    pub fn set_shadow_scale_quantization(&mut self, quantization: ScaleQuantization) {
+        self.shadow_scale_quantization = quantization;
+    }
+
+    /// Rasterize shadows with a large blur radius at a reduced resolution, disabled by default
+    pub fn set_shadow_downsampling(&mut self, downsampling: Option<ShadowDownsampling>) {
+        self.shadow_downsampling = downsampling;
+    }
+
+    /// Validate every submitted tree before drawing it and skip invalid ones,
+    /// enabled by default in debug builds only
+    pub fn set_strict_validation(&mut self, strict_validation: bool) {
+        self.strict_validation = strict_validation;
+    }
+
+    /// Return true if images are still being prepared in the background
+    /// and another frame should be drawn to show them
+    pub fn has_pending_work(&self) -> bool {
+        self.cache.has_pending_work()
+    }
+
+    /// Timings of the most recently drawn frames
+    pub fn frame_timings(&self) -> &FrameTimingsHistory {
+        &self.frame_timings
+    }
+
+    /// Change how many frames are kept in the timings history
+    pub fn set_frame_timings_capacity(&mut self, capacity: usize) {
+        self.frame_timings.set_capacity(capacity);
+    }
+
     pub fn draw(&mut self) -> Result<(), Box<dyn Error>> {
         let current_layer = self
             .latest_frame
@@ -76,14 +144,26 @@ impl PlatformCompositor {
             .clone();
 
         if let Some(layer) = current_layer {
+            let frame_start = Instant::now();
+            let mut composed = frame_start;
+            let mut rasterization = RasterizationTimings::default();
+
             self.context.with_surface(|surface| {
                 let canvas = surface.canvas();
                 canvas.clear(Color::WHITE);
                 canvas.reset_matrix();
                 canvas.scale((self.scale_factor, self.scale_factor));
 
-                SkiaCompositor::new(Some(self.platform.clone()), canvas, &mut self.cache)
-                    .compose(layer);
+                let mut compositor =
+                    SkiaCompositor::new(Some(self.platform.clone()), canvas, &mut self.cache)
+                        .with_picture_scale_options(self.picture_scale)
+                        .with_shadow_scale_quantization(self.shadow_scale_quantization)
+                        .with_strict_validation(self.strict_validation);
+                if let Some(downsampling) = self.shadow_downsampling {
+                    compositor = compositor.with_shadow_downsampling(downsampling);
+                }
+                compositor.compose(layer);
+                rasterization = *compositor.rasterization_timings();
 
                 self.render_fps.as_mut().map(|counter| {
                     canvas.draw_str(
@@ -93,7 +173,10 @@ impl PlatformCompositor {
                         &FPS_PAINT,
                     );
                 });
-            })
+                composed = Instant::now();
+            });
+
+            self.push_frame_timings(frame_start, composed, rasterization);
         }
 
         Ok(())
@@ -109,6 +192,9 @@ impl PlatformCompositor {
             .clone();
 
         if let Some(layer) = current_layer {
+            let frame_start = Instant::now();
+            let mut composed = frame_start;
+
             self.context.with_surface(|surface| {
                 let canvas = surface.canvas();
                 canvas.clear(Color::WHITE);
@@ -123,9 +209,31 @@ impl PlatformCompositor {
                         &FPS_PAINT,
                     );
                 });
-            })
+                composed = Instant::now();
+            });
+
+            self.push_frame_timings(frame_start, composed, RasterizationTimings::default());
         }
 
         Ok(())
     }
+
+    /// Everything between the start of the frame and the end of composition except rasterization
+    /// counts as traversal, everything after composition as flush and present
+    fn push_frame_timings(
+        &mut self,
+        frame_start: Instant,
+        composed: Instant,
+        rasterization: RasterizationTimings,
+    ) {
+        let frame_end = Instant::now();
+        self.frame_id += 1;
+        self.frame_timings.push(FrameTimings {
+            frame_id: self.frame_id,
+            traversal: (composed - frame_start).saturating_sub(rasterization.total_duration()),
+            rasterization,
+            flush_and_present: frame_end.saturating_duration_since(composed),
+            total: frame_end - frame_start,
+        });
+    }
 }
@@ -0,0 +1,332 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Formatter};
+use std::hash::Hash;
+
+/// Decides how long rasterized pictures, tiles and shadows stay in a `Cache`.
+/// Tiles are rasterized as pictures and share their entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RasterCachePolicy {
+    /// Upper bound for the total size of cached images in bytes.
+    /// Once exceeded, the least recently used images are evicted first
+    pub byte_budget: Option<usize>,
+    /// Images that were not used for more frames than this are evicted even within the budget
+    pub max_unused_frames: u64,
+}
+
+impl Default for RasterCachePolicy {
+    fn default() -> Self {
+        Self {
+            byte_budget: Some(256 * 1024 * 1024),
+            max_unused_frames: 120,
+        }
+    }
+}
+
+/// The state of the cached picture or shadow images of a `Cache`.
+/// Counters accumulate until they are reset with `Cache::reset_statistics`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RasterCacheStatistics {
+    pub entries: usize,
+    pub pinned: usize,
+    pub bytes: usize,
+    /// Lookups that found an image
+    pub hits: u64,
+    /// Lookups that did not find an image, after which the picture or shadow is usually rasterized
+    pub misses: u64,
+    /// Images removed because they were unused for too long, did not fit in the byte budget
+    /// or were purged. Explicitly invalidated images are not counted
+    pub evictions: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStatistics {
+    /// The frame counter of the cache, compare it to the last used frame of the entries
+    pub frame: u64,
+    /// Rasterized pictures and tiles
+    pub pictures: RasterCacheStatistics,
+    pub shadows: RasterCacheStatistics,
+    /// Compiled runtime shaders together with cached compilation errors
+    pub shaders: usize,
+}
+
+pub(crate) struct RasterCacheEntry<V> {
+    pub(crate) value: V,
+    pub(crate) bytes: usize,
+    pub(crate) last_used_frame: u64,
+}
+
+/// Rasterized images by key, with their size and the frame in which they were last used.
+/// Pinned keys are never evicted, even if they are rasterized again later
+pub(crate) struct RasterCache<K, V> {
+    entries: HashMap<K, RasterCacheEntry<V>>,
+    pinned: HashSet<K>,
+    frame: u64,
+    bytes: usize,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl<K: Debug, V> Debug for RasterCache<K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RasterCache")
+            .field("entries", &self.entries.keys())
+            .field("pinned", &self.pinned)
+            .field("frame", &self.frame)
+            .field("bytes", &self.bytes)
+            .field("hits", &self.hits)
+            .field("misses", &self.misses)
+            .field("evictions", &self.evictions)
+            .finish()
+    }
+}
+
+impl<K: Hash + Eq + Clone, V> RasterCache<K, V> {
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            pinned: HashSet::new(),
+            frame: 0,
+            bytes: 0,
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+        }
+    }
+
+    /// Return the value of a given key, marking it as used in the current frame
+    pub(crate) fn get(&mut self, key: &K) -> Option<&V> {
+        let frame = self.frame;
+        match self.entries.get_mut(key) {
+            None => {
+                self.misses += 1;
+                None
+            }
+            Some(entry) => {
+                self.hits += 1;
+                entry.last_used_frame = frame;
+                Some(&entry.value)
+            }
+        }
+    }
+
+    pub(crate) fn insert(&mut self, key: K, value: V, bytes: usize) {
+        self.remove(&key);
+        self.bytes += bytes;
+        self.entries.insert(
+            key,
+            RasterCacheEntry {
+                value,
+                bytes,
+                last_used_frame: self.frame,
+            },
+        );
+    }
+
+    pub(crate) fn remove(&mut self, key: &K) -> bool {
+        match self.entries.remove(key) {
+            None => false,
+            Some(entry) => {
+                self.bytes -= entry.bytes;
+                true
+            }
+        }
+    }
+
+    /// Remove a given key because of memory constraints, counting it as an eviction
+    pub(crate) fn evict(&mut self, key: &K) -> bool {
+        let removed = self.remove(key);
+        if removed {
+            self.evictions += 1;
+        }
+        removed
+    }
+
+    pub(crate) fn contains(&self, key: &K) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub(crate) fn entry_bytes(&self, key: &K) -> Option<usize> {
+        self.entries.get(key).map(|entry| entry.bytes)
+    }
+
+    pub(crate) fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub(crate) fn is_pinned(&self, key: &K) -> bool {
+        self.pinned.contains(key)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&K, &RasterCacheEntry<V>)> {
+        self.entries.iter()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.bytes = 0;
+    }
+
+    pub(crate) fn statistics(&self) -> RasterCacheStatistics {
+        RasterCacheStatistics {
+            entries: self.entries.len(),
+            pinned: self
+                .pinned
+                .iter()
+                .filter(|key| self.entries.contains_key(key))
+                .count(),
+            bytes: self.bytes,
+            hits: self.hits,
+            misses: self.misses,
+            evictions: self.evictions,
+        }
+    }
+
+    pub(crate) fn reset_counters(&mut self) {
+        self.hits = 0;
+        self.misses = 0;
+        self.evictions = 0;
+    }
+
+    pub(crate) fn pin(&mut self, key: K) {
+        self.pinned.insert(key);
+    }
+
+    /// Return true if the key was pinned
+    pub(crate) fn unpin(&mut self, key: &K) -> bool {
+        self.pinned.remove(key)
+    }
+
+    /// Start a new frame, entries that are not used during it age by one frame
+    pub(crate) fn begin_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    /// Remove unpinned entries that were not used for more than a given amount of frames.
+    /// Return the amount of removed entries
+    pub(crate) fn remove_unused(&mut self, max_unused_frames: u64) -> usize {
+        let frame = self.frame;
+        self.evict_where(|entry| frame - entry.last_used_frame > max_unused_frames)
+    }
+
+    /// Remove every unpinned entry, return the amount of removed entries
+    pub(crate) fn remove_unpinned(&mut self) -> usize {
+        self.evict_where(|_| true)
+    }
+
+    fn evict_where(&mut self, predicate: impl Fn(&RasterCacheEntry<V>) -> bool) -> usize {
+        let evicted: Vec<K> = self
+            .entries
+            .iter()
+            .filter(|(key, entry)| predicate(entry) && !self.pinned.contains(key))
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        evicted.iter().for_each(|key| {
+            self.evict(key);
+        });
+        evicted.len()
+    }
+
+    /// Unpinned keys together with the frame they were last used in, least recently used first.
+    /// Entries used in the current frame are still being drawn and are never candidates
+    pub(crate) fn eviction_candidates(&self) -> Vec<(u64, K)> {
+        let mut candidates: Vec<(u64, K)> = self
+            .entries
+            .iter()
+            .filter(|(key, entry)| entry.last_used_frame < self.frame && !self.pinned.contains(key))
+            .map(|(key, entry)| (entry.last_used_frame, key.clone()))
+            .collect();
+        candidates.sort_by_key(|(last_used_frame, _)| *last_used_frame);
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn unused_entries_age() {
+        let mut cache = RasterCache::<u32, ()>::new();
+        cache.insert(1, (), 10);
+        cache.insert(2, (), 20);
+        cache.insert(3, (), 30);
+        cache.pin(2);
+
+        cache.begin_frame();
+        cache.begin_frame();
+        cache.get(&3);
+        assert_eq!(cache.remove_unused(2), 0);
+
+        cache.begin_frame();
+        assert_eq!(cache.remove_unused(2), 1);
+        assert!(!cache.contains(&1));
+        assert!(cache.contains(&2));
+        assert!(cache.contains(&3));
+        assert_eq!(cache.bytes(), 50);
+    }
+
+    #[test]
+    pub fn least_recently_used_first() {
+        let mut cache = RasterCache::<u32, ()>::new();
+        cache.insert(1, (), 10);
+        cache.begin_frame();
+        cache.insert(2, (), 10);
+        cache.insert(3, (), 10);
+        cache.pin(3);
+        cache.begin_frame();
+        cache.get(&1);
+
+        let keys = |cache: &RasterCache<u32, ()>| -> Vec<u32> {
+            cache
+                .eviction_candidates()
+                .into_iter()
+                .map(|(_, key)| key)
+                .collect()
+        };
+        // the first picture is used in the current frame
+        assert_eq!(keys(&cache), vec![2]);
+
+        cache.begin_frame();
+        assert_eq!(keys(&cache), vec![2, 1]);
+    }
+
+    #[test]
+    pub fn statistics() {
+        let mut cache = RasterCache::<u32, ()>::new();
+        cache.insert(1, (), 10);
+        cache.insert(2, (), 20);
+        cache.pin(2);
+        cache.pin(3);
+
+        cache.get(&1);
+        cache.get(&3);
+        cache.remove(&1);
+        cache.insert(4, (), 5);
+        assert_eq!(cache.remove_unpinned(), 1);
+
+        assert_eq!(
+            cache.statistics(),
+            RasterCacheStatistics {
+                entries: 1,
+                pinned: 1,
+                bytes: 20,
+                hits: 1,
+                misses: 1,
+                evictions: 1,
+            }
+        );
+
+        cache.reset_counters();
+        assert_eq!(cache.statistics().hits, 0);
+    }
+}
@@ -0,0 +1,194 @@
+use compositor::Picture;
+use skia_safe::Matrix;
+use std::collections::HashMap;
+
+/// Decides whether pictures with `PictureCacheHint::Auto` are rasterized and cached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RasterCacheHeuristics {
+    /// A picture is cached once it was drawn in this many consecutive frames
+    pub min_consecutive_frames: u32,
+    /// Pictures with at most this many drawing operations are cheaper to draw than to cache
+    pub max_cheap_op_count: usize,
+    /// Used instead of the operation count for pictures that do not know it
+    pub max_cheap_bytes: usize,
+    /// Pictures with at least this many drawing operations are cached right away
+    /// when their transformation changes between frames
+    pub min_complex_op_count: usize,
+}
+
+impl Default for RasterCacheHeuristics {
+    fn default() -> Self {
+        Self {
+            min_consecutive_frames: 3,
+            max_cheap_op_count: 4,
+            max_cheap_bytes: 1024,
+            min_complex_op_count: 256,
+        }
+    }
+}
+
+impl RasterCacheHeuristics {
+    pub fn is_cheap(&self, picture: &dyn Picture) -> bool {
+        match picture.approximate_op_count() {
+            0 => picture.approximate_bytes_used() <= self.max_cheap_bytes,
+            op_count => op_count <= self.max_cheap_op_count,
+        }
+    }
+
+    pub fn is_complex(&self, picture: &dyn Picture) -> bool {
+        picture.approximate_op_count() >= self.min_complex_op_count
+    }
+
+    pub fn should_cache(&self, picture: &dyn Picture, usage: &PictureUsage) -> bool {
+        if self.is_cheap(picture) {
+            return false;
+        }
+        if usage.is_animated() && self.is_complex(picture) {
+            return true;
+        }
+        usage.consecutive_frames() >= self.min_consecutive_frames
+    }
+}
+
+/// How a picture was drawn during the recent frames
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PictureUsage {
+    consecutive_frames: u32,
+    last_seen_frame: u64,
+    matrix: Matrix,
+    animated: bool,
+}
+
+impl PictureUsage {
+    /// The amount of frames in a row the picture was drawn in, including the current one
+    pub fn consecutive_frames(&self) -> u32 {
+        self.consecutive_frames
+    }
+
+    /// True if the picture was drawn under a different transformation in the previous frame
+    pub fn is_animated(&self) -> bool {
+        self.animated
+    }
+}
+
+/// Follows the pictures left to the compositor from frame to frame
+#[derive(Debug, Default)]
+pub struct PictureUsageTracker {
+    pictures: HashMap<u32, PictureUsage>,
+    frame: u64,
+}
+
+impl PictureUsageTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    /// Record that a given picture is drawn in the current frame under a given transformation
+    pub fn observe(&mut self, picture_id: u32, matrix: Matrix) -> PictureUsage {
+        let frame = self.frame;
+        let usage = self
+            .pictures
+            .entry(picture_id)
+            .and_modify(|usage| {
+                if usage.last_seen_frame + 1 == frame {
+                    usage.consecutive_frames += 1;
+                    usage.animated = usage.matrix != matrix;
+                } else if usage.last_seen_frame != frame {
+                    usage.consecutive_frames = 1;
+                    usage.animated = false;
+                }
+                usage.last_seen_frame = frame;
+                usage.matrix = matrix;
+            })
+            .or_insert(PictureUsage {
+                consecutive_frames: 1,
+                last_seen_frame: frame,
+                matrix,
+                animated: false,
+            });
+        *usage
+    }
+
+    /// Forget pictures that were not drawn in the current frame, return their amount
+    pub fn remove_unseen_pictures(&mut self) -> usize {
+        let frame = self.frame;
+        let count = self.pictures.len();
+        self.pictures
+            .retain(|_, usage| usage.last_seen_frame == frame);
+        count - self.pictures.len()
+    }
+
+    pub fn count_tracked_pictures(&self) -> usize {
+        self.pictures.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.pictures.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use compositor::Rectangle;
+    use std::any::Any;
+
+    #[derive(Debug)]
+    struct TestPicture(usize);
+
+    impl Picture for TestPicture {
+        fn unique_id(&self) -> u32 {
+            1
+        }
+
+        fn cull_rect(&self) -> Rectangle {
+            Rectangle::extent(10.0, 10.0)
+        }
+
+        fn approximate_op_count(&self) -> usize {
+            self.0
+        }
+
+        fn any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    #[test]
+    pub fn cached_after_consecutive_frames() {
+        let heuristics = RasterCacheHeuristics::default();
+        let picture = TestPicture(100);
+        let mut tracker = PictureUsageTracker::new();
+
+        let mut decisions = vec![];
+        for _ in 0..3 {
+            tracker.begin_frame();
+            let usage = tracker.observe(1, Matrix::new_identity());
+            decisions.push(heuristics.should_cache(&picture, &usage));
+        }
+        assert_eq!(decisions, vec![false, false, true]);
+
+        assert!(
+            !heuristics.should_cache(&TestPicture(2), &tracker.observe(1, Matrix::new_identity()))
+        );
+    }
+
+    #[test]
+    pub fn complex_animated_picture_is_cached() {
+        let heuristics = RasterCacheHeuristics::default();
+        let mut tracker = PictureUsageTracker::new();
+
+        tracker.begin_frame();
+        tracker.observe(1, Matrix::new_identity());
+        tracker.begin_frame();
+        let usage = tracker.observe(1, Matrix::translate((10.0, 0.0)));
+
+        assert!(usage.is_animated());
+        assert!(heuristics.should_cache(&TestPicture(1000), &usage));
+        assert!(!heuristics.should_cache(&TestPicture(100), &usage));
+    }
+}
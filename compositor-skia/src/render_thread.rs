@@ -0,0 +1,400 @@
+use crate::{
+    FrameTimingsHistory, Platform, PlatformCompositor, PlatformContext, RasterCacheHeuristics,
+};
+use compositor::Layer;
+use log::error;
+use skia_safe::ISize;
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// A frame number together with the moment a frame began or was presented,
+/// relative to the start of the render thread
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameTiming {
+    pub frame_id: u64,
+    pub timestamp: Duration,
+}
+
+/// Called on the render thread, so it must not block
+pub type FrameCallback = Arc<dyn Fn(FrameTiming) + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderThreadOptions {
+    /// Frames start on multiples of this interval, usually the refresh interval of the display
+    pub frame_interval: Duration,
+    /// Draw with `SkiaCachelessCompositor` instead of `SkiaCompositor`
+    pub cacheless: bool,
+    /// Decide which pictures with `PictureCacheHint::Auto` are cached
+    pub raster_cache_heuristics: Option<RasterCacheHeuristics>,
+}
+
+impl Default for RenderThreadOptions {
+    fn default() -> Self {
+        Self {
+            frame_interval: Duration::from_micros(16_667),
+            cacheless: false,
+            raster_cache_heuristics: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderThreadStatistics {
+    pub presented_frames: u64,
+    /// Submitted trees that were replaced by a newer one before they could be drawn
+    pub coalesced_frames: u64,
+}
+
+#[derive(Default)]
+struct RenderThreadState {
+    pending_layer: Option<Arc<dyn Layer>>,
+    pending_submissions: u64,
+    redraw_requested: bool,
+    pending_size: Option<ISize>,
+    pending_scale_factor: Option<f32>,
+    pending_strict_validation: Option<bool>,
+    stopped: bool,
+    on_frame_begin: Option<FrameCallback>,
+    on_frame_presented: Option<FrameCallback>,
+    statistics: RenderThreadStatistics,
+    frame_timings: FrameTimingsHistory,
+}
+
+impl RenderThreadState {
+    fn has_work(&self) -> bool {
+        self.stopped
+            || self.pending_layer.is_some()
+            || self.redraw_requested
+            || self.pending_size.is_some()
+            || self.pending_scale_factor.is_some()
+    }
+}
+
+#[derive(Default)]
+struct RenderThreadShared {
+    state: Mutex<RenderThreadState>,
+    wake_up: Condvar,
+}
+
+impl RenderThreadShared {
+    fn lock(&self) -> MutexGuard<'_, RenderThreadState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn update(&self, update: impl FnOnce(&mut RenderThreadState)) {
+        update(&mut self.lock());
+        self.wake_up.notify_one();
+    }
+}
+
+/// The platform and its context wrap native rendering objects (Metal, D3D, GL or EGL contexts
+/// and their surfaces) that are not marked as `Send`
+struct SendablePlatformContext {
+    platform: Platform,
+    context: PlatformContext,
+}
+
+// SAFETY: the native objects may be used from another thread as long as only one thread uses
+// them at a time, and a GL or EGL context is not current on the thread it was created on.
+// `RenderThread::spawn` takes ownership of the platform and the context, so the host has
+// no way to use them afterwards, and `PlatformContext` only makes its context current inside
+// `with_surface` and `resize_surface`, which are called by the render thread alone.
+// The compositor and its cache are created on the render thread and never leave it.
+unsafe impl Send for SendablePlatformContext {}
+
+impl SendablePlatformContext {
+    fn into_compositor(self) -> PlatformCompositor {
+        PlatformCompositor::new(self.platform, self.context)
+    }
+}
+
+/// Creates a `PlatformCompositor` on a dedicated thread and draws with it.
+/// Trees submitted faster than the frame interval are coalesced, only the latest one is drawn.
+/// Frames are only drawn when there is something new to show: a submitted tree, a requested
+/// redraw, a resize, or images that the compositor prepared in the background.
+pub struct RenderThread {
+    shared: Arc<RenderThreadShared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Debug for RenderThread {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RenderThread")
+            .field("statistics", &self.statistics())
+            .finish()
+    }
+}
+
+impl RenderThread {
+    pub fn spawn(
+        platform: Platform,
+        context: PlatformContext,
+        options: RenderThreadOptions,
+    ) -> std::io::Result<Self> {
+        Self::spawn_with(platform, context, options, |_| {})
+    }
+
+    /// Spawn a render thread and configure its compositor on that thread before the first frame
+    pub fn spawn_with(
+        platform: Platform,
+        context: PlatformContext,
+        options: RenderThreadOptions,
+        configure: impl FnOnce(&mut PlatformCompositor) + Send + 'static,
+    ) -> std::io::Result<Self> {
+        let shared = Arc::new(RenderThreadShared::default());
+        let platform_context = SendablePlatformContext { platform, context };
+
+        let thread_shared = shared.clone();
+        let thread = std::thread::Builder::new()
+            .name("compositor-render".to_string())
+            .spawn(move || {
+                let mut compositor = platform_context.into_compositor();
+                compositor.set_raster_cache_heuristics(options.raster_cache_heuristics);
+                configure(&mut compositor);
+                render_loop(compositor, thread_shared, options)
+            })?;
+
+        Ok(Self {
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    /// Submit a tree to be drawn in the next frame. Can be called from any thread
+    pub fn submit_layer(&self, layer: Arc<dyn Layer>) {
+        self.shared.update(|state| {
+            state.pending_layer = Some(layer);
+            state.pending_submissions += 1;
+        });
+    }
+
+    /// Draw the last submitted tree again, for example after its pictures were updated in place
+    pub fn request_redraw(&self) {
+        self.shared.update(|state| state.redraw_requested = true);
+    }
+
+    /// The surface is resized by the render thread before the next frame
+    pub fn resize_surface(&self, size: impl Into<ISize>) {
+        let size = size.into();
+        self.shared.update(|state| state.pending_size = Some(size));
+    }
+
+    pub fn set_scale_factor(&self, scale_factor: f32) {
+        self.shared
+            .update(|state| state.pending_scale_factor = Some(scale_factor));
+    }
+
+    /// Validate submitted trees before drawing them, applied from the next frame on.
+    /// See `PlatformCompositor::set_strict_validation`
+    pub fn set_strict_validation(&self, strict_validation: bool) {
+        self.shared.lock().pending_strict_validation = Some(strict_validation);
+    }
+
+    pub fn set_frame_begin_callback(&self, callback: Option<FrameCallback>) {
+        self.shared.lock().on_frame_begin = callback;
+    }
+
+    pub fn set_frame_presented_callback(&self, callback: Option<FrameCallback>) {
+        self.shared.lock().on_frame_presented = callback;
+    }
+
+    pub fn statistics(&self) -> RenderThreadStatistics {
+        self.shared.lock().statistics
+    }
+
+    /// A copy of the timings of the most recently drawn frames
+    pub fn frame_timings(&self) -> FrameTimingsHistory {
+        self.shared.lock().frame_timings.clone()
+    }
+
+    /// Stop the render thread after the frame that is currently being drawn
+    pub fn stop(&mut self) {
+        self.shared.update(|state| state.stopped = true);
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                error!("Render thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for RenderThread {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn render_loop(
+    mut compositor: PlatformCompositor,
+    shared: Arc<RenderThreadShared>,
+    options: RenderThreadOptions,
+) {
+    let interval = options.frame_interval.max(Duration::from_millis(1));
+    let started = Instant::now();
+    let mut next_frame = started;
+    let mut frame_id = 0;
+
+    loop {
+        let mut state = shared.lock();
+        while !state.has_work() {
+            state = shared
+                .wake_up
+                .wait(state)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+
+        // wait for the next frame slot, trees submitted in the meantime replace the pending one
+        let now = Instant::now();
+        if now < next_frame {
+            state = shared
+                .wake_up
+                .wait_timeout_while(state, next_frame - now, |state| !state.stopped)
+                .map(|(state, _)| state)
+                .unwrap_or_else(|poisoned| poisoned.into_inner().0);
+        }
+        if state.stopped {
+            break;
+        }
+
+        let layer = state.pending_layer.take();
+        let submissions = std::mem::take(&mut state.pending_submissions);
+        state.statistics.coalesced_frames += submissions.saturating_sub(1);
+        state.redraw_requested = false;
+        let size = state.pending_size.take();
+        let scale_factor = state.pending_scale_factor.take();
+        let strict_validation = state.pending_strict_validation.take();
+        let on_frame_begin = state.on_frame_begin.clone();
+        let on_frame_presented = state.on_frame_presented.clone();
+        drop(state);
+
+        frame_id += 1;
+        let frame_start = Instant::now();
+        let elapsed_intervals = (frame_start - started).as_nanos() / interval.as_nanos();
+        next_frame =
+            started + Duration::from_nanos(((elapsed_intervals + 1) * interval.as_nanos()) as u64);
+
+        if let Some(callback) = on_frame_begin {
+            callback(FrameTiming {
+                frame_id,
+                timestamp: frame_start - started,
+            });
+        }
+
+        if let Some(size) = size {
+            compositor.resize_surface(size);
+        }
+        if let Some(scale_factor) = scale_factor {
+            compositor.set_scale_factor(scale_factor);
+        }
+        if let Some(strict_validation) = strict_validation {
+            compositor.set_strict_validation(strict_validation);
+        }
+        if let Some(layer) = layer {
+            if let Err(submit_error) = compositor.submit_layer(layer) {
+                error!("Failed to submit layer: {}", submit_error);
+            }
+        }
+
+        let drawn = if options.cacheless {
+            compositor.draw_cacheless()
+        } else {
+            compositor.draw()
+        };
+        if let Err(draw_error) = drawn {
+            error!("Failed to draw frame {}: {}", frame_id, draw_error);
+        }
+        let has_pending_work = compositor.has_pending_work();
+
+        let mut state = shared.lock();
+        state.statistics.presented_frames += 1;
+        // images prepared in the background only show up in a later frame
+        state.redraw_requested |= has_pending_work;
+        // nothing is drawn before the first tree is submitted
+        let last_frame_id = state.frame_timings.last().map(|timings| timings.frame_id);
+        if let Some(timings) = compositor.frame_timings().last() {
+            if Some(timings.frame_id) != last_frame_id {
+                state.frame_timings.push(*timings);
+            }
+        }
+        drop(state);
+
+        if let Some(callback) = on_frame_presented {
+            callback(FrameTiming {
+                frame_id,
+                timestamp: Instant::now() - started,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use compositor::OffsetLayer;
+
+    fn spawn_unsupported(frame_interval: Duration) -> RenderThread {
+        RenderThread::spawn(
+            Platform::Unsupported,
+            PlatformContext::Unsupported,
+            RenderThreadOptions {
+                frame_interval,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    fn wait_for_frames(thread: &RenderThread, presented_frames: u64) -> RenderThreadStatistics {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let statistics = thread.statistics();
+            if statistics.presented_frames >= presented_frames || Instant::now() > deadline {
+                return statistics;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    pub fn draws_submitted_layers() {
+        let mut thread = spawn_unsupported(Duration::from_millis(1));
+        let presented = Arc::new(Mutex::new(vec![]));
+        let frames = presented.clone();
+        thread.set_frame_presented_callback(Some(Arc::new(move |timing: FrameTiming| {
+            frames.lock().unwrap().push(timing.frame_id)
+        })));
+
+        assert_eq!(thread.statistics().presented_frames, 0);
+        thread.submit_layer(OffsetLayer::new().clone_arc());
+        assert_eq!(wait_for_frames(&thread, 1).presented_frames, 1);
+
+        thread.request_redraw();
+        assert_eq!(wait_for_frames(&thread, 2).presented_frames, 2);
+
+        thread.stop();
+        assert_eq!(*presented.lock().unwrap(), vec![1, 2]);
+        // nothing is drawn without new work
+        assert_eq!(thread.statistics().presented_frames, 2);
+    }
+
+    #[test]
+    pub fn coalesces_layers_submitted_within_a_frame() {
+        let mut thread = spawn_unsupported(Duration::from_millis(500));
+        thread.submit_layer(OffsetLayer::new().clone_arc());
+        wait_for_frames(&thread, 1);
+
+        // the next frame starts half a second after the first one
+        for _ in 0..3 {
+            thread.submit_layer(OffsetLayer::new().clone_arc());
+        }
+        let statistics = wait_for_frames(&thread, 2);
+        thread.stop();
+
+        assert_eq!(statistics.presented_frames, 2);
+        assert_eq!(statistics.coalesced_frames, 2);
+    }
+}
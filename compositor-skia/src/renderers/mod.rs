@@ -2,8 +2,12 @@ mod picture_rasterizer;
 mod rasterizer;
 mod rasterizer_stats;
 mod shadow_rasterizer;
+mod thread_pool_rasterizer;
 
 pub use picture_rasterizer::{PictureRasterizer, PictureToRasterize, RasterizedPicture};
 pub use rasterizer::{Rasterizer, SyncRasterizer};
 pub use rasterizer_stats::{RasterizationStats, RasterizationStepStats, RasterizerSurfaceType};
-pub use shadow_rasterizer::{RasterizedShadow, ShadowRasterizer, ShadowToRasterize};
+pub use shadow_rasterizer::{
+    RasterizedShadow, ShadowDownsampling, ShadowRasterizer, ShadowScale, ShadowToRasterize,
+};
+pub use thread_pool_rasterizer::ThreadPoolRasterizer;
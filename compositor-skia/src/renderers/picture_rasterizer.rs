@@ -1,10 +1,11 @@
 use crate::RasterizationStats;
 use skia_safe::{
-    Canvas, Color, ColorSpace, IRect, Image, ImageInfo, Matrix, Picture, Rect, RoundOut, Vector,
+    Canvas, Color, ColorSpace, IRect, Image, ImageInfo, Matrix, Picture, Rect, RoundOut, Surface,
+    Vector,
 };
 use std::fmt::{Debug, Error, Formatter};
 
-use crate::renderers::rasterizer::create_surface;
+use crate::renderers::rasterizer::{create_software_surface, create_surface};
 
 /// I contain all the necessary data to rasterize a picture
 #[derive(Clone)]
@@ -94,6 +95,24 @@ impl PictureRasterizer {
         &self,
         picture_to_rasterize: PictureToRasterize,
         canvas: &Canvas,
+    ) -> RasterizedPicture {
+        self.rasterize_on(picture_to_rasterize, |stats, image_info| {
+            create_surface(canvas, stats, image_info)
+        })
+    }
+
+    /// Rasterize a given picture on a CPU surface, which can be done on any thread
+    pub fn rasterize_in_software(
+        &self,
+        picture_to_rasterize: PictureToRasterize,
+    ) -> RasterizedPicture {
+        self.rasterize_on(picture_to_rasterize, create_software_surface)
+    }
+
+    fn rasterize_on(
+        &self,
+        picture_to_rasterize: PictureToRasterize,
+        create_surface: impl FnOnce(&mut RasterizationStats, &ImageInfo) -> Option<Surface>,
     ) -> RasterizedPicture {
         let device_bounds = picture_to_rasterize.device_bounds();
         let picture = &picture_to_rasterize.picture;
@@ -104,7 +123,7 @@ impl PictureRasterizer {
 
         let image_info = ImageInfo::new_n32_premul(device_bounds.size(), ColorSpace::new_srgb());
 
-        let surface = create_surface(canvas, &mut stats, &image_info);
+        let surface = create_surface(&mut stats, &image_info);
 
         let image = match surface {
             None => None,
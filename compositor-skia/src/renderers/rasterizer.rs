@@ -2,23 +2,56 @@ use crate::{
     PictureRasterizer, PictureToRasterize, RasterizationStats, RasterizedPicture, RasterizedShadow,
     RasterizerSurfaceType, ShadowRasterizer, ShadowToRasterize,
 };
+use compositor::Shadow;
 use log::error;
 use skia_safe::{surfaces, Canvas, ImageInfo, Surface};
+use std::fmt::Debug;
 
-pub trait Rasterizer {
+/// Turns pictures and shadows into images for the `Cache`.
+/// Rasterizers may work in the background, in which case the jobs are only submitted
+/// and their images are returned by a later `collect_rasterized` call
+pub trait Rasterizer: Debug {
+    /// Rasterize or schedule the given pictures, returning the images that are already finished
     fn rasterize_picture(
         &mut self,
-        canvas: &mut Canvas,
+        canvas: &Canvas,
         to_rasterize: Vec<PictureToRasterize>,
     ) -> Vec<RasterizedPicture>;
 
+    /// Rasterize or schedule the given shadows, returning the images that are already finished
     fn rasterize_shadow(
         &mut self,
-        canvas: &mut Canvas,
+        canvas: &Canvas,
         to_rasterize: Vec<ShadowToRasterize>,
     ) -> Vec<RasterizedShadow>;
+
+    /// Return the images finished in the background since the last call
+    fn collect_rasterized(&mut self) -> (Vec<RasterizedPicture>, Vec<RasterizedShadow>) {
+        (vec![], vec![])
+    }
+
+    /// Return true if jobs are rasterized in the background instead of right away
+    fn works_in_background(&self) -> bool {
+        false
+    }
+
+    /// Return true if some jobs are still being rasterized in the background
+    fn has_pending_jobs(&self) -> bool {
+        false
+    }
+
+    /// Forget the pending jobs of a given picture, their images are never returned
+    fn invalidate_picture(&mut self, _picture_id: u32) {}
+
+    /// Forget the pending jobs of a given normalized shadow at any scale
+    fn invalidate_shadow(&mut self, _shadow: &Shadow) {}
+
+    /// Forget every pending job
+    fn invalidate_all(&mut self) {}
 }
 
+/// Rasterizes every job right away on the surface of the canvas
+#[derive(Debug)]
 pub struct SyncRasterizer {}
 
 impl SyncRasterizer {
@@ -30,7 +63,7 @@ impl SyncRasterizer {
 impl Rasterizer for SyncRasterizer {
     fn rasterize_picture(
         &mut self,
-        canvas: &mut Canvas,
+        canvas: &Canvas,
         to_rasterize: Vec<PictureToRasterize>,
     ) -> Vec<RasterizedPicture> {
         let mut rasterized_pictures: Vec<RasterizedPicture> = vec![];
@@ -45,7 +78,7 @@ impl Rasterizer for SyncRasterizer {
 
     fn rasterize_shadow(
         &mut self,
-        canvas: &mut Canvas,
+        canvas: &Canvas,
         to_rasterize: Vec<ShadowToRasterize>,
     ) -> Vec<RasterizedShadow> {
         let mut rasterized_shadows: Vec<RasterizedShadow> = vec![];
@@ -104,24 +137,30 @@ pub(crate) fn create_surface(
     };
 
     let surface = match surface {
-        None => {
-            let cpu_surface_time = std::time::Instant::now();
-            match surfaces::raster(&image_info, None, None) {
-                None => {
-                    error!(
-                        "Could not create CPU surface of size {:?}",
-                        image_info.dimensions()
-                    );
-                    None
-                }
-                Some(surface) => {
-                    stats.log(cpu_surface_time, String::from("Create CPU Surface"));
-                    stats.set_surface_type(RasterizerSurfaceType::Software);
-                    Some(surface)
-                }
-            }
-        }
+        None => create_software_surface(stats, image_info),
         Some(surface) => Some(surface),
     };
     surface
 }
+
+/// Create a CPU surface, which unlike a GPU one can be used on any thread
+pub(crate) fn create_software_surface(
+    stats: &mut RasterizationStats,
+    image_info: &ImageInfo,
+) -> Option<Surface> {
+    let cpu_surface_time = std::time::Instant::now();
+    match surfaces::raster(&image_info, None, None) {
+        None => {
+            error!(
+                "Could not create CPU surface of size {:?}",
+                image_info.dimensions()
+            );
+            None
+        }
+        Some(surface) => {
+            stats.log(cpu_surface_time, String::from("Create CPU Surface"));
+            stats.set_surface_type(RasterizerSurfaceType::Software);
+            Some(surface)
+        }
+    }
+}
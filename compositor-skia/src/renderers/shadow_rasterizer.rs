@@ -1,23 +1,151 @@
-use crate::{as_skia_point, into_skia_rect, RasterizationStats};
-use compositor::Shadow;
+use crate::{RasterizationStats, ScaleQuantization, as_skia_point, into_skia_rect};
+use compositor::{Scalar, Shadow};
 use log::{error, trace};
 use skia_safe::{
-    Canvas, Color, ColorSpace, IRect, Image, ImageInfo, Matrix, Point, Rect, RoundOut, Vector,
+    Canvas, Color, ColorSpace, IRect, Image, ImageInfo, Matrix, Point, Rect, RoundOut, Surface,
+    Vector,
 };
 
-use crate::renderers::rasterizer::create_surface;
+use crate::renderers::rasterizer::{create_software_surface, create_surface};
 use crate::utils::draw_shadow;
 
+/// The scale a shadow is rasterized at. Shadows are cached per scale,
+/// so the scale is usually quantized to let close scales share an image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShadowScale {
+    x: Scalar,
+    y: Scalar,
+}
+
+impl Default for ShadowScale {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl ShadowScale {
+    /// Shadows whose image at a scale would be wider or taller than this
+    /// number of pixels are drawn directly instead of being rasterized
+    pub const MAX_DEVICE_SIZE: i32 = 4096;
+
+    pub fn new(x: f32, y: f32) -> Self {
+        Self {
+            x: x.into(),
+            y: y.into(),
+        }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(1.0, 1.0)
+    }
+
+    /// Return the quantized scale of a given canvas matrix, or `None` if the matrix rotates,
+    /// skews or collapses the shadow, in which case it should be drawn directly
+    pub fn from_matrix(matrix: &Matrix, quantization: ScaleQuantization) -> Option<Self> {
+        if !matrix.is_scale_translate() {
+            return None;
+        }
+        let (x, y) = (matrix.scale_x().abs(), matrix.scale_y().abs());
+        if !(x.is_finite() && y.is_finite() && x > 0.0 && y > 0.0) {
+            return None;
+        }
+        Some(Self::new(
+            quantization.quantize(x),
+            quantization.quantize(y),
+        ))
+    }
+
+    pub fn x(&self) -> f32 {
+        self.x.0
+    }
+
+    pub fn y(&self) -> f32 {
+        self.y.0
+    }
+
+    pub fn matrix(&self) -> Matrix {
+        Matrix::scale((self.x(), self.y()))
+    }
+
+    /// Return the scale reduced by a given factor
+    pub fn downsample(&self, factor: f32) -> Self {
+        Self::new(self.x() / factor, self.y() / factor)
+    }
+
+    /// Return true if the image of a given shadow at this scale fits within
+    /// [`Self::MAX_DEVICE_SIZE`]
+    pub fn can_rasterize(&self, shadow: &Shadow) -> bool {
+        let bounds = ShadowToRasterize::compute_device_bounds(
+            &into_skia_rect(&shadow.cull_rect()),
+            &self.matrix(),
+        );
+        bounds.width() <= Self::MAX_DEVICE_SIZE && bounds.height() <= Self::MAX_DEVICE_SIZE
+    }
+}
+
+/// Rasterizes shadows with a large blur radius at a reduced resolution.
+/// The blur removes the detail that is lost by downsampling, so the shadow is upsampled
+/// when drawn with a bounded visual error, while its image takes a fraction of the memory
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowDownsampling {
+    /// Shadows with a smaller blur radius in device pixels are rasterized at full resolution
+    pub min_radius: f32,
+    /// The largest size of a downsampled pixel relative to the blur radius,
+    /// which bounds the visual error of the upsampled shadow
+    pub max_pixel_to_radius: f32,
+    /// Shadows are never downsampled by more than this factor
+    pub max_factor: f32,
+}
+
+impl Default for ShadowDownsampling {
+    fn default() -> Self {
+        Self {
+            min_radius: 8.0,
+            max_pixel_to_radius: 0.25,
+            max_factor: 8.0,
+        }
+    }
+}
+
+impl ShadowDownsampling {
+    /// The downscale factor for a given shadow drawn at a given scale.
+    /// Factors are powers of two so that shadows with close radii share the scale of their images
+    pub fn factor(&self, shadow: &Shadow, scale: &ShadowScale) -> f32 {
+        let radius = shadow.radius();
+        let device_radius = (radius.width().0 * scale.x()).min(radius.height().0 * scale.y());
+        if !device_radius.is_finite() || device_radius < self.min_radius {
+            return 1.0;
+        }
+        let factor = (device_radius * self.max_pixel_to_radius)
+            .min(self.max_factor)
+            .max(1.0);
+        factor.log2().floor().exp2()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ShadowToRasterize {
     pub shadow: Shadow,
     pub bounds: Rect,
+    pub scale: ShadowScale,
 }
 
 impl ShadowToRasterize {
     pub fn new(shadow: Shadow) -> Self {
+        Self::new_scaled(shadow, ShadowScale::identity())
+    }
+
+    pub fn new_scaled(shadow: Shadow, scale: ShadowScale) -> Self {
         let bounds = into_skia_rect(&shadow.cull_rect());
-        Self { shadow, bounds }
+        Self {
+            shadow,
+            bounds,
+            scale,
+        }
+    }
+
+    pub fn device_bounds(&self) -> IRect {
+        Self::compute_device_bounds(&self.bounds, &self.scale.matrix())
     }
 
     pub fn into_rasterized(
@@ -25,7 +153,7 @@ impl ShadowToRasterize {
         image: Option<Image>,
         stats: RasterizationStats,
     ) -> RasterizedShadow {
-        RasterizedShadow::new(self.shadow, image, stats)
+        RasterizedShadow::new(self.shadow, self.scale, image, stats)
     }
 
     pub fn compute_device_bounds(bounds: &Rect, matrix: &Matrix) -> IRect {
@@ -37,14 +165,21 @@ impl ShadowToRasterize {
 /// was successful
 pub struct RasterizedShadow {
     pub shadow: Shadow,
+    pub scale: ShadowScale,
     pub image: Option<Image>,
     pub stats: RasterizationStats,
 }
 
 impl RasterizedShadow {
-    pub fn new(shadow: Shadow, image: Option<Image>, stats: RasterizationStats) -> Self {
+    pub fn new(
+        shadow: Shadow,
+        scale: ShadowScale,
+        image: Option<Image>,
+        stats: RasterizationStats,
+    ) -> Self {
         Self {
             shadow,
+            scale,
             image,
             stats,
         }
@@ -62,6 +197,24 @@ impl ShadowRasterizer {
         &self,
         shadow_to_rasterize: ShadowToRasterize,
         canvas: &Canvas,
+    ) -> RasterizedShadow {
+        self.rasterize_on(shadow_to_rasterize, |stats, image_info| {
+            create_surface(canvas, stats, image_info)
+        })
+    }
+
+    /// Rasterize a given shadow on a CPU surface, which can be done on any thread
+    pub fn rasterize_in_software(
+        &self,
+        shadow_to_rasterize: ShadowToRasterize,
+    ) -> RasterizedShadow {
+        self.rasterize_on(shadow_to_rasterize, create_software_surface)
+    }
+
+    fn rasterize_on(
+        &self,
+        shadow_to_rasterize: ShadowToRasterize,
+        create_surface: impl FnOnce(&mut RasterizationStats, &ImageInfo) -> Option<Surface>,
     ) -> RasterizedShadow {
         trace!(
             "Rasterize shadow with bounds = {:?}, cull rectangle = {:?}",
@@ -69,7 +222,7 @@ impl ShadowRasterizer {
             shadow_to_rasterize.shadow.cull_rect()
         );
 
-        let device_bounds = shadow_to_rasterize.bounds.round();
+        let device_bounds = shadow_to_rasterize.device_bounds();
         trace!(
             "About to rasterize shadow with device bounds {:?}",
             &device_bounds
@@ -86,7 +239,7 @@ impl ShadowRasterizer {
         let image_info = ImageInfo::new_n32_premul(device_bounds.size(), ColorSpace::new_srgb());
         trace!("About to create surface with {:?}", &image_info);
 
-        let surface = create_surface(canvas, &mut stats, &image_info);
+        let surface = create_surface(&mut stats, &image_info);
 
         let image = match surface {
             None => {
@@ -103,6 +256,7 @@ impl ShadowRasterizer {
                     -device_bounds.left as f32,
                     -device_bounds.top as f32,
                 ));
+                canvas.concat(&shadow_to_rasterize.scale.matrix());
                 canvas.translate(as_skia_point(shadow.offset()).clone());
 
                 draw_shadow(canvas, shadow, Point::new(0.0, 0.0), None);
@@ -126,3 +280,73 @@ impl ShadowRasterizer {
         shadow_to_rasterize.into_rasterized(image, stats)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn shadow_scale_from_matrix() {
+        let quantization = ScaleQuantization::Step(0.25);
+
+        assert_eq!(
+            ShadowScale::from_matrix(&Matrix::translate((10.0, 20.0)), quantization),
+            Some(ShadowScale::identity())
+        );
+        assert_eq!(
+            ShadowScale::from_matrix(&Matrix::scale((1.1, -2.0)), quantization),
+            Some(ShadowScale::new(1.25, 2.0))
+        );
+        assert_eq!(
+            ShadowScale::from_matrix(&Matrix::rotate_deg(10.0), quantization),
+            None
+        );
+        assert_eq!(
+            ShadowScale::from_matrix(&Matrix::scale((0.0, 1.0)), quantization),
+            None
+        );
+    }
+
+    #[test]
+    pub fn downsampling_factor() {
+        let downsampling = ShadowDownsampling::default();
+        let shadow = |radius: f32| {
+            Shadow::new(
+                compositor::Color::from_argb(0),
+                compositor::Radius::new(radius, radius),
+                compositor::Point::zero(),
+                compositor::Geometry::None,
+            )
+        };
+        let identity = ShadowScale::identity();
+
+        assert_eq!(downsampling.factor(&shadow(4.0), &identity), 1.0);
+        assert_eq!(downsampling.factor(&shadow(8.0), &identity), 2.0);
+        assert_eq!(downsampling.factor(&shadow(20.0), &identity), 4.0);
+        assert_eq!(
+            downsampling.factor(&shadow(20.0), &ShadowScale::new(0.25, 0.25)),
+            1.0
+        );
+        assert_eq!(downsampling.factor(&shadow(500.0), &identity), 8.0);
+    }
+
+    #[test]
+    pub fn large_shadows_are_not_rasterized() {
+        let shadow = |width: f32, height: f32| {
+            Shadow::new(
+                compositor::Color::from_argb(0),
+                compositor::Radius::new(0.0, 0.0),
+                compositor::Point::zero(),
+                compositor::Geometry::Rectangle(compositor::Rectangle::extent(width, height)),
+            )
+        };
+        let identity = ShadowScale::identity();
+
+        assert!(identity.can_rasterize(&shadow(100.0, 100.0)));
+        assert!(identity.can_rasterize(&shadow(4096.0, 10.0)));
+        assert!(!identity.can_rasterize(&shadow(5000.0, 10.0)));
+        assert!(!identity.can_rasterize(&shadow(10.0, 5000.0)));
+        assert!(!ShadowScale::new(2.0, 2.0).can_rasterize(&shadow(3000.0, 10.0)));
+        assert!(ShadowScale::new(0.5, 0.5).can_rasterize(&shadow(5000.0, 10.0)));
+    }
+}
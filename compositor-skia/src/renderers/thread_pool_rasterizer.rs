@@ -0,0 +1,324 @@
+use crate::{
+    PictureRasterizer, PictureToRasterize, RasterizationStats, RasterizedPicture, RasterizedShadow,
+    Rasterizer, ShadowRasterizer, ShadowScale, ShadowToRasterize,
+};
+use compositor::Shadow;
+use log::error;
+use skia_safe::Canvas;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::mpsc::{Receiver, Sender, TryRecvError, channel};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+enum RasterizationJob {
+    Picture(PictureToRasterize),
+    Shadow(ShadowToRasterize),
+}
+
+enum RasterizationResult {
+    Picture(RasterizedPicture),
+    Shadow(RasterizedShadow),
+}
+
+/// Jobs and their results are tagged with the invalidation epoch they were submitted in
+type Epoch = u64;
+
+/// Rasterizes pictures and shadows in parallel on CPU surfaces.
+/// Jobs are only submitted while composing, the compositor draws the pictures directly
+/// until their images are collected into the `Cache` in one of the next frames.
+/// Dropping the rasterizer does not wait for the workers, they finish their current job and exit.
+/// Results of jobs that were invalidated while being rasterized are dropped when collected.
+pub struct ThreadPoolRasterizer {
+    jobs: Sender<(Epoch, RasterizationJob)>,
+    results: Receiver<(Epoch, RasterizationResult)>,
+    workers: Vec<JoinHandle<()>>,
+    epoch: Epoch,
+    /// the epoch each pending job was submitted in
+    pending_pictures: HashMap<u32, Epoch>,
+    pending_shadows: HashMap<(Shadow, ShadowScale), Epoch>,
+}
+
+impl Debug for ThreadPoolRasterizer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThreadPoolRasterizer")
+            .field("workers", &self.workers.len())
+            .field("epoch", &self.epoch)
+            .field("pending_pictures", &self.pending_pictures.len())
+            .field("pending_shadows", &self.pending_shadows.len())
+            .finish()
+    }
+}
+
+impl ThreadPoolRasterizer {
+    /// Spawn a given amount of worker threads, at least one
+    pub fn new(thread_count: usize) -> std::io::Result<Self> {
+        let (job_sender, job_receiver) = channel::<(Epoch, RasterizationJob)>();
+        let (result_sender, result_receiver) = channel::<(Epoch, RasterizationResult)>();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let workers = (0..thread_count.max(1))
+            .map(|index| {
+                let jobs = job_receiver.clone();
+                let results = result_sender.clone();
+                std::thread::Builder::new()
+                    .name(format!("compositor-rasterizer-{}", index))
+                    .spawn(move || rasterize_jobs(jobs, results))
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            jobs: job_sender,
+            results: result_receiver,
+            workers,
+            epoch: 0,
+            pending_pictures: HashMap::new(),
+            pending_shadows: HashMap::new(),
+        })
+    }
+
+    /// One worker per available core, leaving one for the render thread
+    pub fn with_available_parallelism() -> std::io::Result<Self> {
+        let cores = std::thread::available_parallelism()
+            .map(|cores| cores.get())
+            .unwrap_or(2);
+        Self::new(cores.saturating_sub(1))
+    }
+
+    fn submit(&mut self, job: RasterizationJob) -> bool {
+        match self.jobs.send((self.epoch, job)) {
+            Ok(_) => true,
+            Err(_) => {
+                error!("Rasterizer threads are gone");
+                false
+            }
+        }
+    }
+}
+
+/// Rasterize a given job, turning a panic into a failed rasterization
+/// so that the job does not stay pending forever
+fn rasterize_job(
+    job: RasterizationJob,
+    picture_rasterizer: &PictureRasterizer,
+    shadow_rasterizer: &ShadowRasterizer,
+) -> RasterizationResult {
+    match job {
+        RasterizationJob::Picture(picture) => {
+            let failed_picture = picture.clone();
+            catch_unwind(AssertUnwindSafe(|| {
+                picture_rasterizer.rasterize_in_software(picture)
+            }))
+            .map(RasterizationResult::Picture)
+            .unwrap_or_else(|_| {
+                error!("Rasterizer thread panicked while rasterizing a picture");
+                let stats = RasterizationStats::new(failed_picture.picture.unique_id());
+                RasterizationResult::Picture(failed_picture.into_rasterized(None, stats))
+            })
+        }
+        RasterizationJob::Shadow(shadow) => {
+            let failed_shadow = shadow.clone();
+            catch_unwind(AssertUnwindSafe(|| {
+                shadow_rasterizer.rasterize_in_software(shadow)
+            }))
+            .map(RasterizationResult::Shadow)
+            .unwrap_or_else(|_| {
+                error!("Rasterizer thread panicked while rasterizing a shadow");
+                let stats = RasterizationStats::new(0);
+                RasterizationResult::Shadow(failed_shadow.into_rasterized(None, stats))
+            })
+        }
+    }
+}
+
+fn rasterize_jobs(
+    jobs: Arc<Mutex<Receiver<(Epoch, RasterizationJob)>>>,
+    results: Sender<(Epoch, RasterizationResult)>,
+) {
+    let picture_rasterizer = PictureRasterizer::new();
+    let shadow_rasterizer = ShadowRasterizer::new();
+
+    loop {
+        // the lock is only held while waiting for the next job, not while rasterizing it
+        let job = match jobs.lock() {
+            Ok(jobs) => jobs.recv(),
+            Err(_) => return,
+        };
+        let result = match job {
+            Ok((epoch, job)) => (
+                epoch,
+                rasterize_job(job, &picture_rasterizer, &shadow_rasterizer),
+            ),
+            Err(_) => return,
+        };
+        if results.send(result).is_err() {
+            return;
+        }
+    }
+}
+
+impl Rasterizer for ThreadPoolRasterizer {
+    fn rasterize_picture(
+        &mut self,
+        _canvas: &Canvas,
+        to_rasterize: Vec<PictureToRasterize>,
+    ) -> Vec<RasterizedPicture> {
+        for picture in to_rasterize {
+            let picture_id = picture.picture.unique_id();
+            if !self.pending_pictures.contains_key(&picture_id)
+                && self.submit(RasterizationJob::Picture(picture))
+            {
+                self.pending_pictures.insert(picture_id, self.epoch);
+            }
+        }
+        vec![]
+    }
+
+    fn rasterize_shadow(
+        &mut self,
+        _canvas: &Canvas,
+        to_rasterize: Vec<ShadowToRasterize>,
+    ) -> Vec<RasterizedShadow> {
+        for shadow in to_rasterize {
+            let key = (shadow.shadow.clone(), shadow.scale);
+            if !self.pending_shadows.contains_key(&key)
+                && self.submit(RasterizationJob::Shadow(shadow))
+            {
+                self.pending_shadows.insert(key, self.epoch);
+            }
+        }
+        vec![]
+    }
+
+    fn collect_rasterized(&mut self) -> (Vec<RasterizedPicture>, Vec<RasterizedShadow>) {
+        let mut pictures = vec![];
+        let mut shadows = vec![];
+        loop {
+            // results are only kept if their job is still pending in the epoch it was submitted in
+            match self.results.try_recv() {
+                Ok((epoch, RasterizationResult::Picture(picture))) => {
+                    let picture_id = picture.picture_id();
+                    if self.pending_pictures.get(&picture_id) == Some(&epoch) {
+                        self.pending_pictures.remove(&picture_id);
+                        pictures.push(picture);
+                    }
+                }
+                Ok((epoch, RasterizationResult::Shadow(shadow))) => {
+                    let key = (shadow.shadow.clone(), shadow.scale);
+                    if self.pending_shadows.get(&key) == Some(&epoch) {
+                        self.pending_shadows.remove(&key);
+                        shadows.push(shadow);
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    // every worker is gone, nothing pending will ever be rasterized
+                    self.pending_pictures.clear();
+                    self.pending_shadows.clear();
+                    break;
+                }
+            }
+        }
+        (pictures, shadows)
+    }
+
+    fn works_in_background(&self) -> bool {
+        true
+    }
+
+    fn has_pending_jobs(&self) -> bool {
+        !self.pending_pictures.is_empty() || !self.pending_shadows.is_empty()
+    }
+
+    fn invalidate_picture(&mut self, picture_id: u32) {
+        if self.pending_pictures.remove(&picture_id).is_some() {
+            self.epoch += 1;
+        }
+    }
+
+    fn invalidate_shadow(&mut self, shadow: &Shadow) {
+        let pending = self.pending_shadows.len();
+        self.pending_shadows
+            .retain(|(pending_shadow, _), _| pending_shadow != shadow);
+        if self.pending_shadows.len() != pending {
+            self.epoch += 1;
+        }
+    }
+
+    fn invalidate_all(&mut self) {
+        self.pending_pictures.clear();
+        self.pending_shadows.clear();
+        self.epoch += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use skia_safe::{Color, Matrix, PictureRecorder, Rect, surfaces};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    pub fn rasterizes_pictures_in_the_background() {
+        let mut recorder = PictureRecorder::new();
+        recorder
+            .begin_recording(Rect::new(0.0, 0.0, 20.0, 10.0), false)
+            .clear(Color::RED);
+        let picture = recorder.finish_recording_as_picture(None).unwrap();
+        let picture_id = picture.unique_id();
+
+        let mut surface = surfaces::raster_n32_premul((10, 10)).unwrap();
+        let mut rasterizer = ThreadPoolRasterizer::new(2).unwrap();
+        let to_rasterize = PictureToRasterize::new(picture, Matrix::scale((2.0, 2.0)));
+        let rasterized = rasterizer.rasterize_picture(surface.canvas(), vec![to_rasterize.clone()]);
+        assert!(rasterized.is_empty());
+        assert!(rasterizer.has_pending_jobs());
+
+        // a picture that is already pending is not submitted again
+        rasterizer.rasterize_picture(surface.canvas(), vec![to_rasterize]);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut pictures = vec![];
+        while rasterizer.has_pending_jobs() && Instant::now() < deadline {
+            pictures.extend(rasterizer.collect_rasterized().0);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        assert!(!rasterizer.has_pending_jobs());
+        assert_eq!(pictures.len(), 1);
+        assert_eq!(pictures[0].picture_id(), picture_id);
+        let image = pictures[0].image.as_ref().unwrap();
+        assert_eq!((image.width(), image.height()), (40, 20));
+    }
+
+    #[test]
+    pub fn drops_results_of_invalidated_jobs() {
+        let mut recorder = PictureRecorder::new();
+        recorder
+            .begin_recording(Rect::new(0.0, 0.0, 10.0, 10.0), false)
+            .clear(Color::RED);
+        let picture = recorder.finish_recording_as_picture(None).unwrap();
+        let picture_id = picture.unique_id();
+
+        let mut surface = surfaces::raster_n32_premul((10, 10)).unwrap();
+        let mut rasterizer = ThreadPoolRasterizer::new(1).unwrap();
+        let to_rasterize = PictureToRasterize::new(picture, Matrix::default());
+        rasterizer.rasterize_picture(surface.canvas(), vec![to_rasterize.clone()]);
+        rasterizer.invalidate_picture(picture_id);
+        assert!(!rasterizer.has_pending_jobs());
+
+        // the picture is rasterized again after the invalidation, only that result is kept
+        rasterizer.rasterize_picture(surface.canvas(), vec![to_rasterize]);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut pictures = vec![];
+        while rasterizer.has_pending_jobs() && Instant::now() < deadline {
+            pictures.extend(rasterizer.collect_rasterized().0);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        assert_eq!(pictures.len(), 1);
+        assert_eq!(pictures[0].picture_id(), picture_id);
+    }
+}
@@ -0,0 +1,158 @@
+use compositor::{ImageSource, RuntimeShader, ShaderId, ShaderLayer};
+use log::error;
+use skia_safe::runtime_effect::ChildPtr;
+use skia_safe::{Data, Image, RuntimeEffect, SamplingOptions, Shader};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShaderError {
+    /// The SkSL source failed to compile, with the message reported by the compiler
+    Compilation(String),
+    /// The layer binds a uniform that is not declared in the source
+    UnknownUniform(String),
+    /// The value bound to a uniform does not match the declared type
+    UniformSizeMismatch {
+        name: String,
+        expected: usize,
+        actual: usize,
+    },
+    /// The layer binds a child image that is not declared in the source
+    UnknownChild(String),
+    /// A child declared in the source has no image bound to it
+    MissingChild(String),
+    /// Skia refused to create a shader from the compiled effect
+    Instantiation,
+}
+
+impl Display for ShaderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShaderError::Compilation(message) => write!(f, "Failed to compile shader: {}", message),
+            ShaderError::UnknownUniform(name) => write!(f, "Unknown uniform {}", name),
+            ShaderError::UniformSizeMismatch {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Uniform {} expects {} bytes, got {}",
+                name, expected, actual
+            ),
+            ShaderError::UnknownChild(name) => write!(f, "Unknown child {}", name),
+            ShaderError::MissingChild(name) => write!(f, "Missing child {}", name),
+            ShaderError::Instantiation => write!(f, "Failed to instantiate shader"),
+        }
+    }
+}
+
+impl Error for ShaderError {}
+
+/// Compile the SkSL source of a given runtime shader
+pub fn compile_runtime_shader(shader: &RuntimeShader) -> Result<RuntimeEffect, ShaderError> {
+    RuntimeEffect::make_for_shader(shader.source(), None).map_err(ShaderError::Compilation)
+}
+
+/// Create a Skia shader from a compiled effect binding the uniforms and children of a given layer.
+/// Child images are provided by `images`, returns `Ok(None)` if some of them are not ready yet.
+pub fn make_runtime_shader(
+    effect: &RuntimeEffect,
+    layer: &ShaderLayer,
+    mut images: impl FnMut(&ImageSource) -> Option<Image>,
+) -> Result<Option<Shader>, ShaderError> {
+    let mut uniforms = vec![0u8; effect.uniform_size()];
+    for (name, value) in layer.uniforms() {
+        let uniform = effect
+            .find_uniform(name)
+            .ok_or_else(|| ShaderError::UnknownUniform(name.clone()))?;
+
+        if uniform.size_in_bytes() != value.size_in_bytes() {
+            return Err(ShaderError::UniformSizeMismatch {
+                name: name.clone(),
+                expected: uniform.size_in_bytes(),
+                actual: value.size_in_bytes(),
+            });
+        }
+
+        let bytes = value
+            .as_slice()
+            .iter()
+            .flat_map(|float| float.to_ne_bytes());
+        for (target, byte) in uniforms[uniform.offset()..].iter_mut().zip(bytes) {
+            *target = byte;
+        }
+    }
+
+    for (name, _) in layer.children() {
+        if !effect.children().iter().any(|child| child.name() == name) {
+            return Err(ShaderError::UnknownChild(name.clone()));
+        }
+    }
+
+    let mut children = Vec::with_capacity(effect.children().len());
+    for child in effect.children() {
+        let source = layer
+            .child(child.name())
+            .ok_or_else(|| ShaderError::MissingChild(child.name().to_string()))?;
+
+        let Some(image) = images(source) else {
+            return Ok(None);
+        };
+
+        let child_shader = image
+            .to_shader(None, SamplingOptions::default(), None)
+            .ok_or(ShaderError::Instantiation)?;
+        children.push(ChildPtr::from(child_shader));
+    }
+
+    effect
+        .make_shader(Data::new_copy(&uniforms), &children, None)
+        .map(Some)
+        .ok_or(ShaderError::Instantiation)
+}
+
+/// Keeps compiled runtime effects, or their compilation errors, by the id of their source
+/// so that every distinct source is compiled only once
+pub struct ShaderCache {
+    effects: HashMap<ShaderId, Result<RuntimeEffect, ShaderError>>,
+}
+
+impl Debug for ShaderCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ShaderCache")
+            .field("effects", &self.effects.keys())
+            .finish()
+    }
+}
+
+impl ShaderCache {
+    pub fn new() -> Self {
+        Self {
+            effects: HashMap::new(),
+        }
+    }
+
+    /// Return a compiled effect for a given shader, compiling it on the first request
+    pub fn get_runtime_effect(
+        &mut self,
+        shader: &RuntimeShader,
+    ) -> Result<RuntimeEffect, ShaderError> {
+        self.effects
+            .entry(shader.id())
+            .or_insert_with(|| {
+                compile_runtime_shader(shader).inspect_err(|compile_error| {
+                    error!("Runtime shader {}: {}", shader.id(), compile_error)
+                })
+            })
+            .clone()
+    }
+
+    pub fn count_compiled_shaders(&self) -> usize {
+        self.effects.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.effects.clear();
+    }
+}
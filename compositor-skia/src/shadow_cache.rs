@@ -1,54 +1,33 @@
+use crate::ShadowScale;
+use crate::raster_cache::RasterCache;
 use compositor::Shadow;
 use skia_safe::Image;
-use std::cmp::{max, min};
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fmt::{Debug, Error, Formatter};
 
-/// The amount of frames after which a cached shadow image is purged if not used
-pub const CACHED_SHADOW_UNUSED_FRAMES_LIMIT: usize = 5;
-
-pub struct CachedShadowImage {
-    image: Image,
-    frames_to_purge: usize,
-}
-
-impl CachedShadowImage {
-    pub fn new(image: Image) -> Self {
-        Self {
-            image,
-            frames_to_purge: CACHED_SHADOW_UNUSED_FRAMES_LIMIT,
-        }
-    }
-
-    pub fn mark_not_used(&mut self) {
-        self.frames_to_purge = max(self.frames_to_purge - 1, 0);
-    }
-
-    pub fn mark_used(&mut self) {
-        self.frames_to_purge = min(self.frames_to_purge + 1, CACHED_SHADOW_UNUSED_FRAMES_LIMIT);
-    }
-
-    pub fn should_purge(&self) -> bool {
-        self.frames_to_purge <= 0
-    }
-}
-
-impl Debug for CachedShadowImage {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-        f.debug_struct("CachedShadowImage")
-            .field("frames_to_purge:", &self.frames_to_purge)
-            .finish()
-    }
+/// A rasterized shadow as listed by `Cache::shadow_entries`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CachedShadowEntry<'a> {
+    pub shadow: &'a Shadow,
+    /// The scale the shadow was rasterized at
+    pub scale: ShadowScale,
+    pub bytes: usize,
+    pub last_used_frame: u64,
+    pub pinned: bool,
 }
 
+/// Rasterized shadows by shadow and the scale they were rasterized at
 pub struct ShadowCache {
-    pub images: HashMap<Shadow, CachedShadowImage>,
+    pub(crate) images: RasterCache<(Shadow, ShadowScale), Image>,
+    /// Shadows pinned at every scale
+    pinned: HashSet<Shadow>,
 }
 
 impl Debug for ShadowCache {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-        f.debug_map()
-            .entries(self.images.iter().map(|(k, v)| (k, v)))
+        f.debug_struct("ShadowCache")
+            .field("images:", &self.images)
+            .field("pinned:", &self.pinned)
             .finish()
     }
 }
@@ -56,44 +35,92 @@ impl Debug for ShadowCache {
 impl ShadowCache {
     pub fn new() -> Self {
         Self {
-            images: HashMap::new(),
+            images: RasterCache::new(),
+            pinned: HashSet::new(),
         }
     }
 
-    pub fn get_shadow_image(&mut self, shadow: &Shadow) -> Option<&Image> {
-        self.images.get_mut(shadow).and_then(|cached_image| {
-            cached_image.mark_used();
-            Some(&cached_image.image)
-        })
+    pub fn get_shadow_image(&mut self, shadow: &Shadow, scale: &ShadowScale) -> Option<&Image> {
+        self.images.get(&(shadow.clone(), *scale))
     }
 
+    /// Return true if a given shadow is cached at any scale
     pub fn has_cached_shadow(&self, shadow: &Shadow) -> bool {
-        self.images.contains_key(shadow)
+        self.shadow_keys(shadow).next().is_some()
     }
 
     pub fn count_cached_shadows(&self) -> usize {
         self.images.len()
     }
 
-    pub fn push_shadow_image(&mut self, shadow: Shadow, image: Image) {
-        self.images.insert(shadow, CachedShadowImage::new(image));
+    pub fn count_cached_bytes(&self) -> usize {
+        self.images.bytes()
+    }
+
+    pub fn push_shadow_image(&mut self, shadow: Shadow, scale: ShadowScale, image: Image) {
+        let bytes = image.image_info().compute_min_byte_size();
+        if self.pinned.contains(&shadow) {
+            self.images.pin((shadow.clone(), scale));
+        }
+        self.images.insert((shadow, scale), image, bytes);
+    }
+
+    /// Remove the images of a given shadow at every scale, return true if there were any
+    pub fn remove_shadow_images(&mut self, shadow: &Shadow) -> bool {
+        let keys: Vec<(Shadow, ShadowScale)> = self.shadow_keys(shadow).cloned().collect();
+        keys.iter().for_each(|key| {
+            self.images.remove(key);
+        });
+        !keys.is_empty()
+    }
+
+    /// Keep the images of a given shadow at every scale regardless of their age and the byte budget
+    pub fn pin_shadow(&mut self, shadow: Shadow) {
+        let keys: Vec<(Shadow, ShadowScale)> = self.shadow_keys(&shadow).cloned().collect();
+        keys.into_iter().for_each(|key| self.images.pin(key));
+        self.pinned.insert(shadow);
+    }
+
+    pub fn unpin_shadow(&mut self, shadow: &Shadow) -> bool {
+        let keys: Vec<(Shadow, ShadowScale)> = self.shadow_keys(shadow).cloned().collect();
+        keys.iter().for_each(|key| {
+            self.images.unpin(key);
+        });
+        self.pinned.remove(shadow)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = CachedShadowEntry<'_>> {
+        self.images.iter().map(|(key, entry)| CachedShadowEntry {
+            shadow: &key.0,
+            scale: key.1,
+            bytes: entry.bytes,
+            last_used_frame: entry.last_used_frame,
+            pinned: self.images.is_pinned(key),
+        })
     }
 
     pub fn clear(&mut self) {
         self.images.clear();
     }
 
+    fn shadow_keys<'a>(
+        &'a self,
+        shadow: &'a Shadow,
+    ) -> impl Iterator<Item = &'a (Shadow, ShadowScale)> + 'a {
+        self.images
+            .iter()
+            .map(|(key, _)| key)
+            .filter(move |(cached_shadow, _)| cached_shadow == shadow)
+    }
+
+    /// Start a new frame, images that are not used during it age by one frame
     pub fn mark_images_as_not_used(&mut self) {
-        for cached_image in self.images.values_mut() {
-            cached_image.mark_not_used();
-        }
+        self.images.begin_frame();
     }
 
-    pub fn remove_unused_images(&mut self) -> usize {
-        let size = self.images.len();
-        self.images
-            .retain(|_, cached_image| !cached_image.should_purge());
-        size - self.images.len()
+    /// Remove unpinned images that were not used for more than a given amount of frames
+    pub fn remove_unused_images(&mut self, max_unused_frames: u64) -> usize {
+        self.images.remove_unused(max_unused_frames)
     }
 }
 
@@ -1,11 +1,18 @@
-use crate::utils::{clip_canvas, draw_shadow};
-use crate::{as_skia_point, into_skia_matrix, to_skia_point, SkiaDrawable};
+use crate::utils::{
+    clip_canvas, draw_geometry, draw_geometry_layer, draw_image_layer, draw_image_placeholder,
+    draw_shadow,
+};
+use crate::{
+    as_skia_point, compile_runtime_shader, decode_image_source, into_skia_matrix,
+    make_runtime_shader, to_skia_point, SkiaDrawable,
+};
 use compositor::{
-    ClipLayer, Compositor, DynamicOffsetLayer, ExplicitLayer, Layer, LeftoverStateLayer,
-    OffsetLayer, OpacityLayer, PictureLayer, Shadow, ShadowLayer, StateCommandType,
-    TextureLayer, TiledLayer, TransformationLayer,
+    ClipLayer, Compositor, DynamicOffsetLayer, ExplicitLayer, GeometryLayer, ImageLayer, Layer,
+    LeftoverStateLayer, ListLayer, OffsetLayer, OpacityLayer, PictureLayer, ShaderLayer, Shadow,
+    ShadowLayer, StateCommandType, TextureLayer, TiledLayer, TransformationLayer,
 };
-use skia_safe::{Canvas, Vector};
+use log::error;
+use skia_safe::{Canvas, Paint, Vector};
 use std::sync::Arc;
 
 #[derive(Debug)]
@@ -129,6 +136,20 @@ impl<'canvas> Compositor for SkiaCachelessCompositor<'canvas> {
         self.canvas.restore();
     }
 
+    fn compose_list(&mut self, layer: &ListLayer) {
+        let offset = layer.canvas_offset();
+
+        self.canvas.save();
+        self.canvas
+            .translate(Vector::new(offset.x().into(), offset.y().into()));
+
+        for row_picture in layer.visible_row_pictures() {
+            row_picture.compose(self);
+        }
+
+        self.canvas.restore();
+    }
+
     fn compose_explicit(&mut self, layer: &ExplicitLayer) {
         let drawable = layer
             .drawable()
@@ -142,6 +163,43 @@ impl<'canvas> Compositor for SkiaCachelessCompositor<'canvas> {
     }
 
     fn compose_texture(&mut self, layer: &TextureLayer) {}
+
+    /// There is no cache to keep decoded images between frames, so the image is decoded in place
+    fn compose_image(&mut self, layer: &ImageLayer) {
+        let image = decode_image_source(layer.source()).and_then(|image| {
+            if layer.sampling().needs_mipmaps() {
+                image.with_default_mipmaps()
+            } else {
+                Some(image)
+            }
+        });
+
+        match image {
+            None => draw_image_placeholder(self.canvas, layer, None),
+            Some(image) => draw_image_layer(self.canvas, &image, layer, &Paint::default()),
+        }
+    }
+
+    fn compose_geometry(&mut self, layer: &GeometryLayer) {
+        draw_geometry_layer(self.canvas, layer, None);
+    }
+
+    /// The shader is compiled and its child images are decoded in place every time
+    fn compose_shader(&mut self, layer: &ShaderLayer) {
+        let shader = compile_runtime_shader(layer.shader())
+            .and_then(|effect| make_runtime_shader(&effect, layer, decode_image_source));
+
+        match shader {
+            Err(error) => error!("Runtime shader {}: {}", layer.shader().id(), error),
+            Ok(None) => {}
+            Ok(Some(shader)) => {
+                let mut paint = Paint::default();
+                paint.set_anti_alias(true);
+                paint.set_shader(shader);
+                draw_geometry(self.canvas, layer.geometry(), &paint);
+            }
+        }
+    }
 }
 
 impl<'canvas> SkiaCachelessCompositor<'canvas> {
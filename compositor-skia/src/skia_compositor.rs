@@ -4,24 +4,32 @@ use std::sync::Arc;
 use log::error;
 
 use compositor::{
-    ClipLayer, Compositor, DynamicOffsetLayer, ExplicitLayer, Extent, Layer, LeftoverStateLayer,
-    OffsetLayer, OpacityLayer, Picture, PictureLayer, Point, Shadow, ShadowLayer, StateCommandType,
-    Texture, TextureLayer, TiledLayer, TransformationLayer,
+    ClipLayer, Compositor, DynamicOffsetLayer, ExplicitLayer, Extent, GeometryLayer, ImageLayer,
+    InvalidLayerTree, Layer, LayerValidator, LeftoverStateLayer, ListLayer, OffsetLayer,
+    OpacityLayer, Picture, PictureCacheHint, PictureLayer, Point, ShaderLayer, Shadow, ShadowLayer,
+    StateCommandType, Texture, TextureLayer, TiledLayer, TiledLayerTile, TransformationLayer,
+    optimize_layer_tree,
 };
 use compositor_skia_platform::Platform;
 use skia_safe::gpu::{Budgeted, SurfaceOrigin};
 use skia_safe::surface::{BackendHandleAccess, ContentChangeMode};
 use skia_safe::{
-    AlphaType, Canvas, Color4f, ColorType, FilterMode, Font, Image, ImageInfo, Matrix, MipmapMode,
-    Paint, PictureRecorder, Point as SkPoint, RRect, Rect, SamplingOptions, Size, Vector, gpu,
+    AlphaType, Canvas, Color as SkColor, Color4f, ColorType, FilterMode, Font, Image, ImageInfo,
+    Matrix, MipmapMode, Paint, PictureRecorder, Point as SkPoint, RRect, Rect, SamplingOptions,
+    Size, Vector, gpu,
 };
 
 use crate::renderers::PictureToRasterize;
 use crate::textures::disassemble_backend_texture;
-use crate::utils::{clip_canvas, draw_image, draw_shadow};
+use crate::utils::{
+    clip_canvas, draw_geometry, draw_geometry_layer, draw_image, draw_image_layer,
+    draw_image_placeholder, draw_image_with_sampling, draw_shadow,
+};
 use crate::{
-    Cache, PictureRasterizer, ShadowRasterizer, ShadowToRasterize, SkiaDrawable, SkiaPicture,
-    as_skia_point, into_skia_matrix, to_skia_point,
+    Cache, PictureMergeOptions, PictureScaleOptions, RasterCacheHeuristics, RasterizationTimings,
+    RasterizedPicture, ScaleQuantization, ShadowDownsampling, ShadowScale, ShadowToRasterize,
+    SkiaDrawable, SkiaPicture, SkiaValidationRules, as_skia_point, into_skia_matrix,
+    make_runtime_shader, merge_small_pictures, to_skia_point,
 };
 
 #[derive(Debug)]
@@ -30,15 +38,33 @@ pub struct SkiaCompositor<'canvas, 'cache> {
     canvas: &'canvas Canvas,
     cache: &'cache mut Cache,
     alpha: Option<f32>,
+    /// Validate the whole tree before drawing and refuse to compose it if it is invalid
+    strict: bool,
+    /// Remove redundant layers before drawing
+    optimize: bool,
+    removed_layers: usize,
+    /// Merge runs of small sibling pictures before drawing
+    picture_merging: Option<PictureMergeOptions>,
+    rasterization: RasterizationTimings,
+    /// Decides whether pictures with `PictureCacheHint::Auto` are cached
+    raster_cache_heuristics: Option<RasterCacheHeuristics>,
+    /// Decides when cached picture images are reused under a different canvas scale
+    picture_scale: PictureScaleOptions,
+    /// The scales shadows are rasterized and cached at
+    shadow_scale_quantization: ScaleQuantization,
+    /// Rasterize shadows with a large blur radius at a reduced resolution
+    shadow_downsampling: Option<ShadowDownsampling>,
 }
 
 impl<'canvas, 'cache> Compositor for SkiaCompositor<'canvas, 'cache> {
     fn compose(&mut self, layer: Arc<dyn Layer>) {
-        self.cache.mark_images_as_not_used();
-
-        layer.compose(self);
-
-        self.cache.remove_unused_images();
+        if self.strict {
+            if let Err(invalid) = self.try_compose(layer) {
+                error!("Refused to compose {}", invalid);
+            }
+        } else {
+            self.compose_unchecked(layer);
+        }
     }
 
     fn compose_clip(&mut self, layer: &ClipLayer) {
@@ -94,47 +120,13 @@ impl<'canvas, 'cache> Compositor for SkiaCompositor<'canvas, 'cache> {
     }
 
     fn compose_shadow(&mut self, layer: &ShadowLayer) {
-        let paint = self.create_layer_paint();
-        let canvas = &mut self.canvas;
+        // identical shadows at different positions share one cached image
+        let (shadow, origin) = layer.shadow().normalize();
 
-        match self.cache.get_shadow_image(layer.shadow()) {
-            None => {
-                let rasterized_shadow = ShadowRasterizer::new()
-                    .rasterize(ShadowToRasterize::new(layer.shadow().clone()), canvas);
-
-                match rasterized_shadow.image {
-                    None => {
-                        self.draw_shadow(layer.shadow());
-                    }
-                    Some(image) => {
-                        draw_image(
-                            canvas,
-                            &image,
-                            &Matrix::new_identity(),
-                            &layer
-                                .shadow()
-                                .cull_rect()
-                                .translate(&layer.shadow().inflation_offset().neg()),
-                            paint.as_ref(),
-                        );
-
-                        self.cache.push_shadow_image(layer.shadow().clone(), image);
-                    }
-                }
-            }
-            Some(image) => {
-                draw_image(
-                    canvas,
-                    image,
-                    &Matrix::new_identity(),
-                    &layer
-                        .shadow()
-                        .cull_rect()
-                        .translate(&layer.shadow().inflation_offset().neg()),
-                    paint.as_ref(),
-                );
-            }
-        }
+        self.canvas.save();
+        self.canvas.translate(to_skia_point(origin));
+        self.draw_normalized_shadow(&shadow);
+        self.canvas.restore();
 
         for layer in layer.layers() {
             layer.compose(self);
@@ -153,6 +145,8 @@ impl<'canvas, 'cache> Compositor for SkiaCompositor<'canvas, 'cache> {
     }
 
     fn compose_picture(&mut self, layer: &PictureLayer) {
+        // pictures are observed in every frame they are drawn in, cached or not
+        let needs_cache = self.should_cache_picture(layer);
         match self.cache.get_picture_image(layer.id()) {
             None => {
                 let canvas = &mut self.canvas;
@@ -162,28 +156,43 @@ impl<'canvas, 'cache> Compositor for SkiaCompositor<'canvas, 'cache> {
                     .downcast_ref::<skia_safe::Picture>()
                     .expect("Picture is not Skia Picture!");
 
-                if layer.needs_cache() {
-                    let rasterized_picture = PictureRasterizer::new().rasterize(
-                        PictureToRasterize::new(picture.clone(), canvas.local_to_device_as_3x3()),
+                if needs_cache {
+                    let matrix = self
+                        .picture_scale
+                        .rasterization_matrix(&canvas.local_to_device_as_3x3());
+                    let rasterized_picture = self.cache.rasterize_picture(
                         canvas,
+                        PictureToRasterize::new(picture.clone(), matrix),
                     );
 
-                    match rasterized_picture.image {
+                    match rasterized_picture {
                         None => {
+                            // rasterized in the background, draw the picture directly meanwhile
+                            canvas.draw_picture(picture, None, self.create_layer_paint().as_ref());
+                        }
+                        Some(RasterizedPicture {
+                            image: None, stats, ..
+                        }) => {
+                            self.rasterization.add_picture(&stats);
                             error!("Failed to rasterize picture");
                             canvas.draw_picture(picture, None, self.create_layer_paint().as_ref());
                         }
-                        Some(image) => {
+                        Some(RasterizedPicture {
+                            image: Some(image),
+                            matrix,
+                            stats,
+                            ..
+                        }) => {
+                            self.rasterization.add_picture(&stats);
                             draw_image(
                                 canvas,
                                 &image,
-                                &rasterized_picture.matrix,
+                                &matrix,
                                 &layer.cull_rect(),
                                 self.create_layer_paint().as_ref(),
                             );
 
-                            self.cache
-                                .push_id_image(layer.id(), image, rasterized_picture.matrix);
+                            self.cache.push_id_image(layer.id(), image, matrix);
                         }
                     }
                 } else {
@@ -191,32 +200,57 @@ impl<'canvas, 'cache> Compositor for SkiaCompositor<'canvas, 'cache> {
                 }
             }
             Some((image, matrix)) => {
-                let current_canvas_scale =
-                    self.canvas.local_to_device_as_3x3().decompose_scale(None);
-                let image_scale = matrix.decompose_scale(None);
-
-                if current_canvas_scale == image_scale {
-                    let mut paint = self
-                        .create_layer_paint()
-                        .unwrap_or_else(|| Paint::default());
-                    paint.set_anti_alias(true);
-
-                    draw_image(
-                        self.canvas,
-                        &image,
-                        &matrix,
-                        &layer.cull_rect(),
-                        Some(&paint),
-                    );
+                let canvas_matrix = self.canvas.local_to_device_as_3x3();
+
+                if self.picture_scale.can_reuse(&matrix, &canvas_matrix) {
+                    self.draw_cached_picture_image(layer, &image, &matrix);
                 } else {
-                    self.cache.remove_picture_image(layer.id());
                     let compositor_picture = layer.picture();
                     let picture = compositor_picture
                         .any()
                         .downcast_ref::<skia_safe::Picture>()
                         .expect("Picture is not Skia Picture!");
-                    self.canvas
-                        .draw_picture(picture, None, self.create_layer_paint().as_ref());
+
+                    if self.picture_scale.rescale_in_background {
+                        let rasterization_matrix =
+                            self.picture_scale.rasterization_matrix(&canvas_matrix);
+                        let rescaled_picture = self.cache.rasterize_picture(
+                            self.canvas,
+                            PictureToRasterize::new(picture.clone(), rasterization_matrix),
+                        );
+
+                        // the old image is drawn scaled until the new one is ready
+                        match rescaled_picture {
+                            Some(RasterizedPicture {
+                                image: Some(rescaled_image),
+                                matrix: rescaled_matrix,
+                                stats,
+                                ..
+                            }) => {
+                                self.rasterization.add_picture(&stats);
+                                self.draw_cached_picture_image(
+                                    layer,
+                                    &rescaled_image,
+                                    &rescaled_matrix,
+                                );
+                                self.cache.push_id_image(
+                                    layer.id(),
+                                    rescaled_image,
+                                    rescaled_matrix,
+                                );
+                            }
+                            Some(RasterizedPicture { stats, .. }) => {
+                                self.rasterization.add_picture(&stats);
+                                error!("Failed to rasterize picture at a new scale");
+                                self.draw_cached_picture_image(layer, &image, &matrix);
+                            }
+                            None => self.draw_cached_picture_image(layer, &image, &matrix),
+                        }
+                    } else {
+                        self.cache.remove_picture_image(layer.id());
+                        self.canvas
+                            .draw_picture(picture, None, self.create_layer_paint().as_ref());
+                    }
                 }
             }
         }
@@ -247,6 +281,8 @@ impl<'canvas, 'cache> Compositor for SkiaCompositor<'canvas, 'cache> {
     }
 
     fn compose_tiled(&mut self, layer: &TiledLayer) {
+        self.cache.remove_released_tile_images(layer);
+
         let offset = layer.canvas_offset();
 
         self.canvas.save();
@@ -255,15 +291,17 @@ impl<'canvas, 'cache> Compositor for SkiaCompositor<'canvas, 'cache> {
 
         self.scale_tiled_layer(layer);
 
+        let mut recording_budget = layer.tile_recording_budget();
+
         layer.visible_tiles().into_iter().for_each(|tile| {
-            if layer.is_debug_mode() {
-                let rect = Rect::new(
-                    tile.left().into(),
-                    tile.top().into(),
-                    tile.right().into(),
-                    tile.bottom().into(),
-                );
+            let rect = Rect::new(
+                tile.left().into(),
+                tile.top().into(),
+                tile.right().into(),
+                tile.bottom().into(),
+            );
 
+            if layer.is_debug_mode() {
                 let mut paint = Paint::new(Color4f::new(0.8, 0.8, 0.8, 1.0), None);
                 paint.set_stroke(true);
                 paint.set_stroke_width(0.5);
@@ -271,50 +309,52 @@ impl<'canvas, 'cache> Compositor for SkiaCompositor<'canvas, 'cache> {
                 self.canvas.draw_rect(&rect, &paint);
             }
 
-            let mut tile_picture = layer.get_tile_picture(&tile);
-            if tile_picture.is_none() {
-                let mut recorder = PictureRecorder::new();
-                let canvas = recorder.begin_recording(
-                    Rect::new(0.0, 0.0, tile.width().into(), tile.height().into()),
-                    false,
-                );
-
-                let mut compositor = SkiaCompositor::new(self.platform.clone(), canvas, self.cache);
+            if let Some(tile_picture) = layer.get_tile_picture(&tile) {
+                self.compose_tile_picture(&tile, tile_picture);
+                return;
+            }
 
-                for figure in layer.figures_overlapping_tile(&tile) {
-                    if let Some(picture) = figure.get_picture() {
-                        let picture_with_offset = OffsetLayer::wrap_with_offset(
-                            picture,
-                            figure.offset().clone() - tile.origin(),
-                        );
-                        picture_with_offset.compose(&mut compositor);
-                    }
+            let fallback = layer.fallback_tile_pictures(&tile);
+            if fallback.is_empty() || recording_budget.is_none_or(|budget| budget > 0) {
+                if let Some(tile_picture) = self.record_tile_picture(layer, &tile) {
+                    layer.cache_tile_picture(&tile, tile_picture.clone());
+                    self.compose_tile_picture(&tile, tile_picture);
                 }
-
-                let recorded_tile_picture: Option<PictureLayer> = recorder
-                    .finish_recording_as_picture(None)
-                    .map(|tile_picture| {
-                        Arc::new(SkiaPicture::new(tile_picture)) as Arc<dyn Picture>
-                    })
-                    .map(|tile_picture| PictureLayer::new(tile_picture, true));
-
-                if let Some(ref picture) = recorded_tile_picture {
-                    layer.cache_tile_picture(&tile, picture.clone());
-                };
-
-                tile_picture = recorded_tile_picture;
+                if let Some(budget) = recording_budget.as_mut() {
+                    *budget = budget.saturating_sub(1);
+                }
+                return;
             }
 
-            let tile_picture = tile_picture.map(|picture| {
-                let _image = self.cache.get_picture_image(picture.id());
-                OffsetLayer::wrap_with_offset(picture, tile.origin())
-            });
-
-            if let Some(tile_picture) = tile_picture {
-                tile_picture.compose(self)
+            // draw scaled tiles of the closest zoom level until the tile is recorded
+            // in one of the next frames
+            if let Some(placeholder) = layer.tile_placeholder() {
+                let mut paint = Paint::default();
+                paint.set_color(SkColor::new(placeholder.as_argb()));
+                self.canvas.draw_rect(rect, &paint);
             }
+            self.canvas.save();
+            self.canvas.clip_rect(rect, None, None);
+            for (fallback_tile, fallback_picture) in fallback {
+                self.compose_tile_picture(&fallback_tile, fallback_picture);
+            }
+            self.canvas.restore();
         });
 
+        // tiles around the viewport are recorded with what is left of the budget, and only
+        // when their pictures can be rasterized without blocking the frame
+        if self.cache.rasterizes_in_background() {
+            for tile in layer.prefetch_tiles() {
+                if recording_budget.is_some_and(|budget| budget == 0) {
+                    break;
+                }
+                self.prefetch_tile(layer, &tile);
+                if let Some(budget) = recording_budget.as_mut() {
+                    *budget = budget.saturating_sub(1);
+                }
+            }
+        }
+
         self.canvas.restore();
 
         if layer.is_debug_mode() {
@@ -322,6 +362,20 @@ impl<'canvas, 'cache> Compositor for SkiaCompositor<'canvas, 'cache> {
         }
     }
 
+    fn compose_list(&mut self, layer: &ListLayer) {
+        let offset = layer.canvas_offset();
+
+        self.canvas.save();
+        self.canvas
+            .translate(Vector::new(offset.x().into(), offset.y().into()));
+
+        for row_picture in layer.visible_row_pictures() {
+            row_picture.compose(self);
+        }
+
+        self.canvas.restore();
+    }
+
     fn compose_explicit(&mut self, layer: &ExplicitLayer) {
         let drawable = layer
             .drawable()
@@ -436,6 +490,45 @@ impl<'canvas, 'cache> Compositor for SkiaCompositor<'canvas, 'cache> {
             Texture::External(_) => {}
         }
     }
+
+    fn compose_image(&mut self, layer: &ImageLayer) {
+        let needs_mipmaps = layer.sampling().needs_mipmaps();
+
+        match self.cache.get_decoded_image(layer.source(), needs_mipmaps) {
+            None => draw_image_placeholder(self.canvas, layer, self.alpha),
+            Some(image) => {
+                let paint = self.create_layer_paint().unwrap_or_default();
+                draw_image_layer(self.canvas, &image, layer, &paint);
+            }
+        }
+    }
+
+    fn compose_geometry(&mut self, layer: &GeometryLayer) {
+        draw_geometry_layer(self.canvas, layer, self.alpha);
+    }
+
+    fn compose_shader(&mut self, layer: &ShaderLayer) {
+        // compilation errors are reported once by the cache
+        let Ok(effect) = self.cache.get_runtime_effect(layer.shader()) else {
+            return;
+        };
+
+        let shader = make_runtime_shader(&effect, layer, |image| {
+            self.cache.get_decoded_image(image, false)
+        });
+
+        match shader {
+            Err(error) => error!("Runtime shader {}: {}", layer.shader().id(), error),
+            // child images are still being decoded
+            Ok(None) => {}
+            Ok(Some(shader)) => {
+                let mut paint = self.create_layer_paint().unwrap_or_default();
+                paint.set_anti_alias(true);
+                paint.set_shader(shader);
+                draw_geometry(self.canvas, layer.geometry(), &paint);
+            }
+        }
+    }
 }
 
 impl<'canvas, 'cache> SkiaCompositor<'canvas, 'cache> {
@@ -444,11 +537,229 @@ impl<'canvas, 'cache> SkiaCompositor<'canvas, 'cache> {
         canvas: &'canvas Canvas,
         cache: &'cache mut Cache,
     ) -> Self {
+        let raster_cache_heuristics = cache.raster_cache_heuristics();
         Self {
             platform,
             canvas,
             cache,
             alpha: None,
+            strict: cfg!(debug_assertions),
+            optimize: false,
+            removed_layers: 0,
+            picture_merging: None,
+            rasterization: RasterizationTimings::default(),
+            raster_cache_heuristics,
+            picture_scale: PictureScaleOptions::default(),
+            shadow_scale_quantization: Self::DEFAULT_SHADOW_SCALE_QUANTIZATION,
+            shadow_downsampling: None,
+        }
+    }
+
+    /// Shadows are rasterized at scales rounded up to a quarter,
+    /// so that zooming does not rasterize them again for every frame
+    pub const DEFAULT_SHADOW_SCALE_QUANTIZATION: ScaleQuantization = ScaleQuantization::Step(0.25);
+
+    /// Validate every composed tree before drawing it, which walks the whole tree each frame.
+    /// Enabled by default in debug builds only
+    pub fn with_strict_validation(self, strict: bool) -> Self {
+        Self { strict, ..self }
+    }
+
+    /// Optimize layer trees with `optimize_layer_tree` before drawing them
+    pub fn with_optimization(self, optimize: bool) -> Self {
+        Self { optimize, ..self }
+    }
+
+    /// Merge runs of small sibling pictures into one picture per group, reusing the merged
+    /// pictures of previous frames
+    pub fn with_picture_merging(self, options: PictureMergeOptions) -> Self {
+        Self {
+            picture_merging: Some(options),
+            ..self
+        }
+    }
+
+    /// Let the compositor decide which pictures with `PictureCacheHint::Auto` are cached,
+    /// instead of the heuristics of the cache.
+    /// Without heuristics such pictures are never cached
+    pub fn with_raster_cache_heuristics(self, heuristics: RasterCacheHeuristics) -> Self {
+        Self {
+            raster_cache_heuristics: Some(heuristics),
+            ..self
+        }
+    }
+
+    pub fn with_picture_scale_options(self, picture_scale: PictureScaleOptions) -> Self {
+        Self {
+            picture_scale,
+            ..self
+        }
+    }
+
+    pub fn with_shadow_scale_quantization(self, quantization: ScaleQuantization) -> Self {
+        Self {
+            shadow_scale_quantization: quantization,
+            ..self
+        }
+    }
+
+    /// Trade the quality of shadows with a large blur radius for rasterization time and memory
+    pub fn with_shadow_downsampling(self, downsampling: ShadowDownsampling) -> Self {
+        Self {
+            shadow_downsampling: Some(downsampling),
+            ..self
+        }
+    }
+
+    /// The amount of layers removed by the optimization of the last composed tree
+    pub fn count_removed_layers(&self) -> usize {
+        self.removed_layers
+    }
+
+    /// Time spent rasterizing pictures and shadows that were not cached yet
+    pub fn rasterization_timings(&self) -> &RasterizationTimings {
+        &self.rasterization
+    }
+
+    /// Validate a given layer tree and compose it only if no problems were found
+    pub fn try_compose(&mut self, layer: Arc<dyn Layer>) -> Result<(), InvalidLayerTree> {
+        let diagnostics = LayerValidator::new(&SkiaValidationRules).validate(layer.as_ref());
+        if !diagnostics.is_empty() {
+            return Err(InvalidLayerTree(diagnostics));
+        }
+
+        self.compose_unchecked(layer);
+        Ok(())
+    }
+
+    fn compose_unchecked(&mut self, layer: Arc<dyn Layer>) {
+        let layer = if self.optimize {
+            let optimized = optimize_layer_tree(&layer);
+            self.removed_layers = optimized.removed_layers();
+            optimized.into_layer()
+        } else {
+            layer
+        };
+
+        self.cache.receive_decoded_images();
+        self.cache.receive_rasterized_images();
+        self.cache.mark_images_as_not_used();
+
+        let layer = match &self.picture_merging {
+            Some(options) => {
+                merge_small_pictures(&layer, options, &mut self.cache.picture_merge_cache)
+            }
+            None => layer,
+        };
+
+        layer.compose(self);
+
+        self.cache.remove_unused_images();
+    }
+
+    /// Draw the cached image of a picture, scaling it if it was rasterized at a different scale
+    fn draw_cached_picture_image(&mut self, layer: &PictureLayer, image: &Image, matrix: &Matrix) {
+        let mut paint = self
+            .create_layer_paint()
+            .unwrap_or_else(|| Paint::default());
+        paint.set_anti_alias(true);
+
+        draw_image(self.canvas, image, matrix, &layer.cull_rect(), Some(&paint));
+    }
+
+    fn should_cache_picture(&mut self, layer: &PictureLayer) -> bool {
+        match layer.cache_hint() {
+            PictureCacheHint::Always => true,
+            PictureCacheHint::Never => false,
+            PictureCacheHint::Auto => match self.raster_cache_heuristics {
+                None => false,
+                Some(heuristics) => {
+                    let usage = self
+                        .cache
+                        .observe_picture(layer.id(), self.canvas.local_to_device_as_3x3());
+                    heuristics.should_cache(layer.picture().as_ref(), &usage)
+                }
+            },
+        }
+    }
+
+    /// Draw a shadow moved to the pixel origin from the cache, rasterizing it if needed.
+    /// Shadows are cached at the quantized scale of the canvas, rotated or skewed shadows and
+    /// shadows too large for an image at that scale are drawn directly
+    fn draw_normalized_shadow(&mut self, shadow: &Shadow) {
+        let scale = match ShadowScale::from_matrix(
+            &self.canvas.local_to_device_as_3x3(),
+            self.shadow_scale_quantization,
+        ) {
+            None => {
+                self.draw_shadow(shadow);
+                return;
+            }
+            Some(scale) => scale,
+        };
+
+        let downsampling_factor = self
+            .shadow_downsampling
+            .map(|downsampling| downsampling.factor(shadow, &scale))
+            .unwrap_or(1.0);
+        // downsampled shadows are upsampled with linear filtering when drawn
+        let (scale, sampling) = if downsampling_factor > 1.0 {
+            (
+                scale.downsample(downsampling_factor),
+                SamplingOptions::new(FilterMode::Linear, MipmapMode::None),
+            )
+        } else {
+            (scale, SamplingOptions::default())
+        };
+        if !scale.can_rasterize(shadow) {
+            self.draw_shadow(shadow);
+            return;
+        }
+
+        let paint = self.create_layer_paint();
+        let bounds = shadow
+            .cull_rect()
+            .translate(&shadow.inflation_offset().neg());
+
+        match self.cache.get_shadow_image(shadow, &scale) {
+            None => {
+                let rasterized_shadow = self.cache.rasterize_shadow(
+                    self.canvas,
+                    ShadowToRasterize::new_scaled(shadow.clone(), scale),
+                );
+                if let Some(rasterized_shadow) = &rasterized_shadow {
+                    self.rasterization.add_shadow(&rasterized_shadow.stats);
+                }
+
+                // the shadow is drawn directly while it is being rasterized in the background
+                match rasterized_shadow.and_then(|rasterized_shadow| rasterized_shadow.image) {
+                    None => {
+                        self.draw_shadow(shadow);
+                    }
+                    Some(image) => {
+                        draw_image_with_sampling(
+                            self.canvas,
+                            &image,
+                            &scale.matrix(),
+                            &bounds,
+                            sampling,
+                            paint.as_ref(),
+                        );
+
+                        self.cache.push_shadow_image(shadow.clone(), scale, image);
+                    }
+                }
+            }
+            Some(image) => {
+                draw_image_with_sampling(
+                    self.canvas,
+                    image,
+                    &scale.matrix(),
+                    &bounds,
+                    sampling,
+                    paint.as_ref(),
+                );
+            }
         }
     }
 
@@ -580,6 +891,98 @@ impl<'canvas, 'cache> SkiaCompositor<'canvas, 'cache> {
         );
     }
 
+    /// Record the figures overlapping a tile at the resolution of the tile's zoom level
+    fn record_tile_picture(
+        &mut self,
+        layer: &TiledLayer,
+        tile: &TiledLayerTile,
+    ) -> Option<PictureLayer> {
+        let mut recorder = PictureRecorder::new();
+        let canvas = recorder.begin_recording(
+            Rect::new(
+                0.0,
+                0.0,
+                layer.tile_width().into(),
+                layer.tile_height().into(),
+            ),
+            false,
+        );
+        canvas.scale((tile.zoom_scale(), tile.zoom_scale()));
+
+        let mut compositor = SkiaCompositor::new(self.platform.clone(), canvas, self.cache);
+
+        for figure in layer.figures_overlapping_tile(tile) {
+            if let Some(picture) = figure.get_picture() {
+                let picture_with_offset =
+                    OffsetLayer::wrap_with_offset(picture, figure.offset() - tile.origin());
+                picture_with_offset.compose(&mut compositor);
+            }
+        }
+
+        recorder
+            .finish_recording_as_picture(None)
+            .map(|tile_picture| Arc::new(SkiaPicture::new(tile_picture)) as Arc<dyn Picture>)
+            .map(|tile_picture| PictureLayer::new(tile_picture, true))
+    }
+
+    /// Record a tile that is not visible yet and schedule the rasterization of its picture
+    /// with the matrix it is going to be drawn with, so that its image is likely ready
+    /// by the time the tile becomes visible
+    fn prefetch_tile(&mut self, layer: &TiledLayer, tile: &TiledLayerTile) {
+        let Some(tile_picture) = self.record_tile_picture(layer, tile) else {
+            return;
+        };
+        layer.cache_tile_picture(tile, tile_picture.clone());
+
+        let scale = 1.0 / tile.zoom_scale();
+        let mut matrix = self.canvas.local_to_device_as_3x3();
+        matrix.pre_translate(Vector::new(tile.left().into(), tile.top().into()));
+        matrix.pre_scale((scale, scale), None);
+        let matrix = self.picture_scale.rasterization_matrix(&matrix);
+
+        let compositor_picture = tile_picture.picture();
+        let picture = compositor_picture
+            .any()
+            .downcast_ref::<skia_safe::Picture>()
+            .expect("Picture is not Skia Picture!");
+        let rasterized_picture = self.cache.rasterize_picture(
+            self.canvas,
+            PictureToRasterize::new(picture.clone(), matrix),
+        );
+
+        match rasterized_picture {
+            None => {}
+            Some(RasterizedPicture {
+                image: None, stats, ..
+            }) => {
+                self.rasterization.add_picture(&stats);
+                error!("Failed to rasterize tile {:?}", tile.coordinate());
+            }
+            Some(RasterizedPicture {
+                image: Some(image),
+                matrix,
+                stats,
+                ..
+            }) => {
+                self.rasterization.add_picture(&stats);
+                self.cache.push_id_image(tile_picture.id(), image, matrix);
+            }
+        }
+    }
+
+    /// Compose a tile picture at the tile's origin, scaled from the resolution
+    /// of its zoom level to the layer coordinates
+    fn compose_tile_picture(&mut self, tile: &TiledLayerTile, picture: PictureLayer) {
+        let scale = 1.0 / tile.zoom_scale();
+
+        self.canvas.save();
+        self.canvas
+            .translate(Vector::new(tile.left().into(), tile.top().into()));
+        self.canvas.scale((scale, scale));
+        picture.compose(self);
+        self.canvas.restore();
+    }
+
     /// Apply scale transformation when composing a tiled layer.
     fn scale_tiled_layer(&mut self, layer: &TiledLayer) {
         let offset = layer.camera_position().clone();
@@ -601,3 +1004,45 @@ impl<'canvas, 'cache> SkiaCompositor<'canvas, 'cache> {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ThreadPoolRasterizer;
+    use skia_safe::surfaces;
+
+    fn compose_tiled(layer: &TiledLayer) {
+        let mut cache = Cache::with_rasterizer(ThreadPoolRasterizer::new(1).unwrap());
+        let mut surface = surfaces::raster_n32_premul((600, 400)).unwrap();
+        SkiaCompositor::new(None, surface.canvas(), &mut cache).compose(Arc::new(layer.clone()));
+    }
+
+    #[test]
+    pub fn prefetching_uses_what_visible_tiles_leave_of_the_recording_budget() {
+        let layer = TiledLayer::default();
+        let visible_tiles = layer.visible_tiles().count();
+        let prefetch_tiles = layer.prefetch_tiles().len();
+
+        // visible tiles that no other zoom level covers are recorded even over the budget
+        let over_budget = layer.with_tile_recording_budget(Some(visible_tiles - 1));
+        compose_tiled(&over_budget);
+        assert!(!over_budget.has_missing_visible_tiles());
+        assert_eq!(over_budget.tile_statistics().tiles, visible_tiles);
+        assert_eq!(over_budget.prefetch_tiles().len(), prefetch_tiles);
+
+        let within_budget =
+            TiledLayer::default().with_tile_recording_budget(Some(visible_tiles + 2));
+        compose_tiled(&within_budget);
+        assert!(!within_budget.has_missing_visible_tiles());
+        assert_eq!(within_budget.tile_statistics().tiles, visible_tiles + 2);
+        assert_eq!(within_budget.prefetch_tiles().len(), prefetch_tiles - 2);
+
+        let unlimited = TiledLayer::default().with_tile_recording_budget(None);
+        compose_tiled(&unlimited);
+        assert_eq!(
+            unlimited.tile_statistics().tiles,
+            visible_tiles + prefetch_tiles
+        );
+        assert!(unlimited.prefetch_tiles().is_empty());
+    }
+}
@@ -40,6 +40,23 @@ pub fn to_compositor_color(color: skia_safe::Color) -> compositor::Color {
     unsafe { std::mem::transmute(color) }
 }
 
+pub fn into_skia_filter_mode(filter: compositor::ImageFilterMode) -> skia_safe::FilterMode {
+    match filter {
+        compositor::ImageFilterMode::Nearest => skia_safe::FilterMode::Nearest,
+        compositor::ImageFilterMode::Linear => skia_safe::FilterMode::Linear,
+    }
+}
+
+pub fn into_skia_sampling(sampling: &compositor::ImageSampling) -> skia_safe::SamplingOptions {
+    let mipmap = match sampling.mipmap {
+        compositor::ImageMipmapMode::None => skia_safe::MipmapMode::None,
+        compositor::ImageMipmapMode::Nearest => skia_safe::MipmapMode::Nearest,
+        compositor::ImageMipmapMode::Linear => skia_safe::MipmapMode::Linear,
+    };
+
+    skia_safe::SamplingOptions::new(into_skia_filter_mode(sampling.filter), mipmap)
+}
+
 pub fn into_skia_matrix(compositor_matrix: &compositor::Matrix) -> skia_safe::Matrix {
     let mut skia_matrix = skia_safe::Matrix::new_identity();
 
@@ -84,6 +101,14 @@ impl compositor::Picture for SkiaPicture {
         to_compositor_rectangle(self.0.cull_rect())
     }
 
+    fn approximate_bytes_used(&self) -> usize {
+        self.0.approximate_bytes_used()
+    }
+
+    fn approximate_op_count(&self) -> usize {
+        self.0.approximate_op_count()
+    }
+
     fn any(&self) -> &dyn Any {
         &self.0
     }
@@ -110,6 +135,12 @@ impl VectorPath for SkiaPath {
         to_compositor_rectangle(self.0.bounds().clone())
     }
 
+    fn translate(&self, offset: &compositor::Point) -> Option<Box<dyn VectorPath>> {
+        Some(Box::new(Self(
+            self.0.with_offset(as_skia_point(offset).clone()),
+        )))
+    }
+
     fn clone_box(&self) -> Box<dyn VectorPath> {
         Box::new(self.clone())
     }
@@ -126,6 +157,10 @@ impl VectorPath for SkiaPath {
         serialized.hash(state);
     }
 
+    fn approximate_bytes_used(&self) -> usize {
+        self.0.approximate_bytes_used()
+    }
+
     fn any(&self) -> &dyn Any {
         self
     }
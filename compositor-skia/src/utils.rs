@@ -1,13 +1,16 @@
 use crate::{
-    as_skia_point, into_skia_rect, into_skia_rrect, to_skia_point, PictureToRasterize, SkiaPath,
+    as_skia_point, into_skia_filter_mode, into_skia_rect, into_skia_rrect, into_skia_sampling,
+    to_skia_point, PictureToRasterize, SkiaPath,
+};
+use compositor::{
+    Fill, Geometry, GeometryLayer, ImageLayer, Rectangle, Shadow, Stroke, StrokeCap, StrokeJoin,
 };
-use compositor::{Geometry, Rectangle, Shadow};
 use log::trace;
 use skia_safe::image_filters::{drop_shadow_only, CropRect};
-use skia_safe::paint::Style;
+use skia_safe::paint::{Cap, Join, Style};
 use skia_safe::{
-    scalar, BlendMode, Canvas, ClipOp, Color, Image, Matrix, Paint, PathDirection, Point, Vector,
-    M44,
+    scalar, BlendMode, Canvas, ClipOp, Color, IRect, Image, Matrix, Paint, PathDirection,
+    PathEffect, Point, Rect, SamplingOptions, Shader, TileMode, Vector, M44,
 };
 
 pub(crate) fn clip_canvas(
@@ -67,6 +70,26 @@ pub(crate) fn draw_image(
     matrix: &Matrix,
     cull_rectangle: &Rectangle,
     paint: Option<&Paint>,
+) {
+    draw_image_with_sampling(
+        canvas,
+        image,
+        matrix,
+        cull_rectangle,
+        SamplingOptions::default(),
+        paint,
+    );
+}
+
+/// Draw an image rasterized with a given matrix, filtering it with given sampling options
+/// where the canvas scale differs from the one of the image
+pub(crate) fn draw_image_with_sampling(
+    canvas: &Canvas,
+    image: &Image,
+    matrix: &Matrix,
+    cull_rectangle: &Rectangle,
+    sampling: SamplingOptions,
+    paint: Option<&Paint>,
 ) {
     if let Some(paint) = paint {
         if paint.nothing_to_draw() {
@@ -95,7 +118,7 @@ pub(crate) fn draw_image(
 
     let position = Point::new(relative_bounds.left as f32, relative_bounds.top as f32);
     trace!("Draw image at {:?}", &position);
-    canvas.draw_image(image, position, paint);
+    canvas.draw_image_with_sampling_options(image, position, sampling, paint);
     canvas.restore();
 }
 
@@ -162,3 +185,172 @@ pub(crate) fn draw_geometry(canvas: &Canvas, geometry: &Geometry, paint: &Paint)
         }
     }
 }
+
+/// Draw a decoded image of a given image layer stretched to the layer's extent,
+/// either as a whole or as a nine-patch
+pub(crate) fn draw_image_layer(canvas: &Canvas, image: &Image, layer: &ImageLayer, paint: &Paint) {
+    let destination = Rect::from_wh(
+        layer.extent().width().into(),
+        layer.extent().height().into(),
+    );
+
+    match layer.center() {
+        None => {
+            canvas.draw_image_rect_with_sampling_options(
+                image,
+                None,
+                destination,
+                into_skia_sampling(layer.sampling()),
+                paint,
+            );
+        }
+        Some(center) => {
+            let center = IRect::new(
+                f32::from(center.left()).round() as i32,
+                f32::from(center.top()).round() as i32,
+                f32::from(center.right()).round() as i32,
+                f32::from(center.bottom()).round() as i32,
+            );
+            canvas.draw_image_nine(
+                image,
+                center,
+                destination,
+                into_skia_filter_mode(layer.sampling().filter),
+                Some(paint),
+            );
+        }
+    }
+}
+
+/// Fill the extent of a given image layer with its placeholder color, if any
+pub(crate) fn draw_image_placeholder(canvas: &Canvas, layer: &ImageLayer, alpha: Option<f32>) {
+    if let Some(placeholder) = layer.placeholder() {
+        let mut paint = Paint::default();
+        paint.set_color(Color::new(placeholder.as_argb()));
+        if let Some(alpha) = alpha {
+            paint.set_alpha_f(paint.alpha_f() * alpha);
+        }
+
+        let destination = Rect::from_wh(
+            layer.extent().width().into(),
+            layer.extent().height().into(),
+        );
+        canvas.draw_rect(destination, &paint);
+    }
+}
+
+/// Fill and then stroke the geometry of a given layer
+pub(crate) fn draw_geometry_layer(canvas: &Canvas, layer: &GeometryLayer, alpha: Option<f32>) {
+    if let Some(fill) = layer.fill() {
+        let mut paint = fill_paint(fill, alpha);
+        paint.set_style(Style::Fill);
+        draw_geometry(canvas, layer.geometry(), &paint);
+    }
+
+    if let Some(stroke) = layer.stroke() {
+        let paint = stroke_paint(stroke, alpha);
+        draw_geometry(canvas, layer.geometry(), &paint);
+    }
+}
+
+fn fill_paint(fill: &Fill, alpha: Option<f32>) -> Paint {
+    let mut paint = Paint::default();
+    paint.set_anti_alias(true);
+
+    match fill {
+        Fill::Solid(color) => {
+            paint.set_color(Color::new(color.as_argb()));
+        }
+        Fill::LinearGradient { start, end, .. } => {
+            let (colors, positions) = gradient_stops(fill);
+            paint.set_shader(Shader::linear_gradient(
+                (to_skia_point(*start), to_skia_point(*end)),
+                colors.as_slice(),
+                positions.as_slice(),
+                TileMode::Clamp,
+                None,
+                None,
+            ));
+        }
+        Fill::RadialGradient { center, radius, .. } => {
+            let (colors, positions) = gradient_stops(fill);
+            paint.set_shader(Shader::radial_gradient(
+                to_skia_point(*center),
+                (*radius).into(),
+                colors.as_slice(),
+                positions.as_slice(),
+                TileMode::Clamp,
+                None,
+                None,
+            ));
+        }
+        Fill::SweepGradient {
+            center,
+            start_angle,
+            end_angle,
+            ..
+        } => {
+            let (colors, positions) = gradient_stops(fill);
+            let angles: (scalar, scalar) = ((*start_angle).into(), (*end_angle).into());
+            paint.set_shader(Shader::sweep_gradient(
+                to_skia_point(*center),
+                colors.as_slice(),
+                positions.as_slice(),
+                TileMode::Clamp,
+                angles,
+                None,
+                None,
+            ));
+        }
+    }
+
+    if let Some(alpha) = alpha {
+        paint.set_alpha_f(paint.alpha_f() * alpha);
+    }
+
+    paint
+}
+
+fn stroke_paint(stroke: &Stroke, alpha: Option<f32>) -> Paint {
+    let mut paint = fill_paint(stroke.fill(), alpha);
+    paint.set_style(Style::Stroke);
+    paint.set_stroke_width(stroke.width().into());
+    paint.set_stroke_cap(match stroke.cap() {
+        StrokeCap::Butt => Cap::Butt,
+        StrokeCap::Round => Cap::Round,
+        StrokeCap::Square => Cap::Square,
+    });
+    paint.set_stroke_join(match stroke.join() {
+        StrokeJoin::Miter => Join::Miter,
+        StrokeJoin::Round => Join::Round,
+        StrokeJoin::Bevel => Join::Bevel,
+    });
+
+    if let Some(dash) = stroke.dash() {
+        let intervals: Vec<scalar> = dash
+            .intervals()
+            .iter()
+            .map(|interval| (*interval).into())
+            .collect();
+        // skia requires an even amount of positive intervals, otherwise the dash is ignored
+        match PathEffect::dash(intervals.as_slice(), dash.phase().into()) {
+            None => trace!("Invalid dash intervals {:?}", intervals),
+            Some(dash_effect) => {
+                paint.set_path_effect(dash_effect);
+            }
+        }
+    }
+
+    paint
+}
+
+fn gradient_stops(fill: &Fill) -> (Vec<Color>, Vec<scalar>) {
+    fill.stops()
+        .iter()
+        .map(|stop| {
+            let color = Color::new(stop.color().as_argb());
+            let position: scalar = stop.position().into();
+            (color, position)
+        })
+        .unzip()
+}
@@ -0,0 +1,21 @@
+use compositor::{Drawable, Path, Picture, ValidationRules};
+
+use crate::{SkiaDrawable, SkiaPath};
+
+/// Rejects pictures, paths and drawables that were not created by the Skia backend
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SkiaValidationRules;
+
+impl ValidationRules for SkiaValidationRules {
+    fn is_supported_picture(&self, picture: &dyn Picture) -> bool {
+        picture.any().is::<skia_safe::Picture>()
+    }
+
+    fn is_supported_path(&self, path: &Path) -> bool {
+        path.any().is::<SkiaPath>()
+    }
+
+    fn is_supported_drawable(&self, drawable: &dyn Drawable) -> bool {
+        drawable.any().is::<SkiaDrawable>()
+    }
+}
@@ -0,0 +1,202 @@
+use crate::{
+    ClipLayer, Geometry, Layer, Matrix, OffsetLayer, OpacityLayer, Picture, PictureLayer, Point,
+    Shadow, ShadowLayer, TransformationLayer,
+};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayerTreeBuilderError {
+    /// `pop` was called without a matching `push`
+    UnbalancedPop,
+    /// The tree was built while some pushed layers were not popped
+    UnclosedLayers(usize),
+}
+
+impl Display for LayerTreeBuilderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayerTreeBuilderError::UnbalancedPop => write!(f, "pop without a matching push"),
+            LayerTreeBuilderError::UnclosedLayers(count) => {
+                write!(f, "{} pushed layer(s) were not popped", count)
+            }
+        }
+    }
+}
+
+impl Error for LayerTreeBuilderError {}
+
+/// An open container without children, together with the children added so far
+type OpenLayer = (Arc<dyn Layer>, Vec<Arc<dyn Layer>>);
+
+/// Builds a layer tree top-down the same way a canvas is painted: `push_*` opens a container
+/// layer, `add_*` appends a leaf to the innermost open container and `pop` closes it.
+/// The first unbalanced `pop` is remembered and reported by `build`.
+#[derive(Debug, Default)]
+pub struct LayerTreeBuilder {
+    stack: Vec<OpenLayer>,
+    roots: Vec<Arc<dyn Layer>>,
+    error: Option<LayerTreeBuilderError>,
+}
+
+impl LayerTreeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open a given container layer, its existing children are replaced by the ones added until `pop`
+    pub fn push_layer(&mut self, layer: Arc<dyn Layer>) -> &mut Self {
+        self.stack.push((layer, vec![]));
+        self
+    }
+
+    pub fn push_offset(&mut self, offset: Point) -> &mut Self {
+        self.push_layer(Arc::new(OffsetLayer::new_offset(offset)))
+    }
+
+    pub fn push_clip(&mut self, geometry: Geometry, offset: Point) -> &mut Self {
+        self.push_layer(Arc::new(ClipLayer::new(geometry, offset)))
+    }
+
+    pub fn push_opacity(&mut self, alpha: f32) -> &mut Self {
+        self.push_layer(Arc::new(OpacityLayer::new_alpha(alpha)))
+    }
+
+    pub fn push_transform(&mut self, matrix: Matrix) -> &mut Self {
+        self.push_layer(Arc::new(TransformationLayer::new(matrix)))
+    }
+
+    pub fn push_shadow(&mut self, shadow: Shadow) -> &mut Self {
+        self.push_layer(Arc::new(ShadowLayer::new(shadow)))
+    }
+
+    /// Append a layer as is to the innermost open container
+    pub fn add_layer(&mut self, layer: Arc<dyn Layer>) -> &mut Self {
+        match self.stack.last_mut() {
+            None => self.roots.push(layer),
+            Some((_, children)) => children.push(layer),
+        }
+        self
+    }
+
+    pub fn add_picture(&mut self, picture: Arc<dyn Picture>, needs_cache: bool) -> &mut Self {
+        self.add_layer(Arc::new(PictureLayer::new(picture, needs_cache)))
+    }
+
+    /// Close the innermost open container and append it to its parent
+    pub fn pop(&mut self) -> &mut Self {
+        match self.stack.pop() {
+            None => {
+                self.error
+                    .get_or_insert(LayerTreeBuilderError::UnbalancedPop);
+            }
+            Some((container, children)) => {
+                let layer = container.with_layers(children);
+                self.add_layer(layer);
+            }
+        }
+        self
+    }
+
+    /// The amount of containers that are pushed but not yet popped
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Return the only top level layer, or an offset layer holding all of them
+    pub fn build(self) -> Result<Arc<dyn Layer>, LayerTreeBuilderError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        if !self.stack.is_empty() {
+            return Err(LayerTreeBuilderError::UnclosedLayers(self.stack.len()));
+        }
+
+        let mut roots = self.roots;
+        if roots.len() == 1 {
+            Ok(roots.remove(0))
+        } else {
+            Ok(OffsetLayer::new().with_layers(roots))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LayerTreeDump, Rectangle};
+    use std::any::Any;
+
+    #[derive(Debug)]
+    struct TestPicture;
+
+    impl Picture for TestPicture {
+        fn unique_id(&self) -> u32 {
+            1
+        }
+
+        fn cull_rect(&self) -> Rectangle {
+            Rectangle::extent(10.0, 10.0)
+        }
+
+        fn any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    #[test]
+    pub fn nested_tree() {
+        let mut builder = LayerTreeBuilder::new();
+        builder
+            .push_offset(Point::new(10.0, 10.0))
+            .push_opacity(0.5)
+            .add_picture(Arc::new(TestPicture), false)
+            .pop()
+            .add_picture(Arc::new(TestPicture), false)
+            .pop();
+        let root = builder.build().unwrap();
+
+        let dump = LayerTreeDump::new(root.as_ref());
+        let types: Vec<_> = dump.nodes().iter().map(|node| node.layer_type()).collect();
+        assert_eq!(
+            types,
+            vec![
+                "OffsetLayer",
+                "OpacityLayer",
+                "PictureLayer",
+                "PictureLayer"
+            ]
+        );
+        assert_eq!(dump.nodes()[3].parent(), Some(0));
+    }
+
+    #[test]
+    pub fn several_roots_are_wrapped() {
+        let mut builder = LayerTreeBuilder::new();
+        builder
+            .add_picture(Arc::new(TestPicture), false)
+            .add_picture(Arc::new(TestPicture), false);
+        let root = builder.build().unwrap();
+
+        assert!(root.any().is::<OffsetLayer>());
+        assert_eq!(root.count_layers(), 2);
+    }
+
+    #[test]
+    pub fn unbalanced_push_and_pop() {
+        let mut builder = LayerTreeBuilder::new();
+        builder.push_opacity(0.5).pop().pop();
+        assert_eq!(
+            builder.build().unwrap_err(),
+            LayerTreeBuilderError::UnbalancedPop
+        );
+
+        let mut builder = LayerTreeBuilder::new();
+        builder.push_opacity(0.5).push_offset(Point::zero());
+        assert_eq!(
+            builder.build().unwrap_err(),
+            LayerTreeBuilderError::UnclosedLayers(2)
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
-    ClipLayer, DynamicOffsetLayer, ExplicitLayer, Layer, LeftoverStateLayer, OffsetLayer,
-    OpacityLayer, PictureLayer, ShadowLayer, TextureLayer, TiledLayer, TransformationLayer,
+    ClipLayer, DynamicOffsetLayer, ExplicitLayer, GeometryLayer, ImageLayer, Layer,
+    LeftoverStateLayer, ListLayer, OffsetLayer, OpacityLayer, PictureLayer, ShaderLayer,
+    ShadowLayer, TextureLayer, TiledLayer, TransformationLayer,
 };
 use std::fmt::Debug;
 use std::sync::Arc;
@@ -16,6 +17,10 @@ pub trait Compositor: Debug {
     fn compose_picture(&mut self, layer: &PictureLayer);
     fn compose_leftover(&mut self, layer: &LeftoverStateLayer);
     fn compose_tiled(&mut self, layer: &TiledLayer);
+    fn compose_list(&mut self, layer: &ListLayer);
     fn compose_explicit(&mut self, layer: &ExplicitLayer);
     fn compose_texture(&mut self, layer: &TextureLayer);
+    fn compose_image(&mut self, layer: &ImageLayer);
+    fn compose_geometry(&mut self, layer: &GeometryLayer);
+    fn compose_shader(&mut self, layer: &ShaderLayer);
 }
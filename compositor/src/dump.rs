@@ -0,0 +1,316 @@
+use crate::{
+    ClipLayer, Compositor, DynamicOffsetLayer, ExplicitLayer, GeometryLayer, ImageLayer, Layer,
+    LeftoverStateLayer, ListLayer, OffsetLayer, OpacityLayer, PictureLayer, ShaderLayer,
+    ShadowLayer, StateCommandType, TextureLayer, TiledLayer, TransformationLayer,
+};
+use std::fmt::{Display, Formatter, Write};
+use std::sync::Arc;
+
+/// A layer of a dumped tree with its most important properties
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerTreeNode {
+    layer_type: &'static str,
+    depth: usize,
+    /// Index of the parent node in the dump
+    parent: Option<usize>,
+    properties: Vec<(&'static str, String)>,
+}
+
+impl LayerTreeNode {
+    pub fn layer_type(&self) -> &'static str {
+        self.layer_type
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    pub fn properties(&self) -> &[(&'static str, String)] {
+        self.properties.as_slice()
+    }
+
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|(each, _)| *each == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl Display for LayerTreeNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.layer_type)?;
+        for (name, value) in &self.properties {
+            write!(f, " {}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// A flat, depth-first snapshot of a layer tree that can be printed as an indented tree
+/// with one line per layer, or exported to Graphviz DOT
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerTreeDump {
+    nodes: Vec<LayerTreeNode>,
+}
+
+impl LayerTreeDump {
+    pub fn new(layer: &dyn Layer) -> Self {
+        let mut dumper = LayerTreeDumper::default();
+        layer.compose(&mut dumper);
+        Self {
+            nodes: dumper.nodes,
+        }
+    }
+
+    pub fn nodes(&self) -> &[LayerTreeNode] {
+        self.nodes.as_slice()
+    }
+
+    /// Export the tree as a Graphviz digraph, for example to render it with `dot -Tsvg`
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph layers {\n");
+        dot.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            let mut label = node.layer_type.to_string();
+            for (name, value) in &node.properties {
+                write!(label, "\n{}={}", name, value).unwrap();
+            }
+            writeln!(dot, "  n{} [label=\"{}\"];", index, escape_dot(&label)).unwrap();
+        }
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            if let Some(parent) = node.parent {
+                writeln!(dot, "  n{} -> n{};", parent, index).unwrap();
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl Display for LayerTreeDump {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for node in &self.nodes {
+            writeln!(f, "{:indent$}{}", "", node, indent = node.depth * 2)?;
+        }
+        Ok(())
+    }
+}
+
+fn escape_dot(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\l")
+        + "\\l"
+}
+
+#[derive(Debug, Default)]
+struct LayerTreeDumper {
+    nodes: Vec<LayerTreeNode>,
+    parents: Vec<usize>,
+}
+
+impl LayerTreeDumper {
+    fn visit(
+        &mut self,
+        layer_type: &'static str,
+        properties: Vec<(&'static str, String)>,
+        layers: &[Arc<dyn Layer>],
+    ) {
+        let index = self.nodes.len();
+        self.nodes.push(LayerTreeNode {
+            layer_type,
+            depth: self.parents.len(),
+            parent: self.parents.last().copied(),
+            properties,
+        });
+
+        self.parents.push(index);
+        for layer in layers {
+            layer.compose(self);
+        }
+        self.parents.pop();
+    }
+}
+
+impl Compositor for LayerTreeDumper {
+    fn compose(&mut self, layer: Arc<dyn Layer>) {
+        layer.compose(self);
+    }
+
+    fn compose_clip(&mut self, layer: &ClipLayer) {
+        let properties = vec![
+            ("offset", layer.offset().to_string()),
+            ("clip", layer.geometry().to_string()),
+        ];
+        self.visit("ClipLayer", properties, layer.layers());
+    }
+
+    fn compose_offset(&mut self, layer: &OffsetLayer) {
+        let properties = vec![("offset", layer.offset().to_string())];
+        self.visit("OffsetLayer", properties, layer.layers());
+    }
+
+    fn compose_dynamic_offset(&mut self, layer: &DynamicOffsetLayer) {
+        let offset = layer
+            .offset()
+            .map(|offset| offset.to_string())
+            .unwrap_or_else(|| "none".to_string());
+        self.visit(
+            "DynamicOffsetLayer",
+            vec![("offset", offset)],
+            layer.layers(),
+        );
+    }
+
+    fn compose_opacity(&mut self, layer: &OpacityLayer) {
+        let properties = vec![("alpha", layer.alpha().to_string())];
+        self.visit("OpacityLayer", properties, layer.layers());
+    }
+
+    fn compose_shadow(&mut self, layer: &ShadowLayer) {
+        let shadow = layer.shadow();
+        let properties = vec![
+            ("color", shadow.color().to_string()),
+            ("radius", shadow.radius().to_string()),
+            ("offset", shadow.offset().to_string()),
+            ("geometry", shadow.geometry().to_string()),
+        ];
+        self.visit("ShadowLayer", properties, layer.layers());
+    }
+
+    fn compose_transformation(&mut self, layer: &TransformationLayer) {
+        let properties = vec![("matrix", layer.matrix().to_string())];
+        self.visit("TransformationLayer", properties, layer.layers());
+    }
+
+    fn compose_picture(&mut self, layer: &PictureLayer) {
+        let properties = vec![
+            ("id", layer.id().to_string()),
+            ("cull", layer.cull_rect().to_string()),
+            ("cache", layer.cache_hint().to_string()),
+        ];
+        self.visit("PictureLayer", properties, layer.layers());
+    }
+
+    fn compose_leftover(&mut self, layer: &LeftoverStateLayer) {
+        let properties = layer
+            .commands
+            .iter()
+            .map(|command| match &command.command_type {
+                StateCommandType::Transform(matrix) => {
+                    ("transform", format!("{} at {}", matrix, command.offset))
+                }
+                StateCommandType::Clip(geometry) => {
+                    ("clip", format!("{} at {}", geometry, command.offset))
+                }
+            })
+            .collect();
+        self.visit("LeftoverStateLayer", properties, layer.layers());
+    }
+
+    fn compose_tiled(&mut self, layer: &TiledLayer) {
+        let properties = vec![
+            ("camera", layer.camera_position().to_string()),
+            ("viewport", layer.viewport_extent().to_string()),
+            ("tile", layer.tile_extent().to_string()),
+            ("scale", layer.scale_factor().to_string()),
+            ("zoom_level", layer.zoom_level().to_string()),
+            ("figures", layer.figures().len().to_string()),
+            ("cached_tiles", layer.tile_pictures().len().to_string()),
+        ];
+        self.visit("TiledLayer", properties, layer.layers());
+    }
+
+    fn compose_list(&mut self, layer: &ListLayer) {
+        let properties = vec![
+            ("camera", layer.camera_position().to_string()),
+            ("viewport", layer.viewport_extent().to_string()),
+            ("rows", layer.row_count().to_string()),
+        ];
+        self.visit("ListLayer", properties, layer.layers());
+    }
+
+    fn compose_explicit(&mut self, layer: &ExplicitLayer) {
+        self.visit("ExplicitLayer", vec![], layer.layers());
+    }
+
+    fn compose_texture(&mut self, layer: &TextureLayer) {
+        let properties = vec![("size", format!("{} x {}", layer.width(), layer.height()))];
+        self.visit("TextureLayer", properties, layer.layers());
+    }
+
+    fn compose_image(&mut self, layer: &ImageLayer) {
+        let properties = vec![
+            ("id", layer.source().id().to_string()),
+            ("extent", layer.extent().to_string()),
+        ];
+        self.visit("ImageLayer", properties, layer.layers());
+    }
+
+    fn compose_geometry(&mut self, layer: &GeometryLayer) {
+        let mut properties = vec![("geometry", layer.geometry().to_string())];
+        if let Some(stroke) = layer.stroke() {
+            properties.push(("stroke", stroke.width().to_string()));
+        }
+        self.visit("GeometryLayer", properties, layer.layers());
+    }
+
+    fn compose_shader(&mut self, layer: &ShaderLayer) {
+        let properties = vec![
+            ("shader", layer.shader().id().to_string()),
+            ("geometry", layer.geometry().to_string()),
+        ];
+        self.visit("ShaderLayer", properties, layer.layers());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Point, Rectangle};
+
+    fn tree() -> Arc<dyn Layer> {
+        OffsetLayer::new_offset(Point::new(10.0, 20.0)).with_layers(vec![
+            OpacityLayer::new_alpha(0.5).clone_arc(),
+            ClipLayer::new(
+                crate::Geometry::Rectangle(Rectangle::extent(100.0, 50.0)),
+                Point::zero(),
+            )
+            .clone_arc(),
+        ])
+    }
+
+    #[test]
+    pub fn indented_tree() {
+        let dump = LayerTreeDump::new(tree().as_ref()).to_string();
+
+        assert_eq!(
+            dump,
+            "OffsetLayer offset=(10, 20)\n  \
+               OpacityLayer alpha=0.5\n  \
+               ClipLayer offset=(0, 0) clip=rectangle [0, 0, 100 x 50]\n"
+        );
+    }
+
+    #[test]
+    pub fn dot_edges() {
+        let dump = LayerTreeDump::new(tree().as_ref());
+
+        assert_eq!(dump.nodes()[2].parent(), Some(0));
+        assert_eq!(dump.nodes()[1].property("alpha"), Some("0.5"));
+
+        let dot = dump.to_dot();
+        assert!(dot.starts_with("digraph layers {"));
+        assert!(dot.contains("n0 -> n1;"));
+        assert!(dot.contains("n0 -> n2;"));
+    }
+}
@@ -0,0 +1,114 @@
+use crate::{Compositor, Fill, Geometry, Layer, Stroke};
+use std::any::Any;
+use std::sync::Arc;
+
+/// Fills and / or strokes a geometry directly, without recording a picture.
+/// The fill is drawn first and the stroke on top of it.
+#[derive(Debug, Clone)]
+pub struct GeometryLayer {
+    geometry: Geometry,
+    fill: Option<Fill>,
+    stroke: Option<Stroke>,
+}
+
+impl GeometryLayer {
+    pub fn new(geometry: Geometry) -> Self {
+        Self {
+            geometry,
+            fill: None,
+            stroke: None,
+        }
+    }
+
+    pub fn filled(geometry: Geometry, fill: Fill) -> Self {
+        Self::new(geometry).with_fill(fill)
+    }
+
+    pub fn stroked(geometry: Geometry, stroke: Stroke) -> Self {
+        Self::new(geometry).with_stroke(stroke)
+    }
+
+    pub fn with_fill(&self, fill: Fill) -> Self {
+        Self {
+            fill: Some(fill),
+            ..self.clone()
+        }
+    }
+
+    pub fn with_stroke(&self, stroke: Stroke) -> Self {
+        Self {
+            stroke: Some(stroke),
+            ..self.clone()
+        }
+    }
+
+    pub fn geometry(&self) -> &Geometry {
+        &self.geometry
+    }
+
+    pub fn fill(&self) -> Option<&Fill> {
+        self.fill.as_ref()
+    }
+
+    pub fn stroke(&self) -> Option<&Stroke> {
+        self.stroke.as_ref()
+    }
+}
+
+impl Layer for GeometryLayer {
+    fn compose(&self, compositor: &mut dyn Compositor) {
+        compositor.compose_geometry(self);
+    }
+
+    fn layers(&self) -> &[Arc<dyn Layer>] {
+        &[]
+    }
+
+    fn with_layers(&self, _layers: Vec<Arc<dyn Layer>>) -> Arc<dyn Layer> {
+        self.clone_arc()
+    }
+
+    fn clone_arc(&self) -> Arc<dyn Layer> {
+        Arc::new(self.clone())
+    }
+
+    fn any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, GradientStop, Point, Rectangle, StrokeCap};
+
+    #[test]
+    pub fn fill_and_stroke() {
+        let layer = GeometryLayer::filled(
+            Geometry::Rectangle(Rectangle::extent(100.0, 50.0)),
+            Fill::solid(Color::from_argb(0xFF00FF00)),
+        )
+        .with_stroke(
+            Stroke::new(Fill::solid(Color::from_argb(0xFF000000)), 2.0).with_cap(StrokeCap::Round),
+        );
+
+        assert_eq!(
+            layer.fill(),
+            Some(&Fill::Solid(Color::from_argb(0xFF00FF00)))
+        );
+        assert_eq!(layer.stroke().unwrap().cap(), StrokeCap::Round);
+        assert_eq!(layer.stroke().unwrap().width(), 2.0);
+    }
+
+    #[test]
+    pub fn gradient_stops() {
+        let stops = vec![
+            GradientStop::new(0.0, Color::from_argb(0xFFFF0000)),
+            GradientStop::new(1.0, Color::from_argb(0xFF0000FF)),
+        ];
+        let fill = Fill::linear_gradient(Point::new(0.0, 0.0), Point::new(100.0, 0.0), stops);
+
+        assert_eq!(fill.stops().len(), 2);
+        assert_eq!(Fill::solid(Color::default()).stops().len(), 0);
+    }
+}
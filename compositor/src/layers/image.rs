@@ -0,0 +1,271 @@
+use crate::{Color, Compositor, Extent, Layer, Rectangle};
+use std::any::Any;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::{Debug, Formatter};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// Identifies the content of an image source. Two sources with the same id are expected
+/// to decode into the same pixels, which lets the backend share decoded images between layers.
+pub type ImageId = u64;
+
+/// Raw or encoded pixel data of an [`ImageSource`]
+#[derive(Clone)]
+pub enum ImageData {
+    /// Unpremultiplied RGBA pixels, 4 bytes per pixel, rows are tightly packed
+    Rgba {
+        width: u32,
+        height: u32,
+        pixels: Arc<[u8]>,
+    },
+    /// Encoded bytes in any format supported by the backend (PNG, JPEG, WebP)
+    Encoded(Arc<[u8]>),
+}
+
+impl ImageData {
+    pub fn byte_size(&self) -> usize {
+        match self {
+            ImageData::Rgba { pixels, .. } => pixels.len(),
+            ImageData::Encoded(bytes) => bytes.len(),
+        }
+    }
+}
+
+impl Debug for ImageData {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageData::Rgba { width, height, .. } => f
+                .debug_struct("Rgba")
+                .field("width", width)
+                .field("height", height)
+                .finish(),
+            ImageData::Encoded(bytes) => f
+                .debug_struct("Encoded")
+                .field("bytes", &bytes.len())
+                .finish(),
+        }
+    }
+}
+
+/// The source of an [`ImageLayer`]. Cloning is cheap as the pixel data is shared.
+#[derive(Debug, Clone)]
+pub struct ImageSource {
+    id: ImageId,
+    data: ImageData,
+}
+
+impl ImageSource {
+    /// Create an image source from unpremultiplied RGBA pixels.
+    /// The content id is computed by hashing the pixels.
+    pub fn rgba(width: u32, height: u32, pixels: impl Into<Arc<[u8]>>) -> Self {
+        let pixels = pixels.into();
+        assert_eq!(
+            pixels.len(),
+            width as usize * height as usize * 4,
+            "Pixel buffer does not match the image dimensions"
+        );
+
+        let mut hasher = DefaultHasher::new();
+        width.hash(&mut hasher);
+        height.hash(&mut hasher);
+        pixels.hash(&mut hasher);
+
+        Self {
+            id: hasher.finish(),
+            data: ImageData::Rgba {
+                width,
+                height,
+                pixels,
+            },
+        }
+    }
+
+    /// Create an image source from encoded PNG, JPEG or WebP bytes.
+    /// The content id is computed by hashing the bytes.
+    pub fn encoded(bytes: impl Into<Arc<[u8]>>) -> Self {
+        let bytes = bytes.into();
+
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+
+        Self {
+            id: hasher.finish(),
+            data: ImageData::Encoded(bytes),
+        }
+    }
+
+    /// Replace the computed content id with the one known by the host,
+    /// for example when the host already identifies its images
+    pub fn with_id(self, id: ImageId) -> Self {
+        Self {
+            id,
+            data: self.data,
+        }
+    }
+
+    pub fn id(&self) -> ImageId {
+        self.id
+    }
+
+    pub fn data(&self) -> &ImageData {
+        &self.data
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Default)]
+pub enum ImageFilterMode {
+    Nearest,
+    #[default]
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Default)]
+pub enum ImageMipmapMode {
+    /// Sample from the base level only
+    #[default]
+    None,
+    /// Sample from the nearest mipmap level
+    Nearest,
+    /// Interpolate between the two nearest mipmap levels
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Default)]
+pub struct ImageSampling {
+    pub filter: ImageFilterMode,
+    pub mipmap: ImageMipmapMode,
+}
+
+impl ImageSampling {
+    pub fn new(filter: ImageFilterMode, mipmap: ImageMipmapMode) -> Self {
+        Self { filter, mipmap }
+    }
+
+    pub fn needs_mipmaps(&self) -> bool {
+        self.mipmap != ImageMipmapMode::None
+    }
+}
+
+/// Draws an image stretched to a given extent. The image is decoded by the backend
+/// off the compose thread, until then an optional placeholder color is drawn instead.
+#[derive(Debug, Clone)]
+pub struct ImageLayer {
+    source: ImageSource,
+    extent: Extent,
+    center: Option<Rectangle>,
+    sampling: ImageSampling,
+    placeholder: Option<Color>,
+}
+
+impl ImageLayer {
+    pub fn new(source: ImageSource, extent: Extent) -> Self {
+        Self {
+            source,
+            extent,
+            center: None,
+            sampling: ImageSampling::default(),
+            placeholder: None,
+        }
+    }
+
+    /// Create a new image layer that is drawn as a nine-patch.
+    /// The center rectangle is given in image pixels, the corners outside of it are drawn unscaled,
+    /// the edges are stretched in one direction and the center is stretched in both.
+    pub fn with_nine_patch(&self, center: Rectangle) -> Self {
+        Self {
+            center: Some(center),
+            ..self.clone()
+        }
+    }
+
+    pub fn with_sampling(&self, sampling: ImageSampling) -> Self {
+        Self {
+            sampling,
+            ..self.clone()
+        }
+    }
+
+    pub fn with_placeholder(&self, placeholder: Color) -> Self {
+        Self {
+            placeholder: Some(placeholder),
+            ..self.clone()
+        }
+    }
+
+    pub fn source(&self) -> &ImageSource {
+        &self.source
+    }
+
+    pub fn extent(&self) -> &Extent {
+        &self.extent
+    }
+
+    pub fn center(&self) -> Option<&Rectangle> {
+        self.center.as_ref()
+    }
+
+    pub fn sampling(&self) -> &ImageSampling {
+        &self.sampling
+    }
+
+    pub fn placeholder(&self) -> Option<&Color> {
+        self.placeholder.as_ref()
+    }
+}
+
+impl Layer for ImageLayer {
+    fn compose(&self, compositor: &mut dyn Compositor) {
+        compositor.compose_image(self);
+    }
+
+    fn layers(&self) -> &[Arc<dyn Layer>] {
+        &[]
+    }
+
+    fn with_layers(&self, _layers: Vec<Arc<dyn Layer>>) -> Arc<dyn Layer> {
+        self.clone_arc()
+    }
+
+    fn clone_arc(&self) -> Arc<dyn Layer> {
+        Arc::new(self.clone())
+    }
+
+    fn any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn same_content_has_same_id() {
+        let first = ImageSource::rgba(1, 1, vec![255u8, 0, 0, 255]);
+        let second = ImageSource::rgba(1, 1, vec![255u8, 0, 0, 255]);
+        let third = ImageSource::rgba(1, 1, vec![0u8, 255, 0, 255]);
+
+        assert_eq!(first.id(), second.id());
+        assert_ne!(first.id(), third.id());
+    }
+
+    #[test]
+    pub fn dimensions_are_part_of_id() {
+        let wide = ImageSource::rgba(2, 1, vec![0u8; 8]);
+        let tall = ImageSource::rgba(1, 2, vec![0u8; 8]);
+
+        assert_ne!(wide.id(), tall.id());
+    }
+
+    #[test]
+    pub fn explicit_id() {
+        let source = ImageSource::encoded(vec![1u8, 2, 3]).with_id(42);
+        assert_eq!(source.id(), 42);
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn pixels_must_match_dimensions() {
+        ImageSource::rgba(2, 2, vec![0u8; 4]);
+    }
+}
@@ -0,0 +1,547 @@
+use std::any::Any;
+use std::ops::{Deref, Range};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use parking_lot::RwLock;
+
+use crate::{Compositor, Extent, Layer, OffsetLayer, PictureLayer, Point, Scalar};
+
+pub type ListRowIndex = u32;
+
+/// A virtualized list of rows with variable heights, stacked vertically starting at the top.
+/// Only rows that intersect the viewport are composed, which makes it suitable for lists
+/// with hundreds of thousands of rows.
+#[derive(Debug, Clone)]
+pub struct ListLayer {
+    /// row picture slots together with the cumulative height index
+    rows: Arc<RwLock<ListLayerRows>>,
+    camera_position: Point,
+    viewport_extent: Extent,
+}
+
+impl Default for ListLayer {
+    fn default() -> Self {
+        Self::new(Point::zero(), Extent::new(600.0, 400.0))
+    }
+}
+
+impl ListLayer {
+    pub fn new(camera_position: Point, viewport_extent: Extent) -> Self {
+        Self {
+            rows: Arc::new(Default::default()),
+            camera_position,
+            viewport_extent,
+        }
+    }
+
+    pub fn with_camera_position(&self, camera_position: Point) -> Self {
+        let mut layer = self.clone();
+        layer.camera_position = camera_position;
+        layer
+    }
+
+    /// Append a row with a given height and return its index
+    pub fn add_row(&self, height: f32) -> ListRowIndex {
+        self.rows.write().push(height)
+    }
+
+    /// Append rows with given heights
+    pub fn add_rows(&self, heights: impl IntoIterator<Item = f32>) {
+        let mut rows = self.rows.write();
+        for height in heights {
+            rows.push(height);
+        }
+    }
+
+    /// Change the height of an existing row, shifting all rows below it
+    pub fn set_row_height(&self, index: ListRowIndex, height: f32) {
+        self.rows.write().set_height(index, height);
+    }
+
+    pub fn row_height(&self, index: ListRowIndex) -> Option<Scalar> {
+        self.rows.read().height(index)
+    }
+
+    /// Top coordinate of a row in list coordinates
+    pub fn row_top(&self, index: ListRowIndex) -> Option<Scalar> {
+        self.rows.read().top(index)
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.rows.read().len()
+    }
+
+    /// Sum of the heights of all rows
+    pub fn total_height(&self) -> Scalar {
+        self.rows.read().total_height()
+    }
+
+    /// Find the index of a row that contains a given vertical coordinate
+    pub fn row_at(&self, y: impl Into<Scalar>) -> Option<ListRowIndex> {
+        self.rows.read().row_at(y.into())
+    }
+
+    /// Return a range of rows that overlap a given vertical span
+    pub fn rows_in_range(
+        &self,
+        top: impl Into<Scalar>,
+        bottom: impl Into<Scalar>,
+    ) -> Range<ListRowIndex> {
+        self.rows.read().rows_in_range(top.into(), bottom.into())
+    }
+
+    /// The amount of rows that have a picture, maintained as row pictures are set
+    pub fn count_row_pictures(&self) -> usize {
+        self.rows.read().pictures.count.load(Ordering::Relaxed)
+    }
+
+    /// Bytes retained by the pictures of all rows, maintained as row pictures are set.
+    /// A picture shared by several rows is counted for every row
+    pub fn row_picture_bytes(&self) -> usize {
+        self.rows.read().pictures.bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn find_row(&self, index: ListRowIndex) -> Option<ListLayerRow> {
+        self.rows.read().row(index)
+    }
+
+    pub fn camera_position(&self) -> &Point {
+        &self.camera_position
+    }
+
+    pub fn viewport_extent(&self) -> &Extent {
+        &self.viewport_extent
+    }
+
+    /// Left coordinate of the viewport which depends on the camera position and viewport size
+    pub fn viewport_left(&self) -> Scalar {
+        self.camera_position.x() - (self.viewport_extent.width() / 2.0)
+    }
+
+    /// Top coordinate of the viewport which depends on the camera position and viewport size
+    pub fn viewport_top(&self) -> Scalar {
+        self.camera_position.y() - (self.viewport_extent.height() / 2.0)
+    }
+
+    pub fn viewport_width(&self) -> Scalar {
+        self.viewport_extent.width()
+    }
+
+    /// Right coordinate of the viewport which depends on the camera position and viewport size
+    pub fn viewport_right(&self) -> Scalar {
+        self.camera_position.x() + (self.viewport_extent.width() / 2.0)
+    }
+
+    /// Bottom coordinate of the viewport which depends on the camera position and viewport size
+    pub fn viewport_bottom(&self) -> Scalar {
+        self.camera_position.y() + (self.viewport_extent.height() / 2.0)
+    }
+
+    pub fn viewport_height(&self) -> Scalar {
+        self.viewport_extent.height()
+    }
+
+    pub fn canvas_offset(&self) -> Point {
+        Into::<Point>::into(self.viewport_extent / 2.0) - self.camera_position
+    }
+
+    /// Return a range of rows that intersect the viewport
+    pub fn visible_rows(&self) -> Range<ListRowIndex> {
+        self.rows_in_range(self.viewport_top(), self.viewport_bottom())
+    }
+
+    /// Find and return IDs of all visible rows that don't have a picture
+    pub fn visible_rows_without_picture(&self) -> Vec<ListRowIndex> {
+        let rows = self.rows.read();
+        rows.rows_in_range(self.viewport_top(), self.viewport_bottom())
+            .filter(|index| {
+                rows.row(*index)
+                    .map(|row| !row.has_picture())
+                    .unwrap_or(false)
+            })
+            .collect()
+    }
+
+    /// Return pictures of the visible rows, each wrapped in an offset layer positioned at the top of its row
+    pub fn visible_row_pictures(&self) -> Vec<OffsetLayer> {
+        let rows = self.rows.read();
+        rows.rows_in_range(self.viewport_top(), self.viewport_bottom())
+            .filter_map(|index| {
+                let top = rows.top(index)?;
+                rows.row(index)?
+                    .get_picture()
+                    .map(|picture| OffsetLayer::wrap_with_offset(picture, Point::new(0.0, top)))
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Default)]
+struct ListLayerRows {
+    rows: Vec<ListLayerRow>,
+    heights: ListLayerHeightIndex,
+    /// shared with the rows, which update it when their picture changes
+    pictures: Arc<ListLayerRowPictures>,
+}
+
+/// The amount and the size of row pictures, so that statistics don't visit every row
+#[derive(Debug, Default)]
+struct ListLayerRowPictures {
+    count: AtomicUsize,
+    bytes: AtomicUsize,
+}
+
+impl ListLayerRowPictures {
+    fn replace(&self, old_picture: Option<&PictureLayer>, new_picture: &PictureLayer) {
+        match old_picture {
+            None => {
+                self.count.fetch_add(1, Ordering::Relaxed);
+            }
+            Some(old_picture) => {
+                self.bytes.fetch_sub(
+                    old_picture.picture().approximate_bytes_used(),
+                    Ordering::Relaxed,
+                );
+            }
+        }
+        self.bytes.fetch_add(
+            new_picture.picture().approximate_bytes_used(),
+            Ordering::Relaxed,
+        );
+    }
+}
+
+impl ListLayerRows {
+    fn push(&mut self, height: f32) -> ListRowIndex {
+        let index = self.rows.len() as ListRowIndex;
+        self.rows
+            .push(ListLayerRow::new(index, self.pictures.clone()));
+        self.heights.push(height);
+        index
+    }
+
+    fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    fn row(&self, index: ListRowIndex) -> Option<ListLayerRow> {
+        self.rows.get(index as usize).cloned()
+    }
+
+    fn set_height(&mut self, index: ListRowIndex, height: f32) {
+        if (index as usize) < self.heights.len() {
+            self.heights.set(index as usize, height);
+        }
+    }
+
+    fn height(&self, index: ListRowIndex) -> Option<Scalar> {
+        self.heights.get(index as usize).map(Scalar::from)
+    }
+
+    fn top(&self, index: ListRowIndex) -> Option<Scalar> {
+        if (index as usize) < self.heights.len() {
+            Some(Scalar::from(self.heights.prefix_sum(index as usize) as f32))
+        } else {
+            None
+        }
+    }
+
+    fn total_height(&self) -> Scalar {
+        Scalar::from(self.heights.total() as f32)
+    }
+
+    fn row_at(&self, y: Scalar) -> Option<ListRowIndex> {
+        let y = y.0 as f64;
+        if y < 0.0 || y >= self.heights.total() {
+            return None;
+        }
+        Some(self.heights.rows_above(y) as ListRowIndex)
+    }
+
+    fn rows_in_range(&self, top: Scalar, bottom: Scalar) -> Range<ListRowIndex> {
+        let top = (top.0 as f64).max(0.0);
+        let bottom = bottom.0 as f64;
+        if bottom <= top || top >= self.heights.total() {
+            return 0..0;
+        }
+
+        let start = self.heights.rows_above(top);
+        let end = (self.heights.rows_above(bottom) + 1).min(self.heights.len());
+        (start as ListRowIndex)..(end as ListRowIndex)
+    }
+}
+
+/// A cumulative height index (Fenwick tree) that supports changing the height
+/// of any row and finding a row at a given coordinate in logarithmic time.
+#[derive(Debug, Default, Clone)]
+struct ListLayerHeightIndex {
+    heights: Vec<f32>,
+    /// 1-based partial sums, the first element is unused
+    tree: Vec<f64>,
+}
+
+impl ListLayerHeightIndex {
+    fn len(&self) -> usize {
+        self.heights.len()
+    }
+
+    fn get(&self, row: usize) -> Option<f32> {
+        self.heights.get(row).copied()
+    }
+
+    fn push(&mut self, height: f32) {
+        let height = Self::sanitize(height);
+        if self.tree.is_empty() {
+            self.tree.push(0.0);
+        }
+        let node = self.heights.len() + 1;
+        let span = node & node.wrapping_neg();
+        let sum = self.prefix_sum(node - 1) - self.prefix_sum(node - span) + height as f64;
+
+        self.heights.push(height);
+        self.tree.push(sum);
+    }
+
+    fn set(&mut self, row: usize, height: f32) {
+        let height = Self::sanitize(height);
+        let delta = height as f64 - self.heights[row] as f64;
+        self.heights[row] = height;
+
+        let mut node = row + 1;
+        while node < self.tree.len() {
+            self.tree[node] += delta;
+            node += node & node.wrapping_neg();
+        }
+    }
+
+    /// Negative and non-finite heights would corrupt the partial sums and the
+    /// binary search over them, such rows are treated as collapsed
+    fn sanitize(height: f32) -> f32 {
+        if height.is_finite() {
+            height.max(0.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// Sum of the heights of the first `count` rows
+    fn prefix_sum(&self, count: usize) -> f64 {
+        let mut node = count;
+        let mut sum = 0.0;
+        while node > 0 {
+            sum += self.tree[node];
+            node -= node & node.wrapping_neg();
+        }
+        sum
+    }
+
+    fn total(&self) -> f64 {
+        self.prefix_sum(self.heights.len())
+    }
+
+    /// Return the amount of rows that end at or above a given coordinate,
+    /// which is the index of the row containing that coordinate
+    fn rows_above(&self, y: f64) -> usize {
+        let length = self.heights.len();
+        if length == 0 {
+            return 0;
+        }
+
+        let mut position = 0;
+        let mut remaining = y;
+        let mut step = 1usize << (usize::BITS - 1 - length.leading_zeros());
+        while step > 0 {
+            let next = position + step;
+            if next <= length && self.tree[next] <= remaining {
+                position = next;
+                remaining -= self.tree[next];
+            }
+            step >>= 1;
+        }
+        position
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ListLayerRow(Arc<ListLayerRowData>);
+
+#[derive(Debug)]
+struct ListLayerRowData {
+    index: ListRowIndex,
+    picture: RwLock<Option<PictureLayer>>,
+    pictures: Arc<ListLayerRowPictures>,
+}
+
+impl ListLayerRow {
+    fn new(index: ListRowIndex, pictures: Arc<ListLayerRowPictures>) -> Self {
+        Self(Arc::new(ListLayerRowData {
+            index,
+            picture: Default::default(),
+            pictures,
+        }))
+    }
+
+    pub fn index(&self) -> ListRowIndex {
+        self.0.index
+    }
+
+    pub fn has_picture(&self) -> bool {
+        self.0.picture.read().is_some()
+    }
+
+    pub fn get_picture(&self) -> Option<PictureLayer> {
+        self.0.picture.read().deref().clone()
+    }
+
+    pub fn set_picture(&self, picture: PictureLayer) {
+        let mut current_picture = self.0.picture.write();
+        self.0.pictures.replace(current_picture.as_ref(), &picture);
+        let _ = current_picture.insert(picture);
+    }
+
+    pub fn with_picture(self, picture: PictureLayer) -> Self {
+        self.set_picture(picture);
+        self
+    }
+}
+
+impl Layer for ListLayer {
+    fn compose(&self, compositor: &mut dyn Compositor) {
+        compositor.compose_list(self)
+    }
+
+    fn layers(&self) -> &[Arc<dyn Layer>] {
+        &[]
+    }
+
+    fn with_layers(&self, _layers: Vec<Arc<dyn Layer>>) -> Arc<dyn Layer> {
+        self.clone_arc()
+    }
+
+    fn clone_arc(&self) -> Arc<dyn Layer> {
+        Arc::new(self.clone())
+    }
+
+    fn any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list_layer(heights: &[f32]) -> ListLayer {
+        let layer = ListLayer::new(Point::new_f32(50.0, 50.0), Extent::new(100.0, 100.0));
+        layer.add_rows(heights.iter().copied());
+        layer
+    }
+
+    #[test]
+    pub fn test_row_at() {
+        let layer = list_layer(&[10.0, 20.0, 30.0]);
+
+        assert_eq!(layer.total_height(), Scalar::from(60.0));
+        assert_eq!(layer.row_at(0.0), Some(0));
+        assert_eq!(layer.row_at(9.5), Some(0));
+        assert_eq!(layer.row_at(10.0), Some(1));
+        assert_eq!(layer.row_at(29.9), Some(1));
+        assert_eq!(layer.row_at(30.0), Some(2));
+        assert_eq!(layer.row_at(60.0), None);
+        assert_eq!(layer.row_at(-1.0), None);
+    }
+
+    #[test]
+    pub fn test_set_row_height() {
+        let layer = list_layer(&[10.0, 20.0, 30.0, 40.0, 50.0]);
+        layer.set_row_height(1, 5.0);
+
+        assert_eq!(layer.row_top(2), Some(Scalar::from(15.0)));
+        assert_eq!(layer.row_top(4), Some(Scalar::from(85.0)));
+        assert_eq!(layer.total_height(), Scalar::from(135.0));
+        assert_eq!(layer.row_at(15.0), Some(2));
+    }
+
+    #[test]
+    pub fn test_invalid_row_heights() {
+        let layer = list_layer(&[10.0, f32::NAN, -20.0, f32::INFINITY, 30.0]);
+
+        assert_eq!(layer.row_height(1), Some(Scalar::from(0.0)));
+        assert_eq!(layer.row_height(2), Some(Scalar::from(0.0)));
+        assert_eq!(layer.row_height(3), Some(Scalar::from(0.0)));
+        assert_eq!(layer.total_height(), Scalar::from(40.0));
+        assert_eq!(layer.row_at(10.0), Some(4));
+
+        layer.set_row_height(4, -5.0);
+        assert_eq!(layer.total_height(), Scalar::from(10.0));
+        layer.set_row_height(0, f32::NAN);
+        assert_eq!(layer.total_height(), Scalar::from(0.0));
+        assert_eq!(layer.row_at(0.0), None);
+    }
+
+    #[test]
+    pub fn test_visible_rows() {
+        let layer = list_layer(&[40.0; 1000]);
+
+        assert_eq!(layer.visible_rows(), 0..3);
+        assert_eq!(
+            layer
+                .with_camera_position(Point::new_f32(50.0, 4050.0))
+                .visible_rows(),
+            100..103
+        );
+        assert_eq!(
+            layer
+                .with_camera_position(Point::new_f32(50.0, 50000.0))
+                .visible_rows(),
+            0..0
+        );
+    }
+
+    #[test]
+    pub fn test_visible_rows_without_picture() {
+        let layer = list_layer(&[40.0; 10]);
+        layer
+            .find_row(1)
+            .unwrap()
+            .set_picture(PictureLayer::new(Arc::new(TestPicture), false));
+
+        assert_eq!(layer.visible_rows_without_picture(), vec![0, 2]);
+        assert_eq!(layer.visible_row_pictures().len(), 1);
+    }
+
+    #[test]
+    pub fn test_row_picture_bytes() {
+        let layer = list_layer(&[40.0; 10]);
+        let picture = || PictureLayer::new(Arc::new(TestPicture), false);
+
+        layer.find_row(1).unwrap().set_picture(picture());
+        layer.find_row(7).unwrap().set_picture(picture());
+        layer.find_row(7).unwrap().set_picture(picture());
+
+        assert_eq!(layer.count_row_pictures(), 2);
+        assert_eq!(layer.row_picture_bytes(), 2 * 32);
+    }
+
+    #[derive(Debug)]
+    struct TestPicture;
+
+    impl crate::Picture for TestPicture {
+        fn unique_id(&self) -> u32 {
+            1
+        }
+
+        fn cull_rect(&self) -> crate::Rectangle {
+            crate::Rectangle::extent(100.0, 40.0)
+        }
+
+        fn approximate_bytes_used(&self) -> usize {
+            32
+        }
+
+        fn any(&self) -> &dyn Any {
+            self
+        }
+    }
+}
@@ -1,24 +1,37 @@
 pub use clip::ClipLayer;
 pub use explicit::ExplicitLayer;
+pub use geometry::GeometryLayer;
+pub use image::{
+    ImageData, ImageFilterMode, ImageId, ImageLayer, ImageMipmapMode, ImageSampling, ImageSource,
+};
 pub use layer::Layer;
 pub use leftover_state::{LeftoverStateLayer, StateCommand, StateCommandType};
+pub use list::{ListLayer, ListLayerRow, ListRowIndex};
 pub use offset::OffsetLayer;
 pub use offset_dynamic::*;
 pub use opacity::OpacityLayer;
-pub use picture::{Picture, PictureLayer};
+pub use picture::{Picture, PictureCacheHint, PictureLayer};
+pub use shader::{RuntimeShader, ShaderId, ShaderLayer, ShaderUniform};
 pub use shadow::{Shadow, ShadowLayer};
 pub use texture::*;
-pub use tiled::{TiledFigureId, TiledLayer, TiledLayerFigure, TiledLayerScaleFactor};
+pub use tiled::{
+    TiledFigureId, TiledLayer, TiledLayerFigure, TiledLayerScaleFactor, TiledLayerTile,
+    TiledLayerTileStatistics, TiledLayerTransaction,
+};
 pub use transformation::TransformationLayer;
 
 mod clip;
 mod explicit;
+mod geometry;
+mod image;
 mod layer;
 mod leftover_state;
+mod list;
 mod offset;
 mod offset_dynamic;
 mod opacity;
 mod picture;
+mod shader;
 mod shadow;
 mod texture;
 mod tiled;
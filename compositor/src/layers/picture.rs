@@ -1,23 +1,52 @@
 use crate::{Compositor, Layer, Rectangle};
 use std::any::Any;
-use std::fmt::Debug;
+use std::fmt::{Debug, Display, Formatter};
 use std::sync::Arc;
 
+/// Tells the compositor whether a picture should be rasterized and cached as an image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PictureCacheHint {
+    /// Let the compositor decide based on how often, and under which transformation,
+    /// the picture is drawn. Compositors without such heuristics do not cache it
+    Auto,
+    Always,
+    Never,
+}
+
+impl Display for PictureCacheHint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PictureCacheHint::Auto => write!(f, "auto"),
+            PictureCacheHint::Always => write!(f, "always"),
+            PictureCacheHint::Never => write!(f, "never"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PictureLayer {
     picture: Arc<dyn Picture>,
     picture_id: u32,
-    needs_cache: bool,
+    cache_hint: PictureCacheHint,
 }
 
 impl PictureLayer {
     pub fn new(picture: Arc<dyn Picture>, needs_cache: bool) -> Self {
+        let cache_hint = if needs_cache {
+            PictureCacheHint::Always
+        } else {
+            PictureCacheHint::Never
+        };
+        Self::new_with_cache_hint(picture, cache_hint)
+    }
+
+    pub fn new_with_cache_hint(picture: Arc<dyn Picture>, cache_hint: PictureCacheHint) -> Self {
         let id = picture.unique_id();
 
         Self {
             picture,
             picture_id: id,
-            needs_cache,
+            cache_hint,
         }
     }
 
@@ -29,8 +58,13 @@ impl PictureLayer {
         self.picture.clone()
     }
 
+    /// True if the picture must always be cached, see `cache_hint` for pictures left to the compositor
     pub fn needs_cache(&self) -> bool {
-        self.needs_cache
+        self.cache_hint == PictureCacheHint::Always
+    }
+
+    pub fn cache_hint(&self) -> PictureCacheHint {
+        self.cache_hint
     }
 
     pub fn cull_rect(&self) -> Rectangle {
@@ -63,5 +97,13 @@ impl Layer for PictureLayer {
 pub trait Picture: Send + Sync + Debug {
     fn unique_id(&self) -> u32;
     fn cull_rect(&self) -> Rectangle;
+    /// An estimate of the memory retained by the picture, in bytes
+    fn approximate_bytes_used(&self) -> usize {
+        0
+    }
+    /// An estimate of the amount of drawing operations, or zero if unknown
+    fn approximate_op_count(&self) -> usize {
+        0
+    }
     fn any(&self) -> &dyn Any;
 }
@@ -0,0 +1,213 @@
+use crate::{Compositor, Geometry, ImageSource, Layer};
+use std::any::Any;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// Identifies the SkSL source of a runtime shader, computed by hashing the source
+pub type ShaderId = u64;
+
+/// The SkSL source of a runtime shader. The backend compiles every distinct source once
+/// and caches the result by its id.
+#[derive(Debug, Clone)]
+pub struct RuntimeShader {
+    id: ShaderId,
+    source: Arc<str>,
+}
+
+impl RuntimeShader {
+    pub fn new(source: impl Into<Arc<str>>) -> Self {
+        let source = source.into();
+
+        let mut hasher = DefaultHasher::new();
+        source.hash(&mut hasher);
+
+        Self {
+            id: hasher.finish(),
+            source,
+        }
+    }
+
+    pub fn id(&self) -> ShaderId {
+        self.id
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+}
+
+/// The value of a uniform declared in the shader source.
+/// Matrices are given in column-major order, the same way SkSL stores them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShaderUniform {
+    Float(f32),
+    Float2([f32; 2]),
+    Float3([f32; 3]),
+    Float4([f32; 4]),
+    Float2x2([f32; 4]),
+    Float3x3([f32; 9]),
+    Float4x4([f32; 16]),
+}
+
+impl ShaderUniform {
+    pub fn as_slice(&self) -> &[f32] {
+        match self {
+            ShaderUniform::Float(value) => std::slice::from_ref(value),
+            ShaderUniform::Float2(values) => values,
+            ShaderUniform::Float3(values) => values,
+            ShaderUniform::Float4(values) => values,
+            ShaderUniform::Float2x2(values) => values,
+            ShaderUniform::Float3x3(values) => values,
+            ShaderUniform::Float4x4(values) => values,
+        }
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        size_of_val(self.as_slice())
+    }
+}
+
+/// Fills a geometry with a runtime shader. Uniforms and child images are bound by the names
+/// used in the shader source. The shader is evaluated in the local coordinates of the layer.
+#[derive(Debug, Clone)]
+pub struct ShaderLayer {
+    shader: RuntimeShader,
+    geometry: Geometry,
+    uniforms: Vec<(String, ShaderUniform)>,
+    children: Vec<(String, ImageSource)>,
+}
+
+impl ShaderLayer {
+    pub fn new(shader: RuntimeShader, geometry: Geometry) -> Self {
+        Self {
+            shader,
+            geometry,
+            uniforms: vec![],
+            children: vec![],
+        }
+    }
+
+    /// Create a new shader layer with a given uniform value, replacing a previous one with the same name
+    pub fn with_uniform(&self, name: impl Into<String>, value: ShaderUniform) -> Self {
+        let name = name.into();
+        let mut uniforms = self.uniforms.clone();
+        match uniforms.iter_mut().find(|(each, _)| *each == name) {
+            None => uniforms.push((name, value)),
+            Some((_, existing)) => *existing = value,
+        }
+
+        Self {
+            uniforms,
+            ..self.clone()
+        }
+    }
+
+    /// Create a new shader layer binding an image to a child shader declared in the source,
+    /// replacing a previous one with the same name
+    pub fn with_child(&self, name: impl Into<String>, image: ImageSource) -> Self {
+        let name = name.into();
+        let mut children = self.children.clone();
+        match children.iter_mut().find(|(each, _)| *each == name) {
+            None => children.push((name, image)),
+            Some((_, existing)) => *existing = image,
+        }
+
+        Self {
+            children,
+            ..self.clone()
+        }
+    }
+
+    pub fn shader(&self) -> &RuntimeShader {
+        &self.shader
+    }
+
+    pub fn geometry(&self) -> &Geometry {
+        &self.geometry
+    }
+
+    pub fn uniforms(&self) -> &[(String, ShaderUniform)] {
+        self.uniforms.as_slice()
+    }
+
+    pub fn uniform(&self, name: &str) -> Option<&ShaderUniform> {
+        self.uniforms
+            .iter()
+            .find(|(each, _)| each == name)
+            .map(|(_, value)| value)
+    }
+
+    pub fn children(&self) -> &[(String, ImageSource)] {
+        self.children.as_slice()
+    }
+
+    pub fn child(&self, name: &str) -> Option<&ImageSource> {
+        self.children
+            .iter()
+            .find(|(each, _)| each == name)
+            .map(|(_, image)| image)
+    }
+}
+
+impl Layer for ShaderLayer {
+    fn compose(&self, compositor: &mut dyn Compositor) {
+        compositor.compose_shader(self);
+    }
+
+    fn layers(&self) -> &[Arc<dyn Layer>] {
+        &[]
+    }
+
+    fn with_layers(&self, _layers: Vec<Arc<dyn Layer>>) -> Arc<dyn Layer> {
+        self.clone_arc()
+    }
+
+    fn clone_arc(&self) -> Arc<dyn Layer> {
+        Arc::new(self.clone())
+    }
+
+    fn any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rectangle;
+
+    const SOURCE: &str = "uniform float time; half4 main(float2 p) { return half4(time); }";
+
+    #[test]
+    pub fn same_source_has_same_id() {
+        assert_eq!(
+            RuntimeShader::new(SOURCE).id(),
+            RuntimeShader::new(SOURCE).id()
+        );
+        assert_ne!(
+            RuntimeShader::new(SOURCE).id(),
+            RuntimeShader::new("half4 main(float2 p) { return half4(1); }").id()
+        );
+    }
+
+    #[test]
+    pub fn uniform_is_replaced() {
+        let layer = ShaderLayer::new(
+            RuntimeShader::new(SOURCE),
+            Geometry::Rectangle(Rectangle::extent(10.0, 10.0)),
+        )
+        .with_uniform("time", ShaderUniform::Float(1.0))
+        .with_uniform("time", ShaderUniform::Float(2.0));
+
+        assert_eq!(layer.uniforms().len(), 1);
+        assert_eq!(layer.uniform("time"), Some(&ShaderUniform::Float(2.0)));
+    }
+
+    #[test]
+    pub fn uniform_sizes() {
+        assert_eq!(ShaderUniform::Float(0.0).size_in_bytes(), 4);
+        assert_eq!(ShaderUniform::Float3([0.0; 3]).size_in_bytes(), 12);
+        assert_eq!(ShaderUniform::Float4x4([0.0; 16]).size_in_bytes(), 64);
+    }
+}
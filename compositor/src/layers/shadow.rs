@@ -50,7 +50,9 @@ impl Layer for ShadowLayer {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+/// Shadows are compared including the position of their geometry,
+/// but hashed without it so that normalized shadows share hashes with the original ones
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
 pub struct Shadow {
     color: Color,
     radius: Radius,
@@ -88,12 +90,51 @@ impl Shadow {
         &self.geometry
     }
 
+    /// Hash everything but the absolute position of the geometry
     pub fn compute_default_hash(&self) -> u64 {
         let mut hasher = DefaultHasher::new();
-        self.hash(&mut hasher);
+        self.color.hash(&mut hasher);
+        self.radius.hash(&mut hasher);
+        self.offset.hash(&mut hasher);
+        self.geometry
+            .translate(&-self.pixel_origin())
+            .unwrap_or_else(|| self.geometry.clone())
+            .hash(&mut hasher);
         hasher.finish()
     }
 
+    /// The whole-pixel part of the geometry's origin
+    pub fn pixel_origin(&self) -> Point {
+        let bounds = self.geometry.bounds();
+        Point::new_f32(bounds.left().0.floor(), bounds.top().0.floor())
+    }
+
+    /// Return the same shadow with its geometry moved by a given offset,
+    /// or `None` if the geometry can not be translated
+    pub fn translate(&self, offset: &Point) -> Option<Self> {
+        Some(Self::new(
+            self.color.clone(),
+            self.radius.clone(),
+            self.offset,
+            self.geometry.translate(offset)?,
+        ))
+    }
+
+    /// Return the shadow with its geometry moved to the pixel origin together with the translation
+    /// to draw it at. Shadows that only differ in their position are rasterized into one image.
+    /// The fraction of the origin is kept to preserve the anti-aliasing of unscaled shadows.
+    /// Shadows whose geometry can not be translated are returned as is with a zero translation
+    pub fn normalize(&self) -> (Self, Point) {
+        let origin = self.pixel_origin();
+        if origin == Point::zero() {
+            return (self.clone(), origin);
+        }
+        match self.translate(&-origin) {
+            Some(normalized) => (normalized, origin),
+            None => (self.clone(), Point::zero()),
+        }
+    }
+
     pub fn inflation_offset(&self) -> Point {
         let inflation_x = self.radius.width() * 3.0;
         let inflation_y = self.radius.height() * 3.0;
@@ -115,9 +156,16 @@ impl Shadow {
     }
 }
 
+impl Hash for Shadow {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash.unwrap_or_else(|| self.compute_default_hash()));
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{Path, VectorPath};
 
     #[test]
     pub fn test_shadow_equals() {
@@ -151,4 +199,64 @@ mod tests {
             Rectangle::new(200.0, 100.0, 420.0, 260.0)
         );
     }
+
+    #[test]
+    pub fn test_shadow_normalize() {
+        let shadow = |left: f32, top: f32| {
+            Shadow::new(
+                Color::from_argb(0),
+                Radius::new(5.0, 5.0),
+                Point::new_f32(0.0, 1.0),
+                Geometry::Rectangle(Rectangle::new(left, top, 300.0, 200.0)),
+            )
+        };
+
+        let (normalized, origin) = shadow(40.5, 120.0).normalize();
+        assert_eq!(origin, Point::new_f32(40.0, 120.0));
+        assert_eq!(normalized, shadow(0.5, 0.0));
+        assert_eq!(normalized, shadow(10.5, 300.0).normalize().0);
+        assert_ne!(normalized, shadow(10.0, 300.0).normalize().0);
+        assert_eq!(
+            shadow(40.5, 120.0).compute_default_hash(),
+            normalized.compute_default_hash()
+        );
+    }
+
+    #[test]
+    pub fn test_shadow_normalize_untranslatable_path() {
+        let shadow = Shadow::new(
+            Color::from_argb(0),
+            Radius::new(5.0, 5.0),
+            Point::new_f32(0.0, 1.0),
+            Geometry::Path(Path::new(Box::new(FixedPath))),
+        );
+
+        let (normalized, origin) = shadow.normalize();
+        assert_eq!(origin, Point::zero());
+        assert_eq!(normalized, shadow);
+    }
+
+    /// A path that does not implement `VectorPath::translate`
+    #[derive(Debug, Clone)]
+    struct FixedPath;
+
+    impl VectorPath for FixedPath {
+        fn bounds(&self) -> Rectangle {
+            Rectangle::new(40.5, 120.0, 300.0, 200.0)
+        }
+
+        fn clone_box(&self) -> Box<dyn VectorPath> {
+            Box::new(self.clone())
+        }
+
+        fn eq_box(&self, other: &Box<dyn VectorPath>) -> bool {
+            other.any().is::<FixedPath>()
+        }
+
+        fn hash_box(&self, _state: &mut DefaultHasher) {}
+
+        fn any(&self) -> &dyn Any {
+            self
+        }
+    }
 }
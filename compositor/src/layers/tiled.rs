@@ -2,14 +2,16 @@ use fraction::{Fraction, ToPrimitive};
 use std::any::Any;
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
-use std::ops::Deref;
+use std::ops::{Deref, RangeInclusive};
 use std::rc::Rc;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 
 use parking_lot::RwLock;
-use rstar::{ParentNode, RTree, RTreeObject, AABB};
+use rstar::{Envelope, ParentNode, RTree, RTreeObject, AABB};
 
-use crate::{Compositor, Extent, Layer, OffsetLayer, PictureLayer, Point, Scalar};
+use crate::{
+    Color, Compositor, Extent, Layer, OffsetLayer, PictureLayer, Point, Rectangle, Scalar,
+};
 
 pub type RowIndex = i32;
 pub type ColumnIndex = i32;
@@ -22,13 +24,25 @@ pub struct TiledLayer {
     /// optimized for finding figures based on their id
     figures_hash: Arc<RwLock<HashMap<TiledFigureId, TiledLayerFigure>>>,
     camera_position: Point,
+    /// how much the camera moved with the last position update
+    camera_velocity: Point,
     viewport_extent: Extent,
     tile_extent: Extent,
-    tile_pictures: Arc<RwLock<HashMap<(ColumnIndex, RowIndex), PictureLayer>>>,
+    tile_pictures: Arc<TiledLayerTilePictures>,
     scale_factor: TiledLayerScaleFactor,
+    tile_recording_budget: Option<usize>,
+    prefetch_margin: u32,
+    tile_placeholder: Option<Color>,
     debug_mode: bool,
 }
 
+/// The amount of missing tiles recorded per frame while scaled tiles of other zoom levels
+/// can be drawn in their place
+const DEFAULT_TILE_RECORDING_BUDGET: usize = 4;
+
+/// The amount of tiles around the viewport that are prefetched by default
+const DEFAULT_PREFETCH_MARGIN: u32 = 1;
+
 impl Default for TiledLayer {
     fn default() -> Self {
         Self::new(
@@ -45,29 +59,145 @@ impl TiledLayer {
             figures: Default::default(),
             figures_hash: Arc::new(Default::default()),
             camera_position,
+            camera_velocity: Point::zero(),
             viewport_extent,
             tile_extent,
-            tile_pictures: Arc::new(Default::default()),
+            tile_pictures: Arc::new(TiledLayerTilePictures::new(tile_extent)),
             scale_factor: TiledLayerScaleFactor::scale_in(1.0),
+            tile_recording_budget: Some(DEFAULT_TILE_RECORDING_BUDGET),
+            prefetch_margin: DEFAULT_PREFETCH_MARGIN,
+            tile_placeholder: None,
             debug_mode: true,
         }
     }
 
+    /// Move the camera, remembering how far it moved to predict which tiles to prefetch
     pub fn with_camera_position(&self, camera_position: Point) -> Self {
         let mut layer = self.clone();
+        layer.camera_velocity = camera_position - self.camera_position;
         layer.camera_position = camera_position;
         layer
     }
 
+    /// The amount of tiles around the viewport to record and rasterize ahead of time
+    pub fn with_prefetch_margin(&self, prefetch_margin: u32) -> Self {
+        let mut layer = self.clone();
+        layer.prefetch_margin = prefetch_margin;
+        layer
+    }
+
+    /// The color drawn under the scaled tiles of other zoom levels while a visible tile
+    /// waits to be recorded, filling the parts they do not cover
+    pub fn with_tile_placeholder(&self, tile_placeholder: Option<Color>) -> Self {
+        let mut layer = self.clone();
+        layer.tile_placeholder = tile_placeholder;
+        layer
+    }
+
     pub fn with_scale_factor(&self, scale_factor: TiledLayerScaleFactor) -> Self {
         let mut layer = self.clone();
         layer.scale_factor = scale_factor;
         layer
     }
 
+    /// Add a figure and invalidate the cached pictures of the tiles it overlaps,
+    /// replacing a figure with the same id if there is one.
+    /// From now on setting a picture of the figure invalidates those tiles too
     pub fn add_figure(&self, figure: TiledLayerFigure) {
-        self.figures.write().insert(figure.clone());
-        self.figures_hash.write().insert(figure.id(), figure);
+        let mut transaction = TiledLayerTransaction::new();
+        transaction.add_figure(figure);
+        self.apply_transaction(transaction);
+    }
+
+    /// Remove a figure with a given id and invalidate the tiles it overlapped.
+    /// Return false if there is no such figure
+    pub fn remove_figure(&self, id: TiledFigureId) -> bool {
+        let mut transaction = TiledLayerTransaction::new();
+        transaction.remove_figure(id);
+        self.apply_transaction(transaction) > 0
+    }
+
+    /// Move and resize a figure with a given id, keeping its picture, and invalidate the tiles
+    /// it overlapped before and after the update. Return false if there is no such figure
+    pub fn update_figure(&self, id: TiledFigureId, offset: Point, extent: Extent) -> bool {
+        let mut transaction = TiledLayerTransaction::new();
+        transaction.update_figure(id, offset, extent);
+        self.apply_transaction(transaction) > 0
+    }
+
+    /// Apply all changes of a transaction while holding both figure indices locked,
+    /// so that other threads never see them out of sync.
+    /// Return the amount of changes that found their figure
+    pub fn apply_transaction(&self, transaction: TiledLayerTransaction) -> usize {
+        let mut figures = self.figures.write();
+        let mut figures_hash = self.figures_hash.write();
+
+        let mut applied = 0;
+        for change in transaction.changes {
+            let is_applied = match change {
+                TiledLayerChange::Add(figure) => {
+                    if let Some(previous) = figures_hash.remove(&figure.id()) {
+                        self.detach_figure(&mut figures, &previous);
+                    }
+                    self.attach_figure(&mut figures, &mut figures_hash, figure);
+                    true
+                }
+                TiledLayerChange::Remove(id) => match figures_hash.remove(&id) {
+                    Some(previous) => {
+                        self.detach_figure(&mut figures, &previous);
+                        true
+                    }
+                    None => false,
+                },
+                TiledLayerChange::Update { id, offset, extent } => match figures_hash.get(&id) {
+                    Some(figure) => {
+                        self.move_figure(&mut figures, figure, offset, extent);
+                        true
+                    }
+                    None => false,
+                },
+            };
+            if is_applied {
+                applied += 1;
+            }
+        }
+        applied
+    }
+
+    fn attach_figure(
+        &self,
+        figures: &mut RTree<TiledLayerFigure>,
+        figures_hash: &mut HashMap<TiledFigureId, TiledLayerFigure>,
+        figure: TiledLayerFigure,
+    ) {
+        *figure.0.tile_pictures.write() = Arc::downgrade(&self.tile_pictures);
+        self.tile_pictures.invalidate_envelope(&figure.envelope());
+        figures.insert(figure.clone());
+        figures_hash.insert(figure.id(), figure);
+    }
+
+    /// Remove a figure from the tree and detach it from the tile pictures,
+    /// so that setting its picture no longer affects the layer
+    fn detach_figure(&self, figures: &mut RTree<TiledLayerFigure>, figure: &TiledLayerFigure) {
+        figures.remove(figure);
+        *figure.0.tile_pictures.write() = Weak::new();
+        self.tile_pictures.invalidate_envelope(&figure.envelope());
+    }
+
+    /// Change the bounds of a figure in place, so that handles to it stay attached to the layer.
+    /// The figure is taken out of the tree while its envelope changes
+    fn move_figure(
+        &self,
+        figures: &mut RTree<TiledLayerFigure>,
+        figure: &TiledLayerFigure,
+        offset: Point,
+        extent: Extent,
+    ) {
+        figures.remove(figure);
+        self.tile_pictures.invalidate_envelope(&figure.envelope());
+        *figure.0.bounds.write() = (offset, extent);
+        self.tile_pictures.invalidate_envelope(&figure.envelope());
+        figures.insert(figure.clone());
     }
 
     pub fn camera_position(&self) -> &Point {
@@ -94,6 +224,28 @@ impl TiledLayer {
         &self.scale_factor
     }
 
+    pub fn camera_velocity(&self) -> &Point {
+        &self.camera_velocity
+    }
+
+    pub fn prefetch_margin(&self) -> u32 {
+        self.prefetch_margin
+    }
+
+    pub fn tile_placeholder(&self) -> Option<&Color> {
+        self.tile_placeholder.as_ref()
+    }
+
+    /// The zoom level tiles are recorded at for the current scale factor
+    pub fn zoom_level(&self) -> Fraction {
+        self.scale_factor.zoom_level()
+    }
+
+    /// Extent of the tiles at the current zoom level in layer coordinates
+    pub fn level_tile_extent(&self) -> Extent {
+        level_tile_extent(&self.tile_extent, &self.zoom_level())
+    }
+
     pub fn clone_figures(&self) -> Vec<TiledLayerFigure> {
         self.figures
             .read()
@@ -158,20 +310,42 @@ impl TiledLayer {
         self.tile_extent.height() * self.tile_scale_factor()
     }
 
+    /// The part of the layer shown in the viewport, in layer coordinates.
+    /// The layer is scaled around the camera, which stays in the center of the viewport
+    pub fn visible_envelope(&self) -> AABB<Point> {
+        let half_extent: Point = (self.viewport_extent / (2.0 * self.scale_factor.value())).into();
+        AABB::from_corners(
+            self.camera_position - half_extent,
+            self.camera_position + half_extent,
+        )
+    }
+
     pub fn left_tile_column(&self) -> ColumnIndex {
-        column_at(self.viewport_left(), self.tile_scaled_width())
+        column_at(
+            self.visible_envelope().lower().x(),
+            self.level_tile_extent().width(),
+        )
     }
 
     pub fn right_tile_column(&self) -> ColumnIndex {
-        column_at(self.viewport_right(), self.tile_scaled_width())
+        column_at(
+            self.visible_envelope().upper().x(),
+            self.level_tile_extent().width(),
+        )
     }
 
     pub fn top_tile_row(&self) -> RowIndex {
-        row_at(self.viewport_top(), self.tile_scaled_height())
+        row_at(
+            self.visible_envelope().lower().y(),
+            self.level_tile_extent().height(),
+        )
     }
 
     pub fn bottom_tile_row(&self) -> RowIndex {
-        row_at(self.viewport_bottom(), self.tile_scaled_height())
+        row_at(
+            self.visible_envelope().upper().y(),
+            self.level_tile_extent().height(),
+        )
     }
 
     /// Find and return figures that overlap a given tile
@@ -248,20 +422,660 @@ impl TiledLayer {
         self.figures_hash.read().get(&id).cloned()
     }
 
+    /// Cache a recorded picture of a tile at the zoom level of the tile. If more zoom levels than
+    /// the budget have cached tiles, the least recently used level other than this one is dropped
     pub fn cache_tile_picture(&self, tile: &TiledLayerTile, picture: PictureLayer) {
-        let _ = self
-            .tile_pictures
+        let focus = self.tile_focus();
+        self.tile_pictures
+            .levels
             .write()
-            .insert(tile.coordinate(), picture);
+            .insert(tile, picture, &focus);
     }
 
+    /// Return a cached picture of a tile recorded at the zoom level of the tile
     pub fn get_tile_picture(&self, tile: &TiledLayerTile) -> Option<PictureLayer> {
-        self.tile_pictures.read().get(&tile.coordinate()).cloned()
+        self.tile_pictures.levels.write().get(tile)
+    }
+
+    /// Return cached tiles of the zoom level closest to the one of a given tile that together
+    /// cover the tile, or an empty vector if no other level covers it.
+    /// The tiles are meant to be drawn scaled while the tile is not recorded at its own level
+    pub fn fallback_tile_pictures(
+        &self,
+        tile: &TiledLayerTile,
+    ) -> Vec<(TiledLayerTile, PictureLayer)> {
+        self.tile_pictures
+            .levels
+            .write()
+            .fallback(tile, &self.tile_extent)
+    }
+
+    /// Return all cached tile pictures, including the ones of tiles that are no longer visible
+    /// and the ones recorded at other zoom levels
+    pub fn tile_pictures(&self) -> Vec<PictureLayer> {
+        self.tile_pictures
+            .levels
+            .read()
+            .levels
+            .values()
+            .flat_map(|level| level.pictures.values().cloned())
+            .collect()
+    }
+
+    /// Call a given function with every cached tile picture over all zoom levels,
+    /// without copying them out of the cache
+    pub fn for_each_tile_picture(&self, mut f: impl FnMut(&PictureLayer)) {
+        let levels = self.tile_pictures.levels.read();
+        for picture in levels
+            .levels
+            .values()
+            .flat_map(|level| level.pictures.values())
+        {
+            f(picture);
+        }
+    }
+
+    /// Return zoom levels that have cached tile pictures, from the smallest to the largest
+    pub fn cached_zoom_levels(&self) -> Vec<Fraction> {
+        let mut zoom_levels = self
+            .tile_pictures
+            .levels
+            .read()
+            .levels
+            .keys()
+            .copied()
+            .collect::<Vec<Fraction>>();
+        zoom_levels.sort();
+        zoom_levels
+    }
+
+    /// How many zoom levels retain their tile pictures at the same time
+    pub fn zoom_level_budget(&self) -> usize {
+        self.tile_pictures.levels.read().budget
+    }
+
+    /// Set how many zoom levels retain their tile pictures, dropping the least recently used
+    /// levels above the budget. The budget is shared by all copies of the layer
+    pub fn set_zoom_level_budget(&self, budget: usize) {
+        let mut levels = self.tile_pictures.levels.write();
+        levels.budget = budget.max(1);
+        levels.evict_to_budget(None);
+    }
+
+    /// How many tile pictures are retained over all zoom levels, or [`None`] if unlimited
+    pub fn tile_budget(&self) -> Option<usize> {
+        self.tile_pictures.levels.read().tile_budget
+    }
+
+    /// Set how many tile pictures are retained over all zoom levels. Above the budget tiles of
+    /// other zoom levels are evicted first, then the ones farthest from the camera. Visible tiles
+    /// are never evicted. The budget is shared by all copies of the layer
+    pub fn set_tile_budget(&self, budget: Option<usize>) {
+        let focus = self.tile_focus();
+        let mut levels = self.tile_pictures.levels.write();
+        levels.tile_budget = budget;
+        levels.evict_to_tile_budget(&focus, None);
+    }
+
+    /// Return the amount and the size of cached tile pictures
+    pub fn tile_statistics(&self) -> TiledLayerTileStatistics {
+        let levels = self.tile_pictures.levels.read();
+        TiledLayerTileStatistics {
+            tiles: levels.len(),
+            zoom_levels: levels.levels.len(),
+            picture_bytes: levels
+                .levels
+                .values()
+                .flat_map(|level| level.pictures.values())
+                .map(|picture| picture.picture().approximate_bytes_used())
+                .sum(),
+            evictions: levels.evictions,
+        }
+    }
+
+    /// Return ids of tile pictures that were evicted or invalidated since the last call,
+    /// so that the compositor can drop their rasterized images
+    pub fn take_released_tile_pictures(&self) -> Vec<u32> {
+        std::mem::take(&mut self.tile_pictures.levels.write().released)
+    }
+
+    /// How many missing tiles the compositor records per frame when it can draw scaled tiles
+    /// of another zoom level instead. Tiles that no other level covers are always recorded.
+    /// Tiles within the prefetch margin are only recorded with what visible tiles leave over
+    pub fn tile_recording_budget(&self) -> Option<usize> {
+        self.tile_recording_budget
+    }
+
+    pub fn with_tile_recording_budget(&self, budget: Option<usize>) -> Self {
+        let mut layer = self.clone();
+        layer.tile_recording_budget = budget;
+        layer
+    }
+
+    /// Return true if any visible tile has no picture recorded at the current zoom level
+    pub fn has_missing_visible_tiles(&self) -> bool {
+        let levels = self.tile_pictures.levels.read();
+        self.visible_tiles().any(|tile| !levels.contains(&tile))
+    }
+
+    /// Remove cached pictures of all tiles that overlap a given region in layer coordinates
+    /// at every zoom level, so that they are recorded again the next time they are visible.
+    /// Return the amount of invalidated tiles
+    pub fn invalidate_region(&self, region: &Rectangle) -> usize {
+        self.tile_pictures.invalidate_envelope(&AABB::from_corners(
+            Point::new(region.left(), region.top()),
+            Point::new(region.right(), region.bottom()),
+        ))
+    }
+
+    /// Remove all cached tile pictures. Return the amount of invalidated tiles
+    pub fn invalidate_all(&self) -> usize {
+        let mut levels = self.tile_pictures.levels.write();
+        let amount = levels.len();
+        for (_, level) in levels.levels.drain().collect::<Vec<_>>() {
+            levels.release(level.pictures.into_values());
+        }
+        amount
+    }
+
+    /// Identity of the tile pictures shared by all copies of the layer
+    pub fn tile_cache_id(&self) -> usize {
+        Arc::as_ptr(&self.tile_pictures) as usize
+    }
+
+    /// Return tiles around the viewport within the prefetch margin that are not recorded yet,
+    /// extended in the direction the camera moves by the distance it moved last time.
+    /// Tiles closer to the predicted camera position come first
+    pub fn prefetch_tiles(&self) -> Vec<TiledLayerTile> {
+        let tile_extent = self.level_tile_extent();
+        let (tile_width, tile_height) = (tile_extent.width(), tile_extent.height());
+        let margin = self.prefetch_margin as i32;
+        let velocity = self.camera_velocity;
+
+        let ahead =
+            |distance: Scalar, tile_size: Scalar| (distance.0 / tile_size.0).abs().ceil() as i32;
+        let (columns_ahead, rows_ahead) = (
+            ahead(velocity.x(), tile_width),
+            ahead(velocity.y(), tile_height),
+        );
+
+        let start_column = offset_index(
+            self.left_tile_column(),
+            -margin
+                - if velocity.x().0 < 0.0 {
+                    columns_ahead
+                } else {
+                    0
+                },
+        );
+        let end_column = offset_index(
+            self.right_tile_column(),
+            margin
+                + if velocity.x().0 > 0.0 {
+                    columns_ahead
+                } else {
+                    0
+                },
+        );
+        let start_row = offset_index(
+            self.top_tile_row(),
+            -margin - if velocity.y().0 < 0.0 { rows_ahead } else { 0 },
+        );
+        let end_row = offset_index(
+            self.bottom_tile_row(),
+            margin + if velocity.y().0 > 0.0 { rows_ahead } else { 0 },
+        );
+
+        let predicted_camera = self.camera_position + velocity;
+        let center_column = column_at(predicted_camera.x(), tile_width);
+        let center_row = row_at(predicted_camera.y(), tile_height);
+
+        let is_visible = |column: ColumnIndex, row: RowIndex| {
+            (self.left_tile_column()..=self.right_tile_column()).contains(&column)
+                && (self.top_tile_row()..=self.bottom_tile_row()).contains(&row)
+        };
+
+        let levels = self.tile_pictures.levels.read();
+        let mut tiles = (start_row..=end_row)
+            .filter(|row| *row != 0)
+            .flat_map(|row| {
+                (start_column..=end_column)
+                    .filter(|column| *column != 0)
+                    .map(move |column| (column, row))
+            })
+            .filter(|(column, row)| !is_visible(*column, *row))
+            .map(|(column, row)| TiledLayerTile {
+                column,
+                row,
+                extent: self.level_tile_extent(),
+                zoom_level: self.zoom_level(),
+            })
+            .filter(|tile| !levels.contains(tile))
+            .collect::<Vec<TiledLayerTile>>();
+
+        tiles.sort_by_key(|tile| {
+            (tile.column - center_column).abs() + (tile.row - center_row).abs()
+        });
+        tiles
     }
 
     pub fn is_debug_mode(&self) -> bool {
         self.debug_mode
     }
+
+    fn tile_focus(&self) -> TiledLayerTileFocus {
+        let tile_extent = self.level_tile_extent();
+        TiledLayerTileFocus {
+            zoom_level: self.zoom_level(),
+            center: (
+                column_at(self.camera_position.x(), tile_extent.width()),
+                row_at(self.camera_position.y(), tile_extent.height()),
+            ),
+            visible_columns: self.left_tile_column()..=self.right_tile_column(),
+            visible_rows: self.top_tile_row()..=self.bottom_tile_row(),
+        }
+    }
+}
+
+/// The amount and the size of tile pictures cached by a tiled layer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TiledLayerTileStatistics {
+    tiles: usize,
+    zoom_levels: usize,
+    picture_bytes: usize,
+    evictions: u64,
+}
+
+impl TiledLayerTileStatistics {
+    /// The amount of cached tile pictures over all zoom levels
+    pub fn tiles(&self) -> usize {
+        self.tiles
+    }
+
+    pub fn zoom_levels(&self) -> usize {
+        self.zoom_levels
+    }
+
+    /// Bytes retained by the recorded tile pictures, not including their rasterized images
+    pub fn picture_bytes(&self) -> usize {
+        self.picture_bytes
+    }
+
+    /// The amount of tile pictures dropped to stay within the tile and zoom level budgets
+    pub fn evictions(&self) -> u64 {
+        self.evictions
+    }
+}
+
+/// Recorded pictures of tiles shared between the layer and its figures,
+/// which invalidate the tiles they overlap when their picture changes
+#[derive(Debug)]
+struct TiledLayerTilePictures {
+    tile_extent: Extent,
+    levels: RwLock<TiledLayerTileLevels>,
+}
+
+impl TiledLayerTilePictures {
+    fn new(tile_extent: Extent) -> Self {
+        Self {
+            tile_extent,
+            levels: RwLock::new(TiledLayerTileLevels::new(DEFAULT_ZOOM_LEVEL_BUDGET)),
+        }
+    }
+
+    /// Remove pictures of the tiles that intersect or touch a given envelope at every zoom level
+    fn invalidate_envelope(&self, envelope: &AABB<Point>) -> usize {
+        let mut levels = self.levels.write();
+        let amount = levels.len();
+        let mut invalidated = vec![];
+        for (zoom_level, level) in levels.levels.iter_mut() {
+            let extent = level_tile_extent(&self.tile_extent, zoom_level);
+            level.pictures.retain(|(column, row), picture| {
+                let tile = TiledLayerTile {
+                    column: *column,
+                    row: *row,
+                    extent,
+                    zoom_level: *zoom_level,
+                };
+                let is_valid = !tile.envelope().intersects(envelope);
+                if !is_valid {
+                    invalidated.push(picture.clone());
+                }
+                is_valid
+            });
+        }
+        levels.release(invalidated);
+        levels.levels.retain(|_, level| !level.pictures.is_empty());
+        amount - levels.len()
+    }
+}
+
+/// The amount of zoom levels that retain their tile pictures by default
+const DEFAULT_ZOOM_LEVEL_BUDGET: usize = 3;
+
+/// The amount of released tile picture ids kept until the compositor takes them
+const MAX_RELEASED_TILE_PICTURES: usize = 1024;
+
+/// Where the viewport is at the current zoom level, to evict the tiles farthest from it first
+#[derive(Debug, Clone)]
+struct TiledLayerTileFocus {
+    zoom_level: Fraction,
+    center: (ColumnIndex, RowIndex),
+    visible_columns: RangeInclusive<ColumnIndex>,
+    visible_rows: RangeInclusive<RowIndex>,
+}
+
+impl TiledLayerTileFocus {
+    fn is_visible(&self, zoom_level: &Fraction, (column, row): &(ColumnIndex, RowIndex)) -> bool {
+        *zoom_level == self.zoom_level
+            && self.visible_columns.contains(column)
+            && self.visible_rows.contains(row)
+    }
+
+    fn distance(&self, (column, row): &(ColumnIndex, RowIndex)) -> i32 {
+        (column - self.center.0).abs() + (row - self.center.1).abs()
+    }
+}
+
+/// A pyramid of tile pictures, one level per zoom level the tiles were recorded at
+#[derive(Debug)]
+struct TiledLayerTileLevels {
+    levels: HashMap<Fraction, TiledLayerTileLevel>,
+    budget: usize,
+    tile_budget: Option<usize>,
+    /// incremented every time a level is used, to find the least recently used one
+    clock: u64,
+    /// the amount of tile pictures dropped to stay within the budgets
+    evictions: u64,
+    /// ids of evicted and invalidated tile pictures, whose images can be dropped
+    released: Vec<u32>,
+}
+
+#[derive(Debug, Default)]
+struct TiledLayerTileLevel {
+    pictures: HashMap<(ColumnIndex, RowIndex), PictureLayer>,
+    last_used: u64,
+}
+
+impl TiledLayerTileLevels {
+    fn new(budget: usize) -> Self {
+        Self {
+            levels: Default::default(),
+            budget,
+            tile_budget: None,
+            clock: 0,
+            evictions: 0,
+            released: vec![],
+        }
+    }
+
+    /// Remember ids of dropped pictures, returning how many there are. Only the most recent
+    /// ones are kept if nobody takes them, images of the others age out of the raster cache
+    fn release(&mut self, pictures: impl IntoIterator<Item = PictureLayer>) -> u64 {
+        let amount = self.released.len();
+        self.released
+            .extend(pictures.into_iter().map(|picture| picture.id()));
+        let released = (self.released.len() - amount) as u64;
+
+        let overflow = self
+            .released
+            .len()
+            .saturating_sub(MAX_RELEASED_TILE_PICTURES);
+        self.released.drain(..overflow);
+        released
+    }
+
+    fn evict(&mut self, pictures: impl IntoIterator<Item = PictureLayer>) {
+        self.evictions += self.release(pictures);
+    }
+
+    /// The amount of cached tile pictures over all levels
+    fn len(&self) -> usize {
+        self.levels.values().map(|level| level.pictures.len()).sum()
+    }
+
+    fn contains(&self, tile: &TiledLayerTile) -> bool {
+        self.levels
+            .get(&tile.zoom_level)
+            .is_some_and(|level| level.pictures.contains_key(&tile.coordinate()))
+    }
+
+    fn get(&mut self, tile: &TiledLayerTile) -> Option<PictureLayer> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.levels.get_mut(&tile.zoom_level).and_then(|level| {
+            level.last_used = clock;
+            level.pictures.get(&tile.coordinate()).cloned()
+        })
+    }
+
+    fn insert(
+        &mut self,
+        tile: &TiledLayerTile,
+        picture: PictureLayer,
+        focus: &TiledLayerTileFocus,
+    ) {
+        self.clock += 1;
+        let picture_id = picture.id();
+        let level = self.levels.entry(tile.zoom_level).or_default();
+        level.last_used = self.clock;
+        let replaced = level.pictures.insert(tile.coordinate(), picture);
+        self.release(replaced.filter(|replaced| replaced.id() != picture_id));
+        self.evict_to_budget(Some(tile.zoom_level));
+        self.evict_to_tile_budget(focus, Some(tile));
+    }
+
+    /// Drop the least recently used levels until the budget is met, except for a given level
+    fn evict_to_budget(&mut self, keep: Option<Fraction>) {
+        while self.levels.len() > self.budget {
+            let least_recently_used = self
+                .levels
+                .iter()
+                .filter(|(zoom_level, _)| Some(**zoom_level) != keep)
+                .min_by_key(|(_, level)| level.last_used)
+                .map(|(zoom_level, _)| *zoom_level);
+
+            match least_recently_used {
+                Some(zoom_level) => {
+                    if let Some(level) = self.levels.remove(&zoom_level) {
+                        self.evict(level.pictures.into_values());
+                    }
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Drop tiles until the tile budget is met, starting with the least recently used levels
+    /// other than the focused one, then the focused tiles farthest from the camera.
+    /// Visible tiles and a given tile are kept even if that exceeds the budget
+    fn evict_to_tile_budget(&mut self, focus: &TiledLayerTileFocus, keep: Option<&TiledLayerTile>) {
+        let Some(tile_budget) = self.tile_budget else {
+            return;
+        };
+        let excess = self.len().saturating_sub(tile_budget);
+        if excess == 0 {
+            return;
+        }
+
+        let is_kept = |zoom_level: &Fraction, coordinate: &(ColumnIndex, RowIndex)| {
+            focus.is_visible(zoom_level, coordinate)
+                || keep.is_some_and(|tile| {
+                    tile.zoom_level == *zoom_level && tile.coordinate() == *coordinate
+                })
+        };
+
+        let mut candidates = self
+            .levels
+            .iter()
+            .flat_map(|(zoom_level, level)| {
+                level
+                    .pictures
+                    .keys()
+                    .filter(|coordinate| !is_kept(zoom_level, coordinate))
+                    .map(|coordinate| {
+                        // tiles of other levels go first, they can't be drawn without scaling
+                        let rank = if *zoom_level == focus.zoom_level {
+                            (1, u64::MAX, -focus.distance(coordinate))
+                        } else {
+                            (0, level.last_used, 0)
+                        };
+                        (rank, *zoom_level, *coordinate)
+                    })
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|(rank, _, _)| *rank);
+
+        let mut evicted = vec![];
+        for (_, zoom_level, coordinate) in candidates.into_iter().take(excess) {
+            if let Some(level) = self.levels.get_mut(&zoom_level) {
+                evicted.extend(level.pictures.remove(&coordinate));
+            }
+        }
+        self.evict(evicted);
+        self.levels.retain(|_, level| !level.pictures.is_empty());
+    }
+
+    fn fallback(
+        &mut self,
+        tile: &TiledLayerTile,
+        tile_extent: &Extent,
+    ) -> Vec<(TiledLayerTile, PictureLayer)> {
+        let zoom_scale = zoom_level_scale(&tile.zoom_level);
+        let mut zoom_levels = self
+            .levels
+            .keys()
+            .filter(|zoom_level| **zoom_level != tile.zoom_level)
+            .copied()
+            .collect::<Vec<Fraction>>();
+        // the closest level in the logarithmic scale, preferring more detailed levels
+        zoom_levels.sort_by(|first, second| {
+            let distance =
+                |zoom_level: &Fraction| (zoom_level_scale(zoom_level) / zoom_scale).log2().abs();
+            distance(first)
+                .total_cmp(&distance(second))
+                .then_with(|| second.cmp(first))
+        });
+
+        let envelope = tile.envelope();
+        for zoom_level in zoom_levels {
+            let pictures = &self.levels[&zoom_level].pictures;
+            let extent = level_tile_extent(tile_extent, &zoom_level);
+            let covering_tiles = tiles_covering(&envelope, extent, zoom_level)
+                .into_iter()
+                .map(|tile| {
+                    let picture = pictures.get(&tile.coordinate()).cloned();
+                    picture.map(|picture| (tile, picture))
+                })
+                .collect::<Option<Vec<(TiledLayerTile, PictureLayer)>>>();
+
+            if let Some(covering_tiles) = covering_tiles {
+                self.clock += 1;
+                if let Some(level) = self.levels.get_mut(&zoom_level) {
+                    level.last_used = self.clock;
+                }
+                return covering_tiles;
+            }
+        }
+        vec![]
+    }
+}
+
+/// Move a tile index by a given amount of tiles, skipping the index zero that no tile has
+fn offset_index(index: i32, delta: i32) -> i32 {
+    let offset = index + delta;
+    if index > 0 && offset <= 0 {
+        offset - 1
+    } else if index < 0 && offset >= 0 {
+        offset + 1
+    } else {
+        offset
+    }
+}
+
+fn zoom_level_scale(zoom_level: &Fraction) -> f32 {
+    zoom_level.to_f32().unwrap_or(1.0)
+}
+
+/// Tiles of a zoom level cover the same area of the viewport,
+/// so the more the layer is zoomed in the smaller they are in layer coordinates
+fn level_tile_extent(tile_extent: &Extent, zoom_level: &Fraction) -> Extent {
+    *tile_extent / zoom_level_scale(zoom_level)
+}
+
+/// Return tiles of a zoom level that overlap a given envelope by more than an edge
+fn tiles_covering(
+    envelope: &AABB<Point>,
+    extent: Extent,
+    zoom_level: Fraction,
+) -> Vec<TiledLayerTile> {
+    let (lower, upper) = (envelope.lower(), envelope.upper());
+    let mut tiles = vec![];
+    for row in row_at(lower.y(), extent.height())..=row_at(upper.y(), extent.height()) {
+        for column in column_at(lower.x(), extent.width())..=column_at(upper.x(), extent.width()) {
+            if row == 0 || column == 0 {
+                continue;
+            }
+            let tile = TiledLayerTile {
+                column,
+                row,
+                extent,
+                zoom_level,
+            };
+            if tile.right() > lower.x()
+                && tile.left() < upper.x()
+                && tile.bottom() > lower.y()
+                && tile.top() < upper.y()
+            {
+                tiles.push(tile);
+            }
+        }
+    }
+    tiles
+}
+
+/// A batch of figure changes applied at once with [`TiledLayer::apply_transaction`]
+#[derive(Debug, Default)]
+pub struct TiledLayerTransaction {
+    changes: Vec<TiledLayerChange>,
+}
+
+#[derive(Debug)]
+enum TiledLayerChange {
+    Add(TiledLayerFigure),
+    Remove(TiledFigureId),
+    Update {
+        id: TiledFigureId,
+        offset: Point,
+        extent: Extent,
+    },
+}
+
+impl TiledLayerTransaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_figure(&mut self, figure: TiledLayerFigure) {
+        self.changes.push(TiledLayerChange::Add(figure));
+    }
+
+    pub fn remove_figure(&mut self, id: TiledFigureId) {
+        self.changes.push(TiledLayerChange::Remove(id));
+    }
+
+    pub fn update_figure(&mut self, id: TiledFigureId, offset: Point, extent: Extent) {
+        self.changes
+            .push(TiledLayerChange::Update { id, offset, extent });
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -364,7 +1178,8 @@ impl<'layer> TiledLayerVisibleTilesIterator<'layer> {
         let start_row = layer.top_tile_row();
 
         let mut tile = TiledLayerTile::default();
-        tile.extent = layer.tile_extent.clone();
+        tile.extent = layer.level_tile_extent();
+        tile.zoom_level = layer.zoom_level();
 
         Self {
             layer,
@@ -427,11 +1242,18 @@ impl<'layer> Iterator for TiledLayerVisibleTilesAndTheirFiguresIterator<'layer>
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct TiledLayerTile {
     column: ColumnIndex,
     row: RowIndex,
     extent: Extent,
+    zoom_level: Fraction,
+}
+
+impl Default for TiledLayerTile {
+    fn default() -> Self {
+        Self::new(0, 0)
+    }
 }
 
 impl TiledLayerTile {
@@ -440,6 +1262,7 @@ impl TiledLayerTile {
             column: column.into(),
             row: row.into(),
             extent: Default::default(),
+            zoom_level: Fraction::from(1u64),
         }
     }
 
@@ -447,6 +1270,17 @@ impl TiledLayerTile {
         (self.column, self.row)
     }
 
+    /// The zoom level the tile is recorded at
+    pub fn zoom_level(&self) -> Fraction {
+        self.zoom_level
+    }
+
+    /// Scale of the tile picture, recorded at the resolution of its zoom level,
+    /// relative to layer coordinates
+    pub fn zoom_scale(&self) -> f32 {
+        zoom_level_scale(&self.zoom_level)
+    }
+
     pub fn left(&self) -> Scalar {
         let left = if self.column < 0 {
             self.column
@@ -502,18 +1336,20 @@ pub struct TiledLayerFigure(Arc<TiledLayerFigureData>);
 #[derive(Debug)]
 struct TiledLayerFigureData {
     id: TiledFigureId,
-    offset: Point,
-    extent: Extent,
+    /// offset and extent, only changed by the layer while the figure is out of its tree
+    bounds: RwLock<(Point, Extent)>,
     picture: RwLock<Option<PictureLayer>>,
+    /// tile pictures of the layer the figure was added to
+    tile_pictures: RwLock<Weak<TiledLayerTilePictures>>,
 }
 
 impl TiledLayerFigure {
     pub fn new(id: TiledFigureId, offset: Point, extent: Extent) -> Self {
         Self(Arc::new(TiledLayerFigureData {
             id,
-            offset,
-            extent,
+            bounds: RwLock::new((offset, extent)),
             picture: Default::default(),
+            tile_pictures: RwLock::new(Weak::new()),
         }))
     }
 
@@ -521,12 +1357,12 @@ impl TiledLayerFigure {
         self.0.id
     }
 
-    pub fn offset(&self) -> &Point {
-        &self.0.offset
+    pub fn offset(&self) -> Point {
+        self.0.bounds.read().0
     }
 
-    pub fn extent(&self) -> &Extent {
-        &self.0.extent
+    pub fn extent(&self) -> Extent {
+        self.0.bounds.read().1
     }
 
     pub fn top(&self) -> Scalar {
@@ -555,15 +1391,19 @@ impl TiledLayerFigure {
             .read()
             .deref()
             .clone()
-            .map(|layer| OffsetLayer::wrap_with_offset(layer, self.offset().clone()))
+            .map(|layer| OffsetLayer::wrap_with_offset(layer, self.offset()))
     }
 
     pub fn get_picture(&self) -> Option<PictureLayer> {
         self.0.picture.read().deref().clone()
     }
 
+    /// Replace the picture of the figure and invalidate the tiles it overlaps
     pub fn set_picture(&self, picture: PictureLayer) {
         let _ = self.0.picture.write().insert(picture);
+        if let Some(tile_pictures) = self.0.tile_pictures.read().upgrade() {
+            tile_pictures.invalidate_envelope(&self.envelope());
+        }
     }
 
     pub fn with_picture(self, picture: PictureLayer) -> Self {
@@ -572,15 +1412,21 @@ impl TiledLayerFigure {
     }
 }
 
+/// Figures are identical only if they are the same instance,
+/// which lets the layer remove a specific figure from its tree
+impl PartialEq for TiledLayerFigure {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
 impl RTreeObject for TiledLayerFigure {
     type Envelope = AABB<Point>;
 
     fn envelope(&self) -> Self::Envelope {
-        let corner_1 = self.offset().clone();
-        let corner_2 = Point::new(
-            self.offset().x() + self.extent().width(),
-            self.offset().y() + self.extent().height(),
-        );
+        let (offset, extent) = *self.0.bounds.read();
+        let corner_1 = offset;
+        let corner_2 = Point::new(offset.x() + extent.width(), offset.y() + extent.height());
         AABB::from_corners(corner_1, corner_2)
     }
 }
@@ -703,6 +1549,313 @@ mod tests {
             Fraction::new(3u64, 1u64)
         );
     }
+
+    #[test]
+    pub fn test_invalidate_tile_pictures() {
+        let layer = TiledLayer::default();
+        let cache_tiles = || {
+            for (column, row) in [(1, 1), (2, 1), (-1, -1), (5, 5)] {
+                layer.cache_tile_picture(&tile(&layer, column, row), picture());
+            }
+        };
+
+        cache_tiles();
+        let figure = TiledLayerFigure::new(1, Point::new(10.0, 10.0), Extent::new(150.0, 50.0));
+        layer.add_figure(figure.clone());
+        assert!(layer.get_tile_picture(&tile(&layer, 1, 1)).is_none());
+        assert!(layer.get_tile_picture(&tile(&layer, 2, 1)).is_none());
+        assert!(layer.get_tile_picture(&tile(&layer, -1, -1)).is_some());
+        assert_eq!(layer.tile_pictures().len(), 2);
+
+        cache_tiles();
+        figure.set_picture(picture());
+        assert!(layer.get_tile_picture(&tile(&layer, 2, 1)).is_none());
+        assert_eq!(layer.tile_pictures().len(), 2);
+
+        assert_eq!(
+            layer.invalidate_region(&Rectangle::new(-20.0, -20.0, 10.0, 10.0)),
+            1
+        );
+        assert!(layer.get_tile_picture(&tile(&layer, 5, 5)).is_some());
+        assert_eq!(layer.invalidate_all(), 1);
+        assert!(layer.tile_pictures().is_empty());
+    }
+
+    #[test]
+    pub fn test_remove_and_update_figures() {
+        let layer = TiledLayer::default();
+        let overlapping_ids = |column: ColumnIndex, row: RowIndex| {
+            layer
+                .figures_overlapping_tile(&tile(&layer, column, row))
+                .iter()
+                .map(|figure| figure.id())
+                .collect::<Vec<TiledFigureId>>()
+        };
+
+        layer.add_figure(TiledLayerFigure::new(
+            1,
+            Point::new(10.0, 10.0),
+            Extent::new(20.0, 20.0),
+        ));
+        layer.add_figure(
+            TiledLayerFigure::new(2, Point::new(10.0, 10.0), Extent::new(20.0, 20.0))
+                .with_picture(picture()),
+        );
+        assert_eq!(layer.figures().len(), 2);
+
+        let figure = layer.find_figure_by_id(2).unwrap();
+        layer.cache_tile_picture(&tile(&layer, 1, 1), picture());
+        assert!(layer.update_figure(2, Point::new(300.0, 10.0), Extent::new(20.0, 20.0)));
+        assert!(layer.get_tile_picture(&tile(&layer, 1, 1)).is_none());
+        assert_eq!(overlapping_ids(1, 1), vec![1]);
+        assert_eq!(overlapping_ids(3, 1), vec![2]);
+        assert!(layer.find_figure_by_id(2).unwrap().has_picture());
+
+        // handles to the updated figure stay attached to the layer
+        assert_eq!(figure.offset(), Point::new(300.0, 10.0));
+        layer.cache_tile_picture(&tile(&layer, 3, 1), picture());
+        figure.set_picture(picture());
+        assert!(layer.get_tile_picture(&tile(&layer, 3, 1)).is_none());
+
+        assert!(layer.remove_figure(1));
+        assert!(!layer.remove_figure(1));
+        assert!(!layer.update_figure(1, Point::zero(), Extent::zero()));
+        assert!(overlapping_ids(1, 1).is_empty());
+        assert!(layer.find_figure_by_id(1).is_none());
+
+        let mut transaction = TiledLayerTransaction::new();
+        transaction.add_figure(TiledLayerFigure::new(
+            3,
+            Point::zero(),
+            Extent::new(5.0, 5.0),
+        ));
+        transaction.add_figure(TiledLayerFigure::new(
+            3,
+            Point::zero(),
+            Extent::new(10.0, 10.0),
+        ));
+        transaction.remove_figure(2);
+        transaction.remove_figure(4);
+        assert_eq!(layer.apply_transaction(transaction), 3);
+        assert_eq!(layer.figures().len(), 1);
+        assert_eq!(layer.figures_hash.read().len(), 1);
+        assert_eq!(
+            layer.find_figure_by_id(3).unwrap().extent(),
+            Extent::new(10.0, 10.0)
+        );
+    }
+
+    #[test]
+    pub fn test_zoom_level_pyramid() {
+        let layer = TiledLayer::default();
+        let zoomed_in = layer.with_scale_factor(TiledLayerScaleFactor::scale_in(2.0));
+
+        assert_eq!(zoomed_in.level_tile_extent(), Extent::new(64.0, 64.0));
+        assert_eq!(tile(&zoomed_in, 2, 2).origin(), Point::new(64.0, 64.0));
+
+        layer.cache_tile_picture(&tile(&layer, 1, 1), picture());
+        assert!(
+            zoomed_in
+                .get_tile_picture(&tile(&zoomed_in, 1, 1))
+                .is_none()
+        );
+
+        // the four zoomed in tiles are covered by the same tile of the lower level
+        let fallback = zoomed_in.fallback_tile_pictures(&tile(&zoomed_in, 2, 2));
+        assert_eq!(fallback.len(), 1);
+        assert_eq!(fallback[0].0.coordinate(), (1, 1));
+        assert_eq!(fallback[0].0.zoom_level(), Fraction::from(1u64));
+        assert!(
+            zoomed_in
+                .fallback_tile_pictures(&tile(&zoomed_in, 3, 1))
+                .is_empty()
+        );
+
+        // a zoomed out tile needs all four tiles it covers
+        let zoomed_out = layer.with_scale_factor(TiledLayerScaleFactor::scale_in(0.5));
+        assert!(
+            zoomed_out
+                .fallback_tile_pictures(&tile(&zoomed_out, 1, 1))
+                .is_empty()
+        );
+        for (column, row) in [(2, 1), (1, 2), (2, 2)] {
+            layer.cache_tile_picture(&tile(&layer, column, row), picture());
+        }
+        assert_eq!(
+            zoomed_out
+                .fallback_tile_pictures(&tile(&zoomed_out, 1, 1))
+                .len(),
+            4
+        );
+
+        layer.set_zoom_level_budget(2);
+        zoomed_in.cache_tile_picture(&tile(&zoomed_in, 1, 1), picture());
+        zoomed_out.cache_tile_picture(&tile(&zoomed_out, 1, 1), picture());
+        assert_eq!(
+            layer.cached_zoom_levels(),
+            vec![Fraction::new(1u64, 2u64), Fraction::from(2u64)]
+        );
+
+        assert_eq!(
+            layer.invalidate_region(&Rectangle::new(0.0, 0.0, 1.0, 1.0)),
+            2
+        );
+        assert!(layer.cached_zoom_levels().is_empty());
+    }
+
+    #[test]
+    pub fn test_visible_tiles_cover_viewport() {
+        for scale in [0.3, 0.5, 1.0, 1.5, 2.0, 3.0, 4.5] {
+            for camera in [
+                Point::zero(),
+                Point::new(1000.0, -700.0),
+                Point::new(-333.0, 250.0),
+            ] {
+                let layer = TiledLayer::default()
+                    .with_camera_position(camera)
+                    .with_scale_factor(TiledLayerScaleFactor::scale_in(scale));
+                let visible = layer.visible_envelope();
+                let tiles = layer.visible_tiles().collect::<Vec<_>>();
+
+                let left = tiles.iter().map(|tile| tile.left()).min().unwrap();
+                let top = tiles.iter().map(|tile| tile.top()).min().unwrap();
+                let right = tiles.iter().map(|tile| tile.right()).max().unwrap();
+                let bottom = tiles.iter().map(|tile| tile.bottom()).max().unwrap();
+                assert!(left <= visible.lower().x() && top <= visible.lower().y());
+                assert!(right >= visible.upper().x() && bottom >= visible.upper().y());
+
+                // every tile is at least partially visible
+                assert!(tiles.iter().all(|tile| {
+                    tile.right() >= visible.lower().x()
+                        && tile.left() <= visible.upper().x()
+                        && tile.bottom() >= visible.lower().y()
+                        && tile.top() <= visible.upper().y()
+                }));
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_prefetch_tiles() {
+        let layer = TiledLayer::default();
+        assert_eq!(layer.visible_tiles().count(), 24);
+        assert_eq!(layer.prefetch_tiles().len(), 24);
+        assert!(layer.with_prefetch_margin(0).prefetch_tiles().is_empty());
+
+        let moved = layer.with_camera_position(Point::new(200.0, 0.0));
+        assert_eq!(moved.camera_velocity(), &Point::new(200.0, 0.0));
+
+        let tiles = moved.prefetch_tiles();
+        assert_eq!(tiles.len(), 34);
+        assert_eq!(tiles[0].coordinate(), (5, 1));
+        assert!(tiles.iter().all(|tile| tile.column != 0 && tile.row != 0));
+
+        moved.cache_tile_picture(&tiles[0], picture());
+        assert_eq!(moved.prefetch_tiles().len(), 33);
+    }
+
+    #[test]
+    pub fn test_tile_budget() {
+        let layer = TiledLayer::default();
+        for (index, tile) in layer.visible_tiles().enumerate() {
+            layer.cache_tile_picture(&tile, identified_picture(index as u32));
+        }
+        let zoomed_in = layer.with_scale_factor(TiledLayerScaleFactor::scale_in(2.0));
+        zoomed_in.cache_tile_picture(&tile(&zoomed_in, 1, 1), identified_picture(100));
+        layer.cache_tile_picture(&tile(&layer, 10, 1), identified_picture(101));
+        layer.cache_tile_picture(&tile(&layer, 6, 1), identified_picture(102));
+        assert_eq!(layer.tile_statistics().tiles(), 27);
+        assert_eq!(layer.tile_statistics().zoom_levels(), 2);
+
+        // the other zoom level goes first, then the tile farthest from the camera
+        layer.set_tile_budget(Some(25));
+        assert_eq!(layer.tile_budget(), Some(25));
+        assert_eq!(layer.take_released_tile_pictures(), vec![100, 101]);
+        assert!(layer.take_released_tile_pictures().is_empty());
+        assert_eq!(layer.cached_zoom_levels(), vec![Fraction::from(1u64)]);
+
+        // visible tiles are kept over the budget, the inserted one too
+        layer.set_tile_budget(Some(10));
+        assert_eq!(layer.take_released_tile_pictures(), vec![102]);
+        layer.cache_tile_picture(&tile(&layer, 7, 1), identified_picture(103));
+        assert_eq!(layer.tile_statistics().tiles(), 25);
+        layer.cache_tile_picture(&tile(&layer, -7, 1), identified_picture(104));
+        assert_eq!(layer.take_released_tile_pictures(), vec![103]);
+
+        let statistics = layer.tile_statistics();
+        assert_eq!(statistics.tiles(), 25);
+        assert_eq!(statistics.evictions(), 4);
+        assert_eq!(statistics.picture_bytes(), 25 * 64);
+
+        layer.invalidate_all();
+        assert_eq!(layer.take_released_tile_pictures().len(), 25);
+        assert_eq!(layer.tile_statistics().evictions(), 4);
+
+        // released ids are capped when nobody takes them
+        layer.set_tile_budget(None);
+        for column in 1..=(MAX_RELEASED_TILE_PICTURES as i32 + 10) {
+            layer.cache_tile_picture(&tile(&layer, column, 1), identified_picture(column as u32));
+        }
+        layer.invalidate_all();
+        let released = layer.take_released_tile_pictures();
+        assert_eq!(released.len(), MAX_RELEASED_TILE_PICTURES);
+        assert_eq!(layer.tile_statistics().evictions(), 4);
+    }
+
+    fn tile(layer: &TiledLayer, column: ColumnIndex, row: RowIndex) -> TiledLayerTile {
+        TiledLayerTile {
+            column,
+            row,
+            extent: layer.level_tile_extent(),
+            zoom_level: layer.zoom_level(),
+        }
+    }
+
+    fn picture() -> PictureLayer {
+        PictureLayer::new(Arc::new(TestPicture), false)
+    }
+
+    fn identified_picture(id: u32) -> PictureLayer {
+        PictureLayer::new(Arc::new(IdentifiedPicture(id)), false)
+    }
+
+    #[derive(Debug)]
+    struct IdentifiedPicture(u32);
+
+    impl crate::Picture for IdentifiedPicture {
+        fn unique_id(&self) -> u32 {
+            self.0
+        }
+
+        fn cull_rect(&self) -> Rectangle {
+            Rectangle::extent(100.0, 40.0)
+        }
+
+        fn approximate_bytes_used(&self) -> usize {
+            64
+        }
+
+        fn any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    #[derive(Debug)]
+    struct TestPicture;
+
+    impl crate::Picture for TestPicture {
+        fn unique_id(&self) -> u32 {
+            1
+        }
+
+        fn cull_rect(&self) -> Rectangle {
+            Rectangle::extent(100.0, 40.0)
+        }
+
+        fn any(&self) -> &dyn Any {
+            self
+        }
+    }
 }
 
 #[cfg(feature = "phlow")]
@@ -787,8 +1940,8 @@ mod extensions {
                 .items::<TiledLayerFigure>(|figure| {
                     phlow_all!(vec![
                         ("Id", phlow!(figure.id())),
-                        ("Offset", phlow!(figure.offset().clone())),
-                        ("Extent", phlow!(figure.extent().clone())),
+                        ("Offset", phlow!(figure.offset())),
+                        ("Extent", phlow!(figure.extent())),
                         ("Picture", phlow!(figure.picture()))
                     ])
                 })
@@ -3,13 +3,25 @@
 #[macro_use]
 extern crate cfg_if;
 
+mod builder;
 mod compositor;
+mod dump;
 mod layers;
+mod optimizer;
+mod paint;
+mod statistics;
 mod types;
+mod validation;
 
 pub use crate::compositor::Compositor;
+pub use builder::*;
+pub use dump::*;
 pub use layers::*;
+pub use optimizer::*;
+pub use paint::*;
+pub use statistics::*;
 pub use types::*;
+pub use validation::*;
 
 cfg_if! {
     if #[cfg(feature = "phlow")] {
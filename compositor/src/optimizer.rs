@@ -0,0 +1,402 @@
+use crate::{
+    ClipLayer, Compositor, DynamicOffsetLayer, ExplicitLayer, Geometry, GeometryLayer, ImageLayer,
+    Layer, LeftoverStateLayer, ListLayer, Matrix, OffsetLayer, OpacityLayer, PictureLayer, Point,
+    ShaderLayer, ShadowLayer, StateCommand, StateCommandType, TextureLayer, TiledLayer,
+    TransformationLayer,
+};
+use std::sync::Arc;
+
+/// The result of optimizing a layer tree
+#[derive(Debug, Clone)]
+pub struct OptimizedLayerTree {
+    layer: Arc<dyn Layer>,
+    layers_before: usize,
+    layers_after: usize,
+}
+
+impl OptimizedLayerTree {
+    pub fn layer(&self) -> &Arc<dyn Layer> {
+        &self.layer
+    }
+
+    pub fn into_layer(self) -> Arc<dyn Layer> {
+        self.layer
+    }
+
+    pub fn layers_before(&self) -> usize {
+        self.layers_before
+    }
+
+    pub fn layers_after(&self) -> usize {
+        self.layers_after
+    }
+
+    /// Canonicalizing leftover state layers may add layers,
+    /// in that case no layers are reported as removed
+    pub fn removed_layers(&self) -> usize {
+        self.layers_before.saturating_sub(self.layers_after)
+    }
+}
+
+/// Rewrite a layer tree into an equivalent one with fewer layers:
+///  - zero offsets, opaque opacity layers, identity transformations and clips without geometry
+///    are replaced by their children;
+///  - containers without children are removed;
+///  - nested offsets are summed and nested opacities are multiplied;
+///  - leftover state layers are replaced by offset and transformation layers, each clip command
+///    is kept in a leftover state layer of its own because clip layers are not drawn as clips.
+///
+/// Layers that did not change are shared with the original tree.
+pub fn optimize_layer_tree(layer: &Arc<dyn Layer>) -> OptimizedLayerTree {
+    let mut layers = LayerTreeOptimizer::default().optimize_layers(std::slice::from_ref(layer));
+
+    let optimized = if layers.len() == 1 {
+        layers.remove(0)
+    } else {
+        OffsetLayer::new().with_layers(layers)
+    };
+
+    OptimizedLayerTree {
+        layers_before: count_all_layers(layer.as_ref()),
+        layers_after: count_all_layers(optimized.as_ref()),
+        layer: optimized,
+    }
+}
+
+fn count_all_layers(layer: &dyn Layer) -> usize {
+    1 + layer
+        .layers()
+        .iter()
+        .map(|layer| count_all_layers(layer.as_ref()))
+        .sum::<usize>()
+}
+
+/// Each `compose_*` appends the optimized replacement of a layer to `output`,
+/// which may be nothing, the layer itself or its children
+#[derive(Debug, Default)]
+struct LayerTreeOptimizer {
+    output: Vec<Arc<dyn Layer>>,
+    /// The layer that is currently being composed, so that it can be reused as is
+    current: Option<Arc<dyn Layer>>,
+}
+
+impl LayerTreeOptimizer {
+    fn optimize_layers(&mut self, layers: &[Arc<dyn Layer>]) -> Vec<Arc<dyn Layer>> {
+        let output = std::mem::take(&mut self.output);
+        for layer in layers {
+            self.current = Some(layer.clone());
+            layer.compose(self);
+        }
+        std::mem::replace(&mut self.output, output)
+    }
+
+    /// Return the original layer if its children did not change,
+    /// otherwise a copy with the optimized children
+    fn rebuild(
+        &self,
+        original: Option<Arc<dyn Layer>>,
+        layer: &dyn Layer,
+        children: Vec<Arc<dyn Layer>>,
+    ) -> Arc<dyn Layer> {
+        let unchanged = children.len() == layer.layers().len()
+            && children
+                .iter()
+                .zip(layer.layers())
+                .all(|(child, original)| Arc::ptr_eq(child, original));
+
+        match original {
+            Some(original) if unchanged => original,
+            _ => layer.with_layers(children),
+        }
+    }
+
+    fn offset(&self, offset: Point, children: Vec<Arc<dyn Layer>>) -> Vec<Arc<dyn Layer>> {
+        if children.is_empty() || offset == Point::zero() {
+            return children;
+        }
+        if let [child] = children.as_slice()
+            && let Some(nested) = child.any().downcast_ref::<OffsetLayer>()
+        {
+            return self.offset(offset + *nested.offset(), nested.layers().to_vec());
+        }
+        vec![OffsetLayer::new_offset(offset).with_layers(children)]
+    }
+
+    fn transformation(
+        &self,
+        matrix: &Matrix,
+        children: Vec<Arc<dyn Layer>>,
+    ) -> Vec<Arc<dyn Layer>> {
+        if children.is_empty() || matrix.is_identity() {
+            return children;
+        }
+        vec![TransformationLayer::new(matrix.clone()).with_layers(children)]
+    }
+
+    fn clip(
+        &self,
+        geometry: &Geometry,
+        offset: Point,
+        children: Vec<Arc<dyn Layer>>,
+    ) -> Vec<Arc<dyn Layer>> {
+        if children.is_empty() || matches!(geometry, Geometry::None) {
+            return children;
+        }
+        vec![
+            LeftoverStateLayer::new(vec![StateCommand::clip(geometry.clone(), offset)])
+                .with_layers(children),
+        ]
+    }
+
+    fn keep_current(&mut self) {
+        if let Some(layer) = self.current.take() {
+            self.output.push(layer);
+        }
+    }
+}
+
+impl Compositor for LayerTreeOptimizer {
+    fn compose(&mut self, layer: Arc<dyn Layer>) {
+        let layers = self.optimize_layers(std::slice::from_ref(&layer));
+        self.output.extend(layers);
+    }
+
+    fn compose_clip(&mut self, layer: &ClipLayer) {
+        let original = self.current.take();
+        let children = self.optimize_layers(layer.layers());
+
+        if children.is_empty() {
+            return;
+        }
+        if matches!(layer.geometry(), Geometry::None) {
+            self.output.extend(children);
+            return;
+        }
+        let layer = self.rebuild(original, layer, children);
+        self.output.push(layer);
+    }
+
+    fn compose_offset(&mut self, layer: &OffsetLayer) {
+        let original = self.current.take();
+        let children = self.optimize_layers(layer.layers());
+
+        if children.is_empty() {
+            return;
+        }
+        let unchanged = children.len() == layer.layers().len()
+            && children
+                .iter()
+                .zip(layer.layers())
+                .all(|(child, original)| Arc::ptr_eq(child, original));
+        let can_be_merged = children.len() == 1 && children[0].any().is::<OffsetLayer>();
+
+        match original {
+            Some(original) if unchanged && !can_be_merged && *layer.offset() != Point::zero() => {
+                self.output.push(original)
+            }
+            _ => {
+                let layers = self.offset(*layer.offset(), children);
+                self.output.extend(layers);
+            }
+        }
+    }
+
+    fn compose_dynamic_offset(&mut self, layer: &DynamicOffsetLayer) {
+        let original = self.current.take();
+        let children = self.optimize_layers(layer.layers());
+
+        if !children.is_empty() {
+            let layer = self.rebuild(original, layer, children);
+            self.output.push(layer);
+        }
+    }
+
+    fn compose_opacity(&mut self, layer: &OpacityLayer) {
+        let original = self.current.take();
+        let children = self.optimize_layers(layer.layers());
+
+        if children.is_empty() {
+            return;
+        }
+        if layer.alpha() == 1.0 {
+            self.output.extend(children);
+            return;
+        }
+        if let [child] = children.as_slice()
+            && let Some(nested) = child.any().downcast_ref::<OpacityLayer>()
+        {
+            let merged = OpacityLayer::new_alpha(layer.alpha() * nested.alpha())
+                .with_layers(nested.layers().to_vec());
+            self.output.push(merged);
+            return;
+        }
+        let layer = self.rebuild(original, layer, children);
+        self.output.push(layer);
+    }
+
+    fn compose_shadow(&mut self, layer: &ShadowLayer) {
+        // the shadow itself is drawn even without children
+        let original = self.current.take();
+        let children = self.optimize_layers(layer.layers());
+
+        let layer = self.rebuild(original, layer, children);
+        self.output.push(layer);
+    }
+
+    fn compose_transformation(&mut self, layer: &TransformationLayer) {
+        let original = self.current.take();
+        let children = self.optimize_layers(layer.layers());
+
+        if children.is_empty() {
+            return;
+        }
+        if layer.matrix().is_identity() {
+            self.output.extend(children);
+            return;
+        }
+        let layer = self.rebuild(original, layer, children);
+        self.output.push(layer);
+    }
+
+    fn compose_picture(&mut self, _layer: &PictureLayer) {
+        self.keep_current();
+    }
+
+    fn compose_leftover(&mut self, layer: &LeftoverStateLayer) {
+        self.current = None;
+        let mut layers = self.optimize_layers(layer.layers());
+
+        // commands are applied in order, so the last one wraps the children directly
+        for command in layer.commands.iter().rev() {
+            layers = match &command.command_type {
+                StateCommandType::Transform(matrix) => {
+                    let transformed = self.transformation(matrix, layers);
+                    self.offset(command.offset, transformed)
+                }
+                StateCommandType::Clip(geometry) => self.clip(geometry, command.offset, layers),
+            };
+        }
+
+        self.output.extend(layers);
+    }
+
+    fn compose_tiled(&mut self, _layer: &TiledLayer) {
+        self.keep_current();
+    }
+
+    fn compose_list(&mut self, _layer: &ListLayer) {
+        self.keep_current();
+    }
+
+    fn compose_explicit(&mut self, _layer: &ExplicitLayer) {
+        self.keep_current();
+    }
+
+    fn compose_texture(&mut self, _layer: &TextureLayer) {
+        self.keep_current();
+    }
+
+    fn compose_image(&mut self, _layer: &ImageLayer) {
+        self.keep_current();
+    }
+
+    fn compose_geometry(&mut self, _layer: &GeometryLayer) {
+        self.keep_current();
+    }
+
+    fn compose_shader(&mut self, _layer: &ShaderLayer) {
+        self.keep_current();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LayerTreeDump, Picture, Rectangle, StateCommand};
+    use std::any::Any;
+
+    #[derive(Debug)]
+    struct TestPicture;
+
+    impl Picture for TestPicture {
+        fn unique_id(&self) -> u32 {
+            1
+        }
+
+        fn cull_rect(&self) -> Rectangle {
+            Rectangle::extent(10.0, 10.0)
+        }
+
+        fn any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    fn picture_layer() -> Arc<dyn Layer> {
+        Arc::new(PictureLayer::new(Arc::new(TestPicture), false))
+    }
+
+    fn dump(layer: &Arc<dyn Layer>) -> String {
+        LayerTreeDump::new(layer.as_ref()).to_string()
+    }
+
+    #[test]
+    pub fn redundant_layers_are_removed() {
+        let root = OffsetLayer::new().with_layers(vec![
+            OpacityLayer::new_alpha(1.0).with_layers(vec![
+                TransformationLayer::new(Matrix::identity())
+                    .with_layers(vec![ClipLayer::none().with_layers(vec![picture_layer()])]),
+            ]),
+            OffsetLayer::new_offset(Point::new(5.0, 5.0)).clone_arc(),
+        ]);
+
+        let optimized = optimize_layer_tree(&root);
+        assert!(optimized.layer().any().is::<PictureLayer>());
+        assert_eq!(optimized.layers_before(), 6);
+        assert_eq!(optimized.removed_layers(), 5);
+    }
+
+    #[test]
+    pub fn nested_offsets_are_summed() {
+        let root = OffsetLayer::new_offset(Point::new(10.0, 0.0)).with_layers(vec![
+            OffsetLayer::new_offset(Point::new(5.0, 20.0)).with_layers(vec![picture_layer()]),
+        ]);
+
+        let optimized = optimize_layer_tree(&root);
+        let offset = optimized
+            .layer()
+            .any()
+            .downcast_ref::<OffsetLayer>()
+            .unwrap();
+        assert_eq!(offset.offset(), &Point::new(15.0, 20.0));
+        assert_eq!(optimized.removed_layers(), 1);
+    }
+
+    #[test]
+    pub fn unchanged_layers_are_shared() {
+        let picture = picture_layer();
+        let root = OpacityLayer::new_alpha(0.5).with_layers(vec![picture.clone()]);
+
+        let optimized = optimize_layer_tree(&root);
+        assert!(Arc::ptr_eq(optimized.layer(), &root));
+        assert_eq!(optimized.removed_layers(), 0);
+    }
+
+    #[test]
+    pub fn leftover_commands_are_canonicalized() {
+        let clip = Geometry::Rectangle(Rectangle::extent(100.0, 100.0));
+        let root = LeftoverStateLayer::new(vec![
+            StateCommand::transform(Matrix::identity(), Point::new(10.0, 10.0)),
+            StateCommand::clip(clip, Point::zero()),
+        ])
+        .with_layers(vec![picture_layer()]);
+
+        let optimized = optimize_layer_tree(&root);
+        assert_eq!(
+            dump(optimized.layer()),
+            "OffsetLayer offset=(10, 10)\n  \
+               LeftoverStateLayer clip=rectangle [0, 0, 100 x 100] at (0, 0)\n    \
+                 PictureLayer id=1 cull=[0, 0, 10 x 10] cache=never\n"
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use crate::{Color, Point, Scalar};
+
+/// A color at a given position of a gradient, where the position is between 0.0 and 1.0
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct GradientStop {
+    position: Scalar,
+    color: Color,
+}
+
+impl GradientStop {
+    pub fn new(position: impl Into<Scalar>, color: Color) -> Self {
+        Self {
+            position: position.into(),
+            color,
+        }
+    }
+
+    pub fn position(&self) -> Scalar {
+        self.position
+    }
+
+    pub fn color(&self) -> &Color {
+        &self.color
+    }
+}
+
+/// Describes how the inside of a geometry or a stroke is painted.
+/// Gradient coordinates are in the local space of the geometry.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub enum Fill {
+    Solid(Color),
+    LinearGradient {
+        start: Point,
+        end: Point,
+        stops: Vec<GradientStop>,
+    },
+    RadialGradient {
+        center: Point,
+        radius: Scalar,
+        stops: Vec<GradientStop>,
+    },
+    /// Angles are in degrees, clockwise starting from the positive x axis
+    SweepGradient {
+        center: Point,
+        start_angle: Scalar,
+        end_angle: Scalar,
+        stops: Vec<GradientStop>,
+    },
+}
+
+impl Default for Fill {
+    fn default() -> Self {
+        Self::Solid(Color::default())
+    }
+}
+
+impl Fill {
+    pub fn solid(color: Color) -> Self {
+        Self::Solid(color)
+    }
+
+    pub fn linear_gradient(start: Point, end: Point, stops: Vec<GradientStop>) -> Self {
+        Self::LinearGradient { start, end, stops }
+    }
+
+    pub fn radial_gradient(
+        center: Point,
+        radius: impl Into<Scalar>,
+        stops: Vec<GradientStop>,
+    ) -> Self {
+        Self::RadialGradient {
+            center,
+            radius: radius.into(),
+            stops,
+        }
+    }
+
+    pub fn sweep_gradient(
+        center: Point,
+        start_angle: impl Into<Scalar>,
+        end_angle: impl Into<Scalar>,
+        stops: Vec<GradientStop>,
+    ) -> Self {
+        Self::SweepGradient {
+            center,
+            start_angle: start_angle.into(),
+            end_angle: end_angle.into(),
+            stops,
+        }
+    }
+
+    /// Return the gradient stops, or an empty slice for a solid fill
+    pub fn stops(&self) -> &[GradientStop] {
+        match self {
+            Fill::Solid(_) => &[],
+            Fill::LinearGradient { stops, .. }
+            | Fill::RadialGradient { stops, .. }
+            | Fill::SweepGradient { stops, .. } => stops.as_slice(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Default)]
+pub enum StrokeCap {
+    #[default]
+    Butt,
+    Round,
+    Square,
+}
+
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Default)]
+pub enum StrokeJoin {
+    #[default]
+    Miter,
+    Round,
+    Bevel,
+}
+
+/// Alternating lengths of dashes and gaps, starting with a dash.
+/// The phase shifts the pattern along the outline.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct StrokeDash {
+    intervals: Vec<Scalar>,
+    phase: Scalar,
+}
+
+impl StrokeDash {
+    pub fn new(intervals: Vec<Scalar>, phase: impl Into<Scalar>) -> Self {
+        Self {
+            intervals,
+            phase: phase.into(),
+        }
+    }
+
+    pub fn intervals(&self) -> &[Scalar] {
+        self.intervals.as_slice()
+    }
+
+    pub fn phase(&self) -> Scalar {
+        self.phase
+    }
+}
+
+/// Describes how the outline of a geometry is painted. The stroke is centered on the outline.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct Stroke {
+    fill: Fill,
+    width: Scalar,
+    cap: StrokeCap,
+    join: StrokeJoin,
+    dash: Option<StrokeDash>,
+}
+
+impl Stroke {
+    pub fn new(fill: Fill, width: impl Into<Scalar>) -> Self {
+        Self {
+            fill,
+            width: width.into(),
+            cap: StrokeCap::default(),
+            join: StrokeJoin::default(),
+            dash: None,
+        }
+    }
+
+    pub fn with_cap(self, cap: StrokeCap) -> Self {
+        Self { cap, ..self }
+    }
+
+    pub fn with_join(self, join: StrokeJoin) -> Self {
+        Self { join, ..self }
+    }
+
+    pub fn with_dash(self, dash: StrokeDash) -> Self {
+        Self {
+            dash: Some(dash),
+            ..self
+        }
+    }
+
+    pub fn fill(&self) -> &Fill {
+        &self.fill
+    }
+
+    pub fn width(&self) -> Scalar {
+        self.width
+    }
+
+    pub fn cap(&self) -> StrokeCap {
+        self.cap
+    }
+
+    pub fn join(&self) -> StrokeJoin {
+        self.join
+    }
+
+    pub fn dash(&self) -> Option<&StrokeDash> {
+        self.dash.as_ref()
+    }
+}
@@ -0,0 +1,268 @@
+use crate::{
+    ClipLayer, Compositor, DynamicOffsetLayer, ExplicitLayer, Geometry, GeometryLayer, ImageLayer,
+    Layer, LeftoverStateLayer, ListLayer, OffsetLayer, OpacityLayer, PictureLayer, ShaderLayer,
+    ShadowLayer, StateCommandType, TextureLayer, TiledLayer, TransformationLayer,
+};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+/// Counts of layers and an estimate of the memory retained by a layer tree.
+/// Pictures shared by several layers are counted once.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LayerStatistics {
+    layer_counts: BTreeMap<&'static str, usize>,
+    max_depth: usize,
+    distinct_pictures: usize,
+    picture_bytes: usize,
+    path_bytes: usize,
+    tile_picture_bytes: usize,
+    figure_picture_bytes: usize,
+}
+
+impl LayerStatistics {
+    /// Walk a given layer tree, including pictures cached by tiled and list layers
+    pub fn collect(layer: &dyn Layer) -> Self {
+        let mut collector = LayerStatisticsCollector::default();
+        layer.compose(&mut collector);
+        collector.statistics.distinct_pictures = collector.pictures.len() + collector.row_pictures;
+        collector.statistics
+    }
+
+    /// The total amount of layers in the tree
+    pub fn count_layers(&self) -> usize {
+        self.layer_counts.values().sum()
+    }
+
+    /// The amount of layers of a given type, for example `"PictureLayer"`
+    pub fn count_layers_of_type(&self, layer_type: &str) -> usize {
+        self.layer_counts.get(layer_type).copied().unwrap_or(0)
+    }
+
+    /// Layer types present in the tree with their counts, sorted by the type name
+    pub fn layer_counts(&self) -> impl Iterator<Item = (&'static str, usize)> + '_ {
+        self.layer_counts
+            .iter()
+            .map(|(layer_type, count)| (*layer_type, *count))
+    }
+
+    /// The amount of layers on the longest path from the root, the root alone has a depth of 1
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    pub fn count_distinct_pictures(&self) -> usize {
+        self.distinct_pictures
+    }
+
+    /// Bytes retained by pictures of picture layers and list rows.
+    /// A picture shared by several list rows is counted once per row
+    pub fn picture_bytes(&self) -> usize {
+        self.picture_bytes
+    }
+
+    /// Bytes retained by paths of clips, shadows and geometries
+    pub fn path_bytes(&self) -> usize {
+        self.path_bytes
+    }
+
+    /// Bytes retained by tile pictures cached by tiled layers
+    pub fn tile_picture_bytes(&self) -> usize {
+        self.tile_picture_bytes
+    }
+
+    /// Bytes retained by pictures of tiled layer figures
+    pub fn figure_picture_bytes(&self) -> usize {
+        self.figure_picture_bytes
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.picture_bytes + self.path_bytes + self.tile_picture_bytes + self.figure_picture_bytes
+    }
+}
+
+#[derive(Debug, Default)]
+struct LayerStatisticsCollector {
+    statistics: LayerStatistics,
+    depth: usize,
+    pictures: HashSet<u32>,
+    /// pictures of list rows are counted per row rather than by their identity
+    row_pictures: usize,
+}
+
+impl LayerStatisticsCollector {
+    fn visit(&mut self, layer_type: &'static str, layers: &[Arc<dyn Layer>]) {
+        *self.statistics.layer_counts.entry(layer_type).or_default() += 1;
+
+        self.depth += 1;
+        self.statistics.max_depth = self.statistics.max_depth.max(self.depth);
+        for layer in layers {
+            layer.compose(self);
+        }
+        self.depth -= 1;
+    }
+
+    /// Return the size of a picture the first time it is seen and 0 afterwards
+    fn picture_bytes(&mut self, picture: &PictureLayer) -> usize {
+        if self.pictures.insert(picture.picture().unique_id()) {
+            picture.picture().approximate_bytes_used()
+        } else {
+            0
+        }
+    }
+
+    fn add_geometry(&mut self, geometry: &Geometry) {
+        if let Geometry::Path(path) = geometry {
+            self.statistics.path_bytes += path.approximate_bytes_used();
+        }
+    }
+}
+
+impl Compositor for LayerStatisticsCollector {
+    fn compose(&mut self, layer: Arc<dyn Layer>) {
+        layer.compose(self);
+    }
+
+    fn compose_clip(&mut self, layer: &ClipLayer) {
+        self.add_geometry(layer.geometry());
+        self.visit("ClipLayer", layer.layers());
+    }
+
+    fn compose_offset(&mut self, layer: &OffsetLayer) {
+        self.visit("OffsetLayer", layer.layers());
+    }
+
+    fn compose_dynamic_offset(&mut self, layer: &DynamicOffsetLayer) {
+        self.visit("DynamicOffsetLayer", layer.layers());
+    }
+
+    fn compose_opacity(&mut self, layer: &OpacityLayer) {
+        self.visit("OpacityLayer", layer.layers());
+    }
+
+    fn compose_shadow(&mut self, layer: &ShadowLayer) {
+        self.add_geometry(layer.shadow().geometry());
+        self.visit("ShadowLayer", layer.layers());
+    }
+
+    fn compose_transformation(&mut self, layer: &TransformationLayer) {
+        self.visit("TransformationLayer", layer.layers());
+    }
+
+    fn compose_picture(&mut self, layer: &PictureLayer) {
+        self.statistics.picture_bytes += self.picture_bytes(layer);
+        self.visit("PictureLayer", layer.layers());
+    }
+
+    fn compose_leftover(&mut self, layer: &LeftoverStateLayer) {
+        for command in &layer.commands {
+            if let StateCommandType::Clip(geometry) = &command.command_type {
+                self.add_geometry(geometry);
+            }
+        }
+        self.visit("LeftoverStateLayer", layer.layers());
+    }
+
+    fn compose_tiled(&mut self, layer: &TiledLayer) {
+        for figure in layer.figures() {
+            if let Some(picture) = figure.get_picture() {
+                self.statistics.figure_picture_bytes += self.picture_bytes(&picture);
+            }
+        }
+        for picture in layer.tile_pictures() {
+            self.statistics.tile_picture_bytes += self.picture_bytes(&picture);
+        }
+        self.visit("TiledLayer", layer.layers());
+    }
+
+    /// Rows keep running totals of their pictures, so a list is not walked row by row
+    fn compose_list(&mut self, layer: &ListLayer) {
+        self.statistics.picture_bytes += layer.row_picture_bytes();
+        self.row_pictures += layer.count_row_pictures();
+        self.visit("ListLayer", layer.layers());
+    }
+
+    fn compose_explicit(&mut self, layer: &ExplicitLayer) {
+        self.visit("ExplicitLayer", layer.layers());
+    }
+
+    fn compose_texture(&mut self, layer: &TextureLayer) {
+        self.visit("TextureLayer", layer.layers());
+    }
+
+    fn compose_image(&mut self, layer: &ImageLayer) {
+        self.visit("ImageLayer", layer.layers());
+    }
+
+    fn compose_geometry(&mut self, layer: &GeometryLayer) {
+        self.add_geometry(layer.geometry());
+        self.visit("GeometryLayer", layer.layers());
+    }
+
+    fn compose_shader(&mut self, layer: &ShaderLayer) {
+        self.add_geometry(layer.geometry());
+        self.visit("ShaderLayer", layer.layers());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Picture, Point, Rectangle};
+    use std::any::Any;
+
+    #[derive(Debug)]
+    struct TestPicture(u32);
+
+    impl Picture for TestPicture {
+        fn unique_id(&self) -> u32 {
+            self.0
+        }
+
+        fn cull_rect(&self) -> Rectangle {
+            Rectangle::extent(10.0, 10.0)
+        }
+
+        fn approximate_bytes_used(&self) -> usize {
+            100
+        }
+
+        fn any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    fn picture_layer(id: u32) -> Arc<dyn Layer> {
+        Arc::new(PictureLayer::new(Arc::new(TestPicture(id)), false))
+    }
+
+    #[test]
+    pub fn counts_and_depth() {
+        let root = OffsetLayer::new().with_layers(vec![
+            picture_layer(1),
+            OffsetLayer::new_offset(Point::new(10.0, 0.0)).with_layers(vec![
+                OpacityLayer::new_alpha(0.5).with_layers(vec![picture_layer(2)]),
+            ]),
+        ]);
+
+        let statistics = LayerStatistics::collect(root.as_ref());
+        assert_eq!(statistics.count_layers(), 5);
+        assert_eq!(statistics.count_layers_of_type("OffsetLayer"), 2);
+        assert_eq!(statistics.count_layers_of_type("PictureLayer"), 2);
+        assert_eq!(statistics.count_layers_of_type("ClipLayer"), 0);
+        assert_eq!(statistics.max_depth(), 4);
+    }
+
+    #[test]
+    pub fn shared_pictures_are_counted_once() {
+        let root = OffsetLayer::new().with_layers(vec![
+            picture_layer(1),
+            picture_layer(1),
+            picture_layer(2),
+        ]);
+
+        let statistics = LayerStatistics::collect(root.as_ref());
+        assert_eq!(statistics.count_distinct_pictures(), 2);
+        assert_eq!(statistics.picture_bytes(), 200);
+        assert_eq!(statistics.total_bytes(), 200);
+    }
+}
@@ -152,6 +152,45 @@ impl Geometry {
             Geometry::Path(path) => path.bounds(),
         }
     }
+
+    /// Return the geometry moved by a given offset, or `None` if it is a path
+    /// that does not support translation
+    pub fn translate(&self, offset: &Point) -> Option<Self> {
+        Some(match self {
+            Geometry::None => Geometry::None,
+            Geometry::Rectangle(rectangle) => Geometry::Rectangle(rectangle.translate(offset)),
+            Geometry::RoundedRectangle(rounded_rectangle) => {
+                Geometry::RoundedRectangle(rounded_rectangle.translate(offset))
+            }
+            Geometry::Circle(circle) => Geometry::Circle(circle.translate(offset)),
+            Geometry::Path(path) => Geometry::Path(path.translate(offset)?),
+        })
+    }
+}
+
+impl Display for Geometry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Geometry::None => write!(f, "none"),
+            Geometry::Rectangle(rectangle) => write!(f, "rectangle {}", rectangle),
+            Geometry::RoundedRectangle(rounded_rectangle) => {
+                let [top_left, top_right, bottom_right, bottom_left] = rounded_rectangle.radii();
+                write!(
+                    f,
+                    "rounded rectangle {} radii {} {} {} {}",
+                    rounded_rectangle.rectangle(),
+                    top_left,
+                    top_right,
+                    bottom_right,
+                    bottom_left
+                )
+            }
+            Geometry::Circle(circle) => {
+                write!(f, "circle {} radius {}", circle.center(), circle.radius())
+            }
+            Geometry::Path(path) => write!(f, "path {}", path.bounds()),
+        }
+    }
 }
 
 impl Point {
@@ -370,6 +409,19 @@ impl Rectangle {
     }
 }
 
+impl Display for Rectangle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}, {}, {} x {}]",
+            self.left(),
+            self.top(),
+            self.width(),
+            self.height()
+        )
+    }
+}
+
 impl Matrix {
     pub fn from_9(buffer: [Scalar; 9usize]) -> Self {
         Self(buffer)
@@ -377,6 +429,25 @@ impl Matrix {
     pub fn get_9(&self) -> &[Scalar; 9usize] {
         &self.0
     }
+
+    pub fn identity() -> Self {
+        Self([1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0].map(Scalar::from))
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::identity()
+    }
+}
+
+impl Display for Matrix {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let m = &self.0;
+        write!(
+            f,
+            "[{}, {}, {}; {}, {}, {}; {}, {}, {}]",
+            m[0], m[1], m[2], m[3], m[4], m[5], m[6], m[7], m[8]
+        )
+    }
 }
 
 impl Radius {
@@ -397,6 +468,12 @@ impl Radius {
     }
 }
 
+impl Display for Radius {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}, {})", self.width(), self.height())
+    }
+}
+
 impl Color {
     pub fn from_argb(argb: u32) -> Self {
         Self { argb }
@@ -407,6 +484,12 @@ impl Color {
     }
 }
 
+impl Display for Color {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{:08X}", self.argb)
+    }
+}
+
 impl Path {
     pub fn new(inner: Box<dyn VectorPath>) -> Self {
         Self(inner)
@@ -419,13 +502,30 @@ impl Path {
     pub fn bounds(&self) -> Rectangle {
         self.0.bounds()
     }
+
+    pub fn translate(&self, offset: &Point) -> Option<Self> {
+        self.0.translate(offset).map(Self)
+    }
+
+    pub fn approximate_bytes_used(&self) -> usize {
+        self.0.approximate_bytes_used()
+    }
 }
 
 pub trait VectorPath: Send + Sync + Debug {
     fn bounds(&self) -> Rectangle;
+    /// Return the path moved by a given offset. Paths that can not be translated return `None`,
+    /// and shadows of such paths are then cached at their absolute position
+    fn translate(&self, _offset: &Point) -> Option<Box<dyn VectorPath>> {
+        None
+    }
     fn clone_box(&self) -> Box<dyn VectorPath>;
     fn eq_box(&self, other: &Box<dyn VectorPath>) -> bool;
     fn hash_box(&self, state: &mut DefaultHasher);
+    /// An estimate of the memory retained by the path, in bytes
+    fn approximate_bytes_used(&self) -> usize {
+        0
+    }
     fn any(&self) -> &dyn Any;
 }
 
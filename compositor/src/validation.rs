@@ -0,0 +1,533 @@
+use crate::{
+    ClipLayer, Compositor, Drawable, DynamicOffsetLayer, ExplicitLayer, Extent, Fill, Geometry,
+    GeometryLayer, ImageData, ImageLayer, Layer, LeftoverStateLayer, ListLayer, Matrix,
+    OffsetLayer, OpacityLayer, Path, Picture, PictureLayer, Point, Rectangle, Scalar, ShaderLayer,
+    ShadowLayer, StateCommandType, TextureLayer, TiledLayer, TransformationLayer,
+};
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::Arc;
+
+/// Child indices leading from the root of the validated tree to a layer.
+/// An empty path points to the root itself.
+pub type LayerPath = Vec<usize>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayerDiagnosticKind {
+    /// A matrix contains NaN or infinite values
+    NonFiniteMatrix,
+    /// An offset or a camera position contains NaN or infinite values
+    NonFiniteOffset,
+    /// The bounds of a geometry contain NaN or infinite values
+    NonFiniteGeometry,
+    /// A geometry, an extent or a viewport has a negative width or height
+    NegativeExtent,
+    /// Alpha is NaN or outside of [0, 1]
+    InvalidAlpha(f32),
+    /// A shadow radius, a stroke width or a scale factor is negative or not finite
+    InvalidScalar(f32),
+    /// A texture with zero width or height
+    ZeroSizedTexture,
+    /// A tiled layer with a zero or negative tile width or height
+    ZeroTileExtent,
+    /// Raw image pixels with zero width or height
+    EmptyImage,
+    /// A shader uniform contains NaN or infinite values
+    NonFiniteUniform(String),
+    /// A picture that the backend can not draw
+    UnsupportedPicture,
+    /// A path that the backend can not draw
+    UnsupportedPath,
+    /// A drawable that the backend can not draw
+    UnsupportedDrawable,
+}
+
+impl Display for LayerDiagnosticKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayerDiagnosticKind::NonFiniteMatrix => write!(f, "matrix is not finite"),
+            LayerDiagnosticKind::NonFiniteOffset => write!(f, "offset is not finite"),
+            LayerDiagnosticKind::NonFiniteGeometry => write!(f, "geometry is not finite"),
+            LayerDiagnosticKind::NegativeExtent => write!(f, "extent is negative"),
+            LayerDiagnosticKind::InvalidAlpha(alpha) => write!(f, "invalid alpha {}", alpha),
+            LayerDiagnosticKind::InvalidScalar(value) => write!(f, "invalid value {}", value),
+            LayerDiagnosticKind::ZeroSizedTexture => write!(f, "texture has zero size"),
+            LayerDiagnosticKind::ZeroTileExtent => write!(f, "tile extent is zero"),
+            LayerDiagnosticKind::EmptyImage => write!(f, "image has zero size"),
+            LayerDiagnosticKind::NonFiniteUniform(name) => {
+                write!(f, "uniform {} is not finite", name)
+            }
+            LayerDiagnosticKind::UnsupportedPicture => write!(f, "picture is not supported"),
+            LayerDiagnosticKind::UnsupportedPath => write!(f, "path is not supported"),
+            LayerDiagnosticKind::UnsupportedDrawable => write!(f, "drawable is not supported"),
+        }
+    }
+}
+
+/// A problem found in a layer, with the path to that layer from the root of the tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerDiagnostic {
+    path: LayerPath,
+    layer_type: &'static str,
+    kind: LayerDiagnosticKind,
+    /// Points to a part of the layer that is not a layer itself, for example a figure of a tiled layer
+    detail: Option<String>,
+}
+
+impl LayerDiagnostic {
+    pub fn path(&self) -> &[usize] {
+        self.path.as_slice()
+    }
+
+    pub fn layer_type(&self) -> &'static str {
+        self.layer_type
+    }
+
+    pub fn kind(&self) -> &LayerDiagnosticKind {
+        &self.kind
+    }
+
+    pub fn detail(&self) -> Option<&str> {
+        self.detail.as_deref()
+    }
+}
+
+impl Display for LayerDiagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {:?}", self.layer_type, self.path)?;
+        if let Some(detail) = &self.detail {
+            write!(f, " ({})", detail)?;
+        }
+        write!(f, ": {}", self.kind)
+    }
+}
+
+/// Returned by backends that refuse to compose a tree with diagnostics
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidLayerTree(pub Vec<LayerDiagnostic>);
+
+impl InvalidLayerTree {
+    pub fn diagnostics(&self) -> &[LayerDiagnostic] {
+        self.0.as_slice()
+    }
+}
+
+impl Display for InvalidLayerTree {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} invalid layer(s)", self.0.len())?;
+        for diagnostic in &self.0 {
+            write!(f, "\n  {}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for InvalidLayerTree {}
+
+/// Backend specific checks, for example whether a picture was recorded by the same backend
+pub trait ValidationRules: Debug {
+    fn is_supported_picture(&self, _picture: &dyn Picture) -> bool {
+        true
+    }
+
+    fn is_supported_path(&self, _path: &Path) -> bool {
+        true
+    }
+
+    fn is_supported_drawable(&self, _drawable: &dyn Drawable) -> bool {
+        true
+    }
+}
+
+/// Accepts pictures, paths and drawables of any backend
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultValidationRules;
+
+impl ValidationRules for DefaultValidationRules {}
+
+/// Validate a layer tree with backend independent rules
+pub fn validate(layer: &dyn Layer) -> Vec<LayerDiagnostic> {
+    LayerValidator::new(&DefaultValidationRules).validate(layer)
+}
+
+/// Walks a layer tree collecting diagnostics instead of drawing it
+#[derive(Debug)]
+pub struct LayerValidator<'rules> {
+    rules: &'rules dyn ValidationRules,
+    path: LayerPath,
+    diagnostics: Vec<LayerDiagnostic>,
+}
+
+impl<'rules> LayerValidator<'rules> {
+    pub fn new(rules: &'rules dyn ValidationRules) -> Self {
+        Self {
+            rules,
+            path: vec![],
+            diagnostics: vec![],
+        }
+    }
+
+    pub fn validate(mut self, layer: &dyn Layer) -> Vec<LayerDiagnostic> {
+        layer.compose(&mut self);
+        self.diagnostics
+    }
+
+    fn report(&mut self, layer_type: &'static str, kind: LayerDiagnosticKind) {
+        self.report_detail(layer_type, kind, None);
+    }
+
+    fn report_detail(
+        &mut self,
+        layer_type: &'static str,
+        kind: LayerDiagnosticKind,
+        detail: Option<String>,
+    ) {
+        self.diagnostics.push(LayerDiagnostic {
+            path: self.path.clone(),
+            layer_type,
+            kind,
+            detail,
+        });
+    }
+
+    fn validate_children(&mut self, layers: &[Arc<dyn Layer>]) {
+        for (index, layer) in layers.iter().enumerate() {
+            self.path.push(index);
+            layer.compose(self);
+            self.path.pop();
+        }
+    }
+
+    fn check_offset(&mut self, layer_type: &'static str, point: &Point) {
+        if !is_finite(point.x()) || !is_finite(point.y()) {
+            self.report(layer_type, LayerDiagnosticKind::NonFiniteOffset);
+        }
+    }
+
+    fn check_matrix(&mut self, layer_type: &'static str, matrix: &Matrix) {
+        if !matrix.get_9().iter().copied().all(is_finite) {
+            self.report(layer_type, LayerDiagnosticKind::NonFiniteMatrix);
+        }
+    }
+
+    fn check_extent(&mut self, layer_type: &'static str, extent: &Extent) {
+        if !is_finite(extent.width()) || !is_finite(extent.height()) {
+            self.report(layer_type, LayerDiagnosticKind::NonFiniteGeometry);
+        } else if extent.width().0 < 0.0 || extent.height().0 < 0.0 {
+            self.report(layer_type, LayerDiagnosticKind::NegativeExtent);
+        }
+    }
+
+    fn check_rectangle(&mut self, layer_type: &'static str, rectangle: &Rectangle) {
+        let values = [
+            rectangle.left(),
+            rectangle.top(),
+            rectangle.width(),
+            rectangle.height(),
+        ];
+        if !values.into_iter().all(is_finite) {
+            self.report(layer_type, LayerDiagnosticKind::NonFiniteGeometry);
+        } else if rectangle.width().0 < 0.0 || rectangle.height().0 < 0.0 {
+            self.report(layer_type, LayerDiagnosticKind::NegativeExtent);
+        }
+    }
+
+    fn check_geometry(&mut self, layer_type: &'static str, geometry: &Geometry) {
+        if let Geometry::Path(path) = geometry
+            && !self.rules.is_supported_path(path)
+        {
+            self.report(layer_type, LayerDiagnosticKind::UnsupportedPath);
+            // the bounds of an unsupported path are not trustworthy
+            return;
+        }
+        if !matches!(geometry, Geometry::None) {
+            self.check_rectangle(layer_type, &geometry.bounds());
+        }
+    }
+
+    fn check_positive_scalar(&mut self, layer_type: &'static str, value: Scalar) {
+        if !is_finite(value) || value.0 < 0.0 {
+            self.report(layer_type, LayerDiagnosticKind::InvalidScalar(value.into()));
+        }
+    }
+
+    fn check_fill(&mut self, layer_type: &'static str, fill: &Fill) {
+        let points = match fill {
+            Fill::Solid(_) => vec![],
+            Fill::LinearGradient { start, end, .. } => vec![start, end],
+            Fill::RadialGradient { center, radius, .. } => {
+                self.check_positive_scalar(layer_type, *radius);
+                vec![center]
+            }
+            Fill::SweepGradient {
+                center,
+                start_angle,
+                end_angle,
+                ..
+            } => {
+                if !is_finite(*start_angle) || !is_finite(*end_angle) {
+                    self.report(layer_type, LayerDiagnosticKind::NonFiniteGeometry);
+                }
+                vec![center]
+            }
+        };
+        for point in points {
+            if !is_finite(point.x()) || !is_finite(point.y()) {
+                self.report(layer_type, LayerDiagnosticKind::NonFiniteGeometry);
+            }
+        }
+        if !fill.stops().iter().all(|stop| is_finite(stop.position())) {
+            self.report(layer_type, LayerDiagnosticKind::NonFiniteGeometry);
+        }
+    }
+
+    fn check_picture(
+        &mut self,
+        layer_type: &'static str,
+        picture: &PictureLayer,
+        detail: Option<String>,
+    ) {
+        if !self.rules.is_supported_picture(picture.picture().as_ref()) {
+            self.report_detail(layer_type, LayerDiagnosticKind::UnsupportedPicture, detail);
+        }
+    }
+}
+
+fn is_finite(value: Scalar) -> bool {
+    value.0.is_finite()
+}
+
+impl<'rules> Compositor for LayerValidator<'rules> {
+    fn compose(&mut self, layer: Arc<dyn Layer>) {
+        self.path.clear();
+        layer.compose(self);
+    }
+
+    fn compose_clip(&mut self, layer: &ClipLayer) {
+        self.check_offset("ClipLayer", layer.offset());
+        self.check_geometry("ClipLayer", layer.geometry());
+        self.validate_children(layer.layers());
+    }
+
+    fn compose_offset(&mut self, layer: &OffsetLayer) {
+        self.check_offset("OffsetLayer", layer.offset());
+        self.validate_children(layer.layers());
+    }
+
+    fn compose_dynamic_offset(&mut self, layer: &DynamicOffsetLayer) {
+        if let Some(offset) = layer.offset() {
+            self.check_offset("DynamicOffsetLayer", &offset);
+        }
+        self.validate_children(layer.layers());
+    }
+
+    fn compose_opacity(&mut self, layer: &OpacityLayer) {
+        let alpha = layer.alpha();
+        if !(0.0..=1.0).contains(&alpha) {
+            self.report("OpacityLayer", LayerDiagnosticKind::InvalidAlpha(alpha));
+        }
+        self.validate_children(layer.layers());
+    }
+
+    fn compose_shadow(&mut self, layer: &ShadowLayer) {
+        let shadow = layer.shadow();
+        self.check_offset("ShadowLayer", shadow.offset());
+        self.check_positive_scalar("ShadowLayer", shadow.radius().width());
+        self.check_positive_scalar("ShadowLayer", shadow.radius().height());
+        self.check_geometry("ShadowLayer", shadow.geometry());
+        self.validate_children(layer.layers());
+    }
+
+    fn compose_transformation(&mut self, layer: &TransformationLayer) {
+        self.check_matrix("TransformationLayer", layer.matrix());
+        self.validate_children(layer.layers());
+    }
+
+    fn compose_picture(&mut self, layer: &PictureLayer) {
+        self.check_picture("PictureLayer", layer, None);
+    }
+
+    fn compose_leftover(&mut self, layer: &LeftoverStateLayer) {
+        for command in &layer.commands {
+            self.check_offset("LeftoverStateLayer", &command.offset);
+            match &command.command_type {
+                StateCommandType::Transform(matrix) => {
+                    self.check_matrix("LeftoverStateLayer", matrix)
+                }
+                StateCommandType::Clip(geometry) => {
+                    self.check_geometry("LeftoverStateLayer", geometry)
+                }
+            }
+        }
+        self.validate_children(layer.layers());
+    }
+
+    fn compose_tiled(&mut self, layer: &TiledLayer) {
+        self.check_offset("TiledLayer", layer.camera_position());
+        self.check_extent("TiledLayer", layer.viewport_extent());
+
+        let tile_extent = layer.tile_extent();
+        if !(tile_extent.width().0 > 0.0 && tile_extent.height().0 > 0.0) {
+            self.report("TiledLayer", LayerDiagnosticKind::ZeroTileExtent);
+        }
+
+        let scale = layer.scale_factor().value();
+        if !(scale.is_finite() && scale > 0.0) {
+            self.report("TiledLayer", LayerDiagnosticKind::InvalidScalar(scale));
+        }
+
+        for figure in layer.figures() {
+            if let Some(picture) = figure.get_picture() {
+                self.check_picture(
+                    "TiledLayer",
+                    &picture,
+                    Some(format!("figure {}", figure.id())),
+                );
+            }
+        }
+    }
+
+    fn compose_list(&mut self, layer: &ListLayer) {
+        self.check_offset("ListLayer", layer.camera_position());
+        self.check_extent("ListLayer", layer.viewport_extent());
+
+        for index in 0..layer.row_count() as u32 {
+            if let Some(picture) = layer.find_row(index).and_then(|row| row.get_picture()) {
+                self.check_picture("ListLayer", &picture, Some(format!("row {}", index)));
+            }
+        }
+    }
+
+    fn compose_explicit(&mut self, layer: &ExplicitLayer) {
+        if !self.rules.is_supported_drawable(layer.drawable().as_ref()) {
+            self.report("ExplicitLayer", LayerDiagnosticKind::UnsupportedDrawable);
+        }
+    }
+
+    fn compose_texture(&mut self, layer: &TextureLayer) {
+        if layer.width() == 0 || layer.height() == 0 {
+            self.report("TextureLayer", LayerDiagnosticKind::ZeroSizedTexture);
+        }
+    }
+
+    fn compose_image(&mut self, layer: &ImageLayer) {
+        self.check_extent("ImageLayer", layer.extent());
+        if let ImageData::Rgba { width, height, .. } = layer.source().data()
+            && (*width == 0 || *height == 0)
+        {
+            self.report("ImageLayer", LayerDiagnosticKind::EmptyImage);
+        }
+        if let Some(center) = layer.center() {
+            self.check_rectangle("ImageLayer", center);
+        }
+    }
+
+    fn compose_geometry(&mut self, layer: &GeometryLayer) {
+        self.check_geometry("GeometryLayer", layer.geometry());
+        if let Some(fill) = layer.fill() {
+            self.check_fill("GeometryLayer", fill);
+        }
+        if let Some(stroke) = layer.stroke() {
+            self.check_positive_scalar("GeometryLayer", stroke.width());
+            self.check_fill("GeometryLayer", stroke.fill());
+        }
+    }
+
+    fn compose_shader(&mut self, layer: &ShaderLayer) {
+        self.check_geometry("ShaderLayer", layer.geometry());
+        for (name, value) in layer.uniforms() {
+            if !value.as_slice().iter().all(|value| value.is_finite()) {
+                self.report(
+                    "ShaderLayer",
+                    LayerDiagnosticKind::NonFiniteUniform(name.clone()),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::any::Any;
+
+    #[derive(Debug)]
+    struct TestPicture;
+
+    impl Picture for TestPicture {
+        fn unique_id(&self) -> u32 {
+            1
+        }
+
+        fn cull_rect(&self) -> Rectangle {
+            Rectangle::extent(10.0, 10.0)
+        }
+
+        fn any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    #[derive(Debug)]
+    struct RejectPictures;
+
+    impl ValidationRules for RejectPictures {
+        fn is_supported_picture(&self, _picture: &dyn Picture) -> bool {
+            false
+        }
+    }
+
+    fn picture_layer() -> Arc<dyn Layer> {
+        Arc::new(PictureLayer::new(Arc::new(TestPicture), false))
+    }
+
+    #[test]
+    pub fn valid_tree() {
+        let root = OffsetLayer::new_offset(Point::new(10.0, 10.0))
+            .with_layers(vec![picture_layer(), picture_layer()]);
+
+        assert!(validate(root.as_ref()).is_empty());
+    }
+
+    #[test]
+    pub fn nan_matrix_path() {
+        let mut matrix = [Scalar::from(0.0); 9];
+        matrix[0] = Scalar::from(f32::NAN);
+
+        let transformation = TransformationLayer::new(Matrix::from_9(matrix)).clone_arc();
+        let root = OffsetLayer::new().with_layers(vec![picture_layer(), transformation]);
+
+        let diagnostics = validate(root.as_ref());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].path(), &[1]);
+        assert_eq!(diagnostics[0].layer_type(), "TransformationLayer");
+        assert_eq!(diagnostics[0].kind(), &LayerDiagnosticKind::NonFiniteMatrix);
+    }
+
+    #[test]
+    pub fn zero_tile_extent() {
+        let tiled = TiledLayer::new(
+            Point::zero(),
+            Extent::new(100.0, 100.0),
+            Extent::new(0.0, 50.0),
+        );
+
+        let diagnostics = validate(&tiled);
+        assert_eq!(diagnostics[0].kind(), &LayerDiagnosticKind::ZeroTileExtent);
+        assert!(diagnostics[0].path().is_empty());
+    }
+
+    #[test]
+    pub fn unsupported_picture() {
+        let root = OffsetLayer::new()
+            .with_layers(vec![OffsetLayer::new().with_layers(vec![picture_layer()])]);
+
+        let diagnostics = LayerValidator::new(&RejectPictures).validate(root.as_ref());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].path(), &[0, 0]);
+        assert_eq!(
+            diagnostics[0].kind(),
+            &LayerDiagnosticKind::UnsupportedPicture
+        );
+    }
+}